@@ -0,0 +1,5 @@
+CREATE TABLE region_aliases (
+	id			SERIAL PRIMARY KEY,
+	alias		VARCHAR NOT NULL UNIQUE,
+	region		VARCHAR NOT NULL REFERENCES regions (name) ON DELETE CASCADE
+);
@@ -0,0 +1,3 @@
+ALTER TABLE regions
+	DROP COLUMN latitude,
+	DROP COLUMN longitude;
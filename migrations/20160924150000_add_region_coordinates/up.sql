@@ -0,0 +1,17 @@
+ALTER TABLE regions
+	ADD COLUMN latitude		DOUBLE PRECISION,
+	ADD COLUMN longitude	DOUBLE PRECISION;
+
+-- Rough centers of the default regions, used for distance estimates.
+UPDATE regions SET latitude = 39.0,  longitude = -77.5  WHERE name = 'naeast';
+UPDATE regions SET latitude = 37.4,  longitude = -122.0 WHERE name = 'nawest';
+UPDATE regions SET latitude = 52.2,  longitude = 21.0   WHERE name = 'eueast';
+UPDATE regions SET latitude = 50.1,  longitude = 8.7    WHERE name = 'euwest';
+UPDATE regions SET latitude = 19.1,  longitude = 72.9   WHERE name = 'aswest';
+UPDATE regions SET latitude = 1.3,   longitude = 103.8  WHERE name = 'aseast';
+UPDATE regions SET latitude = -31.9, longitude = 115.9  WHERE name = 'auwest';
+UPDATE regions SET latitude = -33.9, longitude = 151.2  WHERE name = 'aueast';
+UPDATE regions SET latitude = 4.7,   longitude = -74.1  WHERE name = 'sanorth';
+UPDATE regions SET latitude = -23.5, longitude = -46.6  WHERE name = 'sasouth';
+UPDATE regions SET latitude = 30.0,  longitude = 31.2   WHERE name = 'afnorth';
+UPDATE regions SET latitude = -26.2, longitude = 28.0   WHERE name = 'afsouth';
@@ -0,0 +1,2 @@
+DROP TABLE player_count_rollups;
+DROP TABLE player_counts;
@@ -0,0 +1,24 @@
+CREATE TABLE player_counts (
+    id                      SERIAL PRIMARY KEY,
+    server_id               INT NOT NULL REFERENCES game_servers (id) ON DELETE CASCADE,
+    region                  VARCHAR NOT NULL,
+    current_users           INT NOT NULL,
+    recorded_at             TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX player_counts_server_time ON player_counts (server_id, recorded_at);
+
+CREATE TABLE player_count_rollups (
+    id                      SERIAL PRIMARY KEY,
+    server_id               INT NOT NULL REFERENCES game_servers (id) ON DELETE CASCADE,
+    region                  VARCHAR NOT NULL,
+    resolution              VARCHAR NOT NULL CHECK (resolution IN ('hour', 'day')),
+    bucket                  TIMESTAMP NOT NULL,
+    average_users           REAL NOT NULL,
+    min_users               INT NOT NULL,
+    max_users               INT NOT NULL,
+    samples                 INT NOT NULL,
+    UNIQUE (server_id, resolution, bucket)
+);
+
+CREATE INDEX player_count_rollups_region_time ON player_count_rollups (region, resolution, bucket);
@@ -0,0 +1,4 @@
+DROP INDEX game_servers_name_trigram;
+DROP INDEX game_servers_search_document;
+DROP FUNCTION fula_search_document(VARCHAR, VARCHAR[], VARCHAR);
+ALTER TABLE game_servers DROP COLUMN motd;
@@ -0,0 +1,16 @@
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+ALTER TABLE game_servers ADD COLUMN motd VARCHAR NOT NULL DEFAULT '';
+
+-- array_to_string is only STABLE, so the searched document gets an IMMUTABLE wrapper
+-- that can be used in an index.
+CREATE FUNCTION fula_search_document(name VARCHAR, tags VARCHAR[], motd VARCHAR)
+RETURNS tsvector AS $$
+    SELECT setweight(to_tsvector('simple', name), 'A')
+        || setweight(to_tsvector('simple', array_to_string(tags, ' ')), 'B')
+        || setweight(to_tsvector('simple', motd), 'C')
+$$ LANGUAGE SQL IMMUTABLE;
+
+CREATE INDEX game_servers_search_document ON game_servers
+    USING gin (fula_search_document(name, tags, motd));
+CREATE INDEX game_servers_name_trigram ON game_servers USING gin (name gin_trgm_ops);
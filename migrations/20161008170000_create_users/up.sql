@@ -0,0 +1,6 @@
+CREATE TABLE users (
+    id                      SERIAL PRIMARY KEY,
+    name                    VARCHAR NOT NULL UNIQUE,
+    token                   VARCHAR NOT NULL UNIQUE,
+    created_at              TIMESTAMP NOT NULL DEFAULT now()
+);
@@ -0,0 +1,2 @@
+DROP TABLE play_history;
+DROP TABLE favorites;
@@ -0,0 +1,21 @@
+-- The ip and name of a server are copied into these lists, so that entries of servers
+-- that have since been deleted can still be shown.
+CREATE TABLE favorites (
+    id                      SERIAL PRIMARY KEY,
+    user_id                 INT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    server_id               INT REFERENCES game_servers (id) ON DELETE SET NULL,
+    ip                      VARCHAR NOT NULL,
+    name                    VARCHAR NOT NULL,
+    added_at                TIMESTAMP NOT NULL DEFAULT now(),
+    UNIQUE (user_id, ip)
+);
+
+CREATE TABLE play_history (
+    id                      SERIAL PRIMARY KEY,
+    user_id                 INT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    server_id               INT REFERENCES game_servers (id) ON DELETE SET NULL,
+    ip                      VARCHAR NOT NULL,
+    name                    VARCHAR NOT NULL,
+    played_at               TIMESTAMP NOT NULL DEFAULT now(),
+    UNIQUE (user_id, ip)
+);
@@ -0,0 +1 @@
+ALTER TABLE game_servers DROP COLUMN owner_id;
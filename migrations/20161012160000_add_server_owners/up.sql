@@ -0,0 +1 @@
+ALTER TABLE game_servers ADD COLUMN owner_id INT REFERENCES users (id) ON DELETE SET NULL;
@@ -0,0 +1,10 @@
+CREATE TABLE audit_log (
+    id                      SERIAL PRIMARY KEY,
+    user_id                 INT REFERENCES users (id) ON DELETE SET NULL,
+    server_id               INT REFERENCES game_servers (id) ON DELETE SET NULL,
+    action                  VARCHAR NOT NULL,
+    detail                  TEXT NOT NULL,
+    created_at              TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX audit_log_server ON audit_log (server_id, created_at);
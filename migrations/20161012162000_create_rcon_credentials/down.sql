@@ -0,0 +1 @@
+DROP TABLE rcon_credentials;
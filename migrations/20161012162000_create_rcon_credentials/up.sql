@@ -0,0 +1,7 @@
+-- The password is encrypted with RCON_SECRET_KEY, see src/secrets.rs.
+CREATE TABLE rcon_credentials (
+    id                      SERIAL PRIMARY KEY,
+    server_id               INT NOT NULL UNIQUE REFERENCES game_servers (id) ON DELETE CASCADE,
+    encrypted_password      BYTEA NOT NULL,
+    port                    INT
+);
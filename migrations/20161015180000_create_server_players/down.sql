@@ -0,0 +1,2 @@
+DROP TABLE server_players;
+DROP TABLE player_rosters;
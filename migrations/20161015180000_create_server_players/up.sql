@@ -0,0 +1,15 @@
+-- When the roster of a server was last fetched, even if nobody was playing.
+CREATE TABLE player_rosters (
+    server_id               INT PRIMARY KEY REFERENCES game_servers (id) ON DELETE CASCADE,
+    fetched_at              TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE TABLE server_players (
+    id                      SERIAL PRIMARY KEY,
+    server_id               INT NOT NULL REFERENCES player_rosters (server_id) ON DELETE CASCADE,
+    name                    VARCHAR NOT NULL,
+    score                   INT NOT NULL,
+    duration                REAL NOT NULL
+);
+
+CREATE INDEX server_players_server ON server_players (server_id);
@@ -0,0 +1,11 @@
+-- Rules (cvars) of servers. Rules the owner supplied are kept over those read with A2S_RULES.
+CREATE TABLE server_rules (
+    id                      SERIAL PRIMARY KEY,
+    server_id               INT NOT NULL REFERENCES game_servers (id) ON DELETE CASCADE,
+    name                    VARCHAR NOT NULL,
+    value                   VARCHAR NOT NULL,
+    owner_supplied          BOOLEAN NOT NULL DEFAULT false,
+    UNIQUE (server_id, name)
+);
+
+CREATE INDEX server_rules_name_value ON server_rules (lower(name), value);
@@ -0,0 +1,3 @@
+ALTER TABLE game_servers
+    DROP COLUMN map_rotation,
+    DROP COLUMN current_map;
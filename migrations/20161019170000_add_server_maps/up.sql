@@ -0,0 +1,5 @@
+ALTER TABLE game_servers
+    ADD COLUMN current_map VARCHAR,
+    ADD COLUMN map_rotation VARCHAR[] NOT NULL DEFAULT '{}';
+
+CREATE INDEX game_servers_current_map ON game_servers (current_map varchar_pattern_ops);
@@ -0,0 +1,8 @@
+ALTER TABLE game_servers
+    DROP COLUMN outdated,
+    DROP COLUMN version,
+    DROP COLUMN os,
+    DROP COLUMN dedicated,
+    DROP COLUMN bots,
+    DROP COLUMN vac_secured,
+    DROP COLUMN password_protected;
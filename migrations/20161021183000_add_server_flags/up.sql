@@ -0,0 +1,10 @@
+ALTER TABLE game_servers
+    ADD COLUMN password_protected BOOLEAN NOT NULL DEFAULT false,
+    ADD COLUMN vac_secured BOOLEAN NOT NULL DEFAULT false,
+    ADD COLUMN bots INT NOT NULL DEFAULT 0,
+    ADD COLUMN dedicated BOOLEAN NOT NULL DEFAULT true,
+    -- `linux`, `windows` or `mac`.
+    ADD COLUMN os VARCHAR,
+    ADD COLUMN version VARCHAR,
+    -- Whether a newer version was seen on another server of the same game type.
+    ADD COLUMN outdated BOOLEAN NOT NULL DEFAULT false;
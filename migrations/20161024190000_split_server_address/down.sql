@@ -0,0 +1,11 @@
+ALTER TABLE game_servers ADD COLUMN ip VARCHAR;
+
+UPDATE game_servers SET ip = CASE family(address)
+    WHEN 6 THEN '[' || host(address) || ']:' || port
+    ELSE host(address) || ':' || port
+END;
+
+ALTER TABLE game_servers
+    ALTER COLUMN ip SET NOT NULL,
+    DROP COLUMN port,
+    DROP COLUMN address;
@@ -0,0 +1,107 @@
+ALTER TABLE game_servers ADD COLUMN address INET, ADD COLUMN port INT;
+
+CREATE FUNCTION pg_temp.fula_try_inet(value TEXT) RETURNS INET AS $$
+BEGIN
+    RETURN value::inet;
+EXCEPTION WHEN others THEN
+    RETURN NULL;
+END
+$$ LANGUAGE plpgsql;
+
+-- `ip` was one of `10.0.0.1`, `10.0.0.1:27016`, `2001:db8::1` or `[2001:db8::1]:27016`.
+UPDATE game_servers SET
+    address = pg_temp.fula_try_inet(CASE
+        WHEN ip LIKE '[%]:%' THEN substring(ip FROM '^\[(.*)\]:')
+        WHEN ip ~ '^[^:]*:[0-9]+$' THEN split_part(ip, ':', 1)
+        ELSE ip
+    END),
+    port = CASE
+        WHEN ip LIKE '[%]:%' OR ip ~ '^[^:]*:[0-9]+$' THEN substring(ip FROM ':([0-9]{1,5})$')::int
+        ELSE 27015
+    END;
+
+-- Servers listed under a host name have no address to keep. Host names can not be
+-- resolved from here, so the migration stops and lists them, to be fixed by hand.
+DO $$
+DECLARE
+    unparsed TEXT;
+BEGIN
+    SELECT string_agg(format('%s (`%s`)', id, ip), ', ' ORDER BY id) INTO unparsed
+    FROM game_servers WHERE address IS NULL OR port IS NULL OR port NOT BETWEEN 1 AND 65535;
+    IF unparsed IS NOT NULL THEN
+        RAISE EXCEPTION 'These servers have no IP address and port to keep: %', unparsed
+            USING HINT = 'Set their ip to the address and port they run at, or delete them, '
+                         'then run the migration again.';
+    END IF;
+END
+$$;
+
+-- Duplicate listings are merged into the oldest one. Their history, favorites, play history,
+-- audit trail and rules move to it before they are deleted.
+CREATE TEMP TABLE fula_merged AS
+SELECT duplicate.id AS duplicate_id, min(original.id) AS kept_id
+FROM game_servers duplicate JOIN game_servers original
+  ON duplicate.address = original.address AND duplicate.port = original.port
+ AND original.id < duplicate.id
+GROUP BY duplicate.id;
+
+CREATE FUNCTION pg_temp.fula_kept(server INT) RETURNS INT AS $$
+    SELECT coalesce((SELECT kept_id FROM fula_merged WHERE duplicate_id = server), server)
+$$ LANGUAGE sql STABLE;
+
+UPDATE player_counts SET server_id = m.kept_id
+FROM fula_merged m WHERE player_counts.server_id = m.duplicate_id;
+-- Rollups are kept for every hour and day a listing has one. Where two listings share one,
+-- the oldest listing's is kept.
+DELETE FROM player_count_rollups r USING player_count_rollups other
+WHERE pg_temp.fula_kept(r.server_id) = pg_temp.fula_kept(other.server_id)
+  AND r.resolution = other.resolution AND r.bucket = other.bucket AND other.server_id < r.server_id;
+UPDATE player_count_rollups SET server_id = m.kept_id
+FROM fula_merged m WHERE player_count_rollups.server_id = m.duplicate_id;
+
+-- A user keeps their first favorite, and their latest play, of the merged listings.
+DELETE FROM favorites f USING favorites other
+WHERE f.user_id = other.user_id AND other.id < f.id
+  AND pg_temp.fula_kept(f.server_id) = pg_temp.fula_kept(other.server_id);
+UPDATE favorites SET server_id = m.kept_id
+FROM fula_merged m WHERE favorites.server_id = m.duplicate_id;
+DELETE FROM play_history p USING play_history other
+WHERE p.user_id = other.user_id AND (other.played_at, other.id) > (p.played_at, p.id)
+  AND pg_temp.fula_kept(p.server_id) = pg_temp.fula_kept(other.server_id);
+UPDATE play_history SET server_id = m.kept_id
+FROM fula_merged m WHERE play_history.server_id = m.duplicate_id;
+
+UPDATE audit_log SET server_id = m.kept_id
+FROM fula_merged m WHERE audit_log.server_id = m.duplicate_id;
+
+-- Rules the owner supplied win over queried ones, then the oldest listing's win.
+DELETE FROM server_rules r USING server_rules other
+WHERE pg_temp.fula_kept(r.server_id) = pg_temp.fula_kept(other.server_id)
+  AND r.name = other.name AND r.id <> other.id
+  AND (other.owner_supplied, -other.server_id) > (r.owner_supplied, -r.server_id);
+UPDATE server_rules SET server_id = m.kept_id
+FROM fula_merged m WHERE server_rules.server_id = m.duplicate_id;
+
+-- RCON passwords are sealed to the id of their listing (see src/secrets.rs), so they can not
+-- move, and rosters are fetched again within minutes. Both go with their listing.
+DELETE FROM game_servers USING fula_merged m WHERE game_servers.id = m.duplicate_id;
+
+-- Favorites and play history keep the address of their server as `10.0.0.1:27015` or
+-- `[2001:db8::1]:27015`, to find it again if it is listed anew.
+UPDATE favorites SET ip = CASE family(game_servers.address)
+    WHEN 6 THEN '[' || host(game_servers.address) || ']:' || game_servers.port
+    ELSE host(game_servers.address) || ':' || game_servers.port
+END
+FROM game_servers WHERE favorites.server_id = game_servers.id;
+UPDATE play_history SET ip = CASE family(game_servers.address)
+    WHEN 6 THEN '[' || host(game_servers.address) || ']:' || game_servers.port
+    ELSE host(game_servers.address) || ':' || game_servers.port
+END
+FROM game_servers WHERE play_history.server_id = game_servers.id;
+
+ALTER TABLE game_servers
+    DROP COLUMN ip,
+    ALTER COLUMN address SET NOT NULL,
+    ALTER COLUMN port SET NOT NULL,
+    ADD CONSTRAINT game_servers_port_range CHECK (port BETWEEN 1 AND 65535),
+    ADD CONSTRAINT game_servers_address_port UNIQUE (address, port);
@@ -0,0 +1,3 @@
+-- The host name a server was listed by, such as `play.example.com`. `address` holds what
+-- it last resolved to.
+ALTER TABLE game_servers ADD COLUMN hostname VARCHAR;
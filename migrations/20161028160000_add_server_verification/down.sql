@@ -0,0 +1,3 @@
+ALTER TABLE game_servers
+    DROP COLUMN verification_token,
+    DROP COLUMN verified;
@@ -0,0 +1,8 @@
+-- Listings are verified once their server shows `verification_token` in its rules or name.
+ALTER TABLE game_servers
+    ADD COLUMN verified BOOLEAN NOT NULL DEFAULT false,
+    ADD COLUMN verification_token VARCHAR;
+
+UPDATE game_servers SET verification_token = 'fula-' || substr(md5(random()::text || id::text), 1, 16);
+
+ALTER TABLE game_servers ALTER COLUMN verification_token SET NOT NULL;
@@ -0,0 +1,5 @@
+DROP TABLE notifications;
+ALTER TABLE users DROP COLUMN contact;
+ALTER TABLE game_servers
+    DROP COLUMN status_reason,
+    DROP COLUMN status;
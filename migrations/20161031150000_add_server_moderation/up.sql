@@ -0,0 +1,21 @@
+-- `pending`, `approved` or `rejected`. Listings made before moderation stay approved.
+ALTER TABLE game_servers
+    ADD COLUMN status VARCHAR NOT NULL DEFAULT 'approved'
+        CHECK (status IN ('pending', 'approved', 'rejected')),
+    ADD COLUMN status_reason VARCHAR;
+ALTER TABLE game_servers ALTER COLUMN status SET DEFAULT 'pending';
+CREATE INDEX game_servers_pending ON game_servers (id) WHERE status = 'pending';
+
+-- Where to tell a user about decisions on their servers: an http(s) webhook URL.
+ALTER TABLE users ADD COLUMN contact VARCHAR;
+
+CREATE TABLE notifications (
+    id                      SERIAL PRIMARY KEY,
+    user_id                 INT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    server_id               INT REFERENCES game_servers (id) ON DELETE SET NULL,
+    message                 VARCHAR NOT NULL,
+    created_at              TIMESTAMP NOT NULL DEFAULT now(),
+    -- When the message was posted to the user's contact, if it was.
+    delivered_at            TIMESTAMP
+);
+CREATE INDEX notifications_user_id ON notifications (user_id, created_at);
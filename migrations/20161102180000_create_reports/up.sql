@@ -0,0 +1,18 @@
+CREATE TABLE reports (
+    id                      SERIAL PRIMARY KEY,
+    server_id               INT NOT NULL REFERENCES game_servers (id) ON DELETE CASCADE,
+    reporter_id             INT REFERENCES users (id) ON DELETE SET NULL,
+    -- One of `reports::CATEGORIES`.
+    category                VARCHAR NOT NULL,
+    detail                  TEXT NOT NULL DEFAULT '',
+    -- `open`, `dismissed` or `upheld`.
+    status                  VARCHAR NOT NULL DEFAULT 'open'
+                                CHECK (status IN ('open', 'dismissed', 'upheld')),
+    created_at              TIMESTAMP NOT NULL DEFAULT now(),
+    resolved_at             TIMESTAMP
+);
+
+CREATE INDEX reports_server ON reports (server_id, status);
+CREATE INDEX reports_reporter ON reports (reporter_id, created_at);
+-- A user can only have one open report on a server.
+CREATE UNIQUE INDEX reports_open_once ON reports (server_id, reporter_id) WHERE status = 'open';
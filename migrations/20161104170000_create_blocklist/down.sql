@@ -0,0 +1,2 @@
+DROP TABLE blocklist_hits;
+DROP TABLE blocked_networks;
@@ -0,0 +1,25 @@
+CREATE TABLE blocked_networks (
+    id                      SERIAL PRIMARY KEY,
+    network                 CIDR NOT NULL UNIQUE,
+    reason                  VARCHAR NOT NULL DEFAULT '',
+    -- Servers in the network can not be added, or moved into it.
+    block_listings          BOOLEAN NOT NULL DEFAULT true,
+    -- Clients in the network can not call the API.
+    block_clients           BOOLEAN NOT NULL DEFAULT true,
+    created_at              TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX blocked_networks_network ON blocked_networks USING gist (network inet_ops);
+
+CREATE TABLE blocklist_hits (
+    id                      SERIAL PRIMARY KEY,
+    network_id              INT NOT NULL REFERENCES blocked_networks (id) ON DELETE CASCADE,
+    address                 INET NOT NULL,
+    -- `listing` or `client`.
+    kind                    VARCHAR NOT NULL CHECK (kind IN ('listing', 'client')),
+    -- The endpoint of a blocked listing, or the request of a blocked client.
+    detail                  VARCHAR NOT NULL DEFAULT '',
+    created_at              TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX blocklist_hits_network ON blocklist_hits (network_id, created_at);
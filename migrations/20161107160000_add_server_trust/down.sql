@@ -0,0 +1,2 @@
+DROP TABLE player_count_checks;
+ALTER TABLE game_servers DROP COLUMN trust_score, DROP COLUMN suspicious;
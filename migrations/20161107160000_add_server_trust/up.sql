@@ -0,0 +1,16 @@
+-- From 0 (certainly faking its player count) to 1 (nothing suspicious), see `trust`.
+ALTER TABLE game_servers
+    ADD COLUMN trust_score REAL NOT NULL DEFAULT 1,
+    ADD COLUMN suspicious BOOLEAN NOT NULL DEFAULT false;
+
+-- The player count a server reported in its heartbeats, next to what it answered to A2S_INFO.
+CREATE TABLE player_count_checks (
+    id                      SERIAL PRIMARY KEY,
+    server_id               INT NOT NULL REFERENCES game_servers (id) ON DELETE CASCADE,
+    reported_users          INT NOT NULL,
+    queried_users           INT NOT NULL,
+    max_users               INT NOT NULL,
+    checked_at              TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX player_count_checks_server_time ON player_count_checks (server_id, checked_at);
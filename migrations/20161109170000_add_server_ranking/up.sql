@@ -0,0 +1,3 @@
+-- From 0 to 1, kept up to date by the `jobs` thread, see `ranking`.
+ALTER TABLE game_servers ADD COLUMN ranking_score REAL NOT NULL DEFAULT 0;
+CREATE INDEX game_servers_ranking ON game_servers (ranking_score DESC);
@@ -0,0 +1,2 @@
+ALTER TABLE game_servers DROP COLUMN rating_average, DROP COLUMN rating_count;
+DROP TABLE reviews;
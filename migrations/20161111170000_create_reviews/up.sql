@@ -0,0 +1,21 @@
+CREATE TABLE reviews (
+    id                      SERIAL PRIMARY KEY,
+    server_id               INT NOT NULL REFERENCES game_servers (id) ON DELETE CASCADE,
+    user_id                 INT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    rating                  INT NOT NULL CHECK (rating BETWEEN 1 AND 5),
+    body                    VARCHAR NOT NULL DEFAULT '',
+    -- The answer of the server's owner, if they gave one.
+    reply                   VARCHAR,
+    -- Hidden by an admin. Hidden reviews are not shown, and do not count towards the rating.
+    hidden                  BOOLEAN NOT NULL DEFAULT false,
+    created_at              TIMESTAMP NOT NULL DEFAULT now(),
+    updated_at              TIMESTAMP NOT NULL DEFAULT now(),
+    replied_at              TIMESTAMP,
+    -- A user reviews a server once, and edits that review afterwards.
+    UNIQUE (server_id, user_id)
+);
+
+-- Kept up to date from the visible reviews, see `reviews`.
+ALTER TABLE game_servers
+    ADD COLUMN rating_average REAL,
+    ADD COLUMN rating_count INT NOT NULL DEFAULT 0;
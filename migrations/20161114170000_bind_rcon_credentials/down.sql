@@ -0,0 +1 @@
+ALTER TABLE rcon_credentials DROP COLUMN address;
@@ -0,0 +1,6 @@
+-- The address of the game server an RCON password was set for. It is TEXT rather than
+-- `inet`, so that `infer_schema!` can still map the table.
+ALTER TABLE rcon_credentials ADD COLUMN address TEXT;
+UPDATE rcon_credentials AS c SET address = host(g.address)
+    FROM game_servers AS g WHERE g.id = c.server_id;
+ALTER TABLE rcon_credentials ALTER COLUMN address SET NOT NULL;
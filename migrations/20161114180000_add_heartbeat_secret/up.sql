@@ -0,0 +1,6 @@
+-- Game servers send the secret with every heartbeat, see src/routes/server/heartbeat.rs.
+-- Listings made from now on get theirs from add_server; existing ones get one here.
+CREATE EXTENSION IF NOT EXISTS pgcrypto;
+ALTER TABLE game_servers ADD COLUMN heartbeat_secret VARCHAR;
+UPDATE game_servers SET heartbeat_secret = encode(gen_random_bytes(16), 'hex');
+ALTER TABLE game_servers ALTER COLUMN heartbeat_secret SET NOT NULL;
@@ -0,0 +1,6 @@
+-- Aggregated hits stay single rows, dated when they were first seen.
+DROP INDEX blocklist_hits_last_at;
+DROP INDEX blocklist_hits_network;
+CREATE INDEX blocklist_hits_network ON blocklist_hits (network_id, created_at);
+ALTER TABLE blocklist_hits DROP CONSTRAINT blocklist_hits_source;
+ALTER TABLE blocklist_hits DROP COLUMN last_at, DROP COLUMN hits;
@@ -0,0 +1,23 @@
+-- One row per network, address and kind, counting its hits, instead of one row per hit.
+ALTER TABLE blocklist_hits
+    ADD COLUMN hits INT NOT NULL DEFAULT 1,
+    ADD COLUMN last_at TIMESTAMP NOT NULL DEFAULT now();
+
+UPDATE blocklist_hits AS h
+SET hits = a.hits, last_at = a.last_at, detail = a.detail
+FROM (SELECT min(id) AS id, count(*) AS hits, max(created_at) AS last_at,
+             (array_agg(detail ORDER BY created_at DESC))[1] AS detail
+      FROM blocklist_hits
+      GROUP BY network_id, address, kind) AS a
+WHERE h.id = a.id;
+
+DELETE FROM blocklist_hits AS h
+WHERE EXISTS (SELECT 1 FROM blocklist_hits AS o
+              WHERE o.network_id = h.network_id AND o.address = h.address
+                AND o.kind = h.kind AND o.id < h.id);
+
+ALTER TABLE blocklist_hits ADD CONSTRAINT blocklist_hits_source UNIQUE (network_id, address, kind);
+
+DROP INDEX blocklist_hits_network;
+CREATE INDEX blocklist_hits_network ON blocklist_hits (network_id, last_at);
+CREATE INDEX blocklist_hits_last_at ON blocklist_hits (last_at);
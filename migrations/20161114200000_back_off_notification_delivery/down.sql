@@ -0,0 +1,2 @@
+DROP INDEX notifications_undelivered;
+ALTER TABLE notifications DROP COLUMN next_attempt_at, DROP COLUMN attempts;
@@ -0,0 +1,6 @@
+-- Failed deliveries are tried again later and later, and given up on after a few tries.
+ALTER TABLE notifications
+    ADD COLUMN attempts INT NOT NULL DEFAULT 0,
+    ADD COLUMN next_attempt_at TIMESTAMP NOT NULL DEFAULT now();
+CREATE INDEX notifications_undelivered ON notifications (next_attempt_at)
+    WHERE delivered_at IS NULL;
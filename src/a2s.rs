@@ -0,0 +1,504 @@
+//! A client for the Source server queries (A2S), used to ask game servers about themselves.
+//!
+//! Queries and replies are UDP packets starting with `0xFFFFFFFF` and a type byte. Servers
+//! may answer a query with a challenge number instead, which is then sent back with the
+//! query to prove the address was not spoofed. Replies too large for one packet start with
+//! `0xFFFFFFFE`, and are split over several packets that are put back together in order.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::mem;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use ::models::Player;
+
+const SINGLE_PACKET: i32 = -1;
+const SPLIT_PACKET: i32 = -2;
+
+const A2S_INFO: u8 = 0x54;
+const S2A_INFO: u8 = 0x49;
+const A2S_PLAYER: u8 = 0x55;
+const S2A_PLAYER: u8 = 0x44;
+const A2S_RULES: u8 = 0x56;
+const S2A_RULES: u8 = 0x45;
+const S2C_CHALLENGE: u8 = 0x41;
+
+/// The largest packet a server sends.
+const MAX_PACKET_SIZE: usize = 1400;
+/// How often a query is retried with a new challenge before giving up.
+const MAX_CHALLENGES: usize = 3;
+/// The most packets a split reply may take. Even the rules of a heavily modded server fit
+/// in a handful.
+const MAX_SPLIT_PACKETS: usize = 32;
+
+/// Queries `query_all` runs at once.
+const QUERY_THREADS: usize = 16;
+/// How long `query_all` skips a server after it failed to answer, doubled for every failure
+/// in a row, up to `MAX_BACKOFF_SECS`.
+const BACKOFF_SECS: u64 = 5 * 60;
+const MAX_BACKOFF_SECS: u64 = 6 * 60 * 60;
+
+lazy_static! {
+    /// When each kind of query last failed on an address, and how many times in a row.
+    static ref FAILURES: Mutex<HashMap<(&'static str, SocketAddr), (Instant, u32)>> =
+        Mutex::new(HashMap::new());
+}
+
+#[derive(Debug)]
+pub enum A2sError {
+    Io(io::Error),
+    /// The server sent something that is not an A2S reply.
+    Protocol(String),
+}
+
+impl fmt::Display for A2sError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            A2sError::Io(ref e) => write!(f, "server query failed: {}", e),
+            A2sError::Protocol(ref msg) => write!(f, "server query protocol error: {}", msg),
+        }
+    }
+}
+
+impl Error for A2sError {
+    fn description(&self) -> &str {
+        match *self {
+            A2sError::Io(ref e) => e.description(),
+            A2sError::Protocol(_) => "server query protocol error",
+        }
+    }
+}
+
+impl From<io::Error> for A2sError {
+    fn from(e: io::Error) -> A2sError {
+        A2sError::Io(e)
+    }
+}
+
+/// Extra data flags of an A2S_INFO reply.
+const EDF_PORT: u8 = 0x80;
+const EDF_STEAM_ID: u8 = 0x10;
+const EDF_SOURCE_TV: u8 = 0x40;
+const EDF_KEYWORDS: u8 = 0x20;
+
+/// The Steam app id of The Ship, whose info replies have extra fields.
+const THE_SHIP_APP_ID: u16 = 2400;
+
+/// What a server says about itself.
+#[derive(Debug, Clone)]
+pub struct Info {
+    pub name: String,
+    pub map: String,
+    /// The game directory, such as `csgo` or `tf`.
+    pub folder: String,
+    pub game: String,
+    pub app_id: u16,
+    pub players: u8,
+    pub max_players: u8,
+    pub bots: u8,
+    /// `d` for a dedicated server, `l` for a listen server and `p` for a SourceTV relay.
+    pub server_type: char,
+    /// `l` for Linux, `w` for Windows and `m` or `o` for Mac.
+    pub environment: char,
+    pub password: bool,
+    pub vac: bool,
+    pub version: String,
+    /// The `sv_tags` of the server, comma separated.
+    pub keywords: Option<String>,
+}
+
+/// Asks a server about itself.
+pub fn info(address: SocketAddr, timeout: Duration) -> Result<Info, A2sError> {
+    let reply = try!(query(address, timeout, A2S_INFO, b"Source Engine Query\0", S2A_INFO));
+    let mut reader = Reader { data: &reply, pos: 0 };
+    try!(reader.u8());
+    let name = try!(reader.string());
+    let map = try!(reader.string());
+    let folder = try!(reader.string());
+    let game = try!(reader.string());
+    let app_id = try!(reader.u16());
+    let players = try!(reader.u8());
+    let max_players = try!(reader.u8());
+    let bots = try!(reader.u8());
+    let server_type = try!(reader.u8()) as char;
+    let environment = try!(reader.u8()) as char;
+    let password = try!(reader.u8()) == 1;
+    let vac = try!(reader.u8()) == 1;
+    if app_id == THE_SHIP_APP_ID {
+        try!(reader.take(3));
+    }
+    let version = try!(reader.string());
+
+    let mut keywords = None;
+    if let Ok(flags) = reader.u8() {
+        if flags & EDF_PORT != 0 {
+            try!(reader.take(2));
+        }
+        if flags & EDF_STEAM_ID != 0 {
+            try!(reader.take(8));
+        }
+        if flags & EDF_SOURCE_TV != 0 {
+            try!(reader.take(2));
+            try!(reader.string());
+        }
+        if flags & EDF_KEYWORDS != 0 {
+            keywords = Some(try!(reader.string()));
+        }
+    }
+
+    Ok(Info {
+        name: name,
+        map: map,
+        folder: folder,
+        game: game,
+        app_id: app_id,
+        players: players,
+        max_players: max_players,
+        bots: bots,
+        server_type: server_type,
+        environment: environment,
+        password: password,
+        vac: vac,
+        version: version,
+        keywords: keywords,
+    })
+}
+
+/// Asks a server who is playing on it.
+pub fn players(address: SocketAddr, timeout: Duration) -> Result<Vec<Player>, A2sError> {
+    let reply = try!(query(address, timeout, A2S_PLAYER, &[], S2A_PLAYER));
+    let mut reader = Reader { data: &reply, pos: 0 };
+    let count = try!(reader.u8());
+    let mut players = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        // The index of the player is always 0 on some games, so it is not worth keeping.
+        try!(reader.u8());
+        let name = try!(reader.string());
+        let score = try!(reader.i32());
+        let duration = try!(reader.f32());
+        players.push(Player { name: name, score: score, duration: duration });
+    }
+    Ok(players)
+}
+
+/// Asks a server for its rules (cvars), as name and value pairs.
+pub fn rules(address: SocketAddr, timeout: Duration) -> Result<Vec<(String, String)>, A2sError> {
+    let reply = try!(query(address, timeout, A2S_RULES, &[], S2A_RULES));
+    let mut reader = Reader { data: &reply, pos: 0 };
+    let count = try!(reader.u16());
+    let mut rules = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let name = try!(reader.string());
+        let value = try!(reader.string());
+        rules.push((name, value));
+    }
+    Ok(rules)
+}
+
+/// Runs `query` on every address, `QUERY_THREADS` at a time, and returns what each answered,
+/// in the order of `addresses`. Addresses that recently failed the same `kind` of query are
+/// skipped, and answer `None`.
+pub fn query_all<T, F>(kind: &'static str, addresses: &[SocketAddr], query: F)
+                       -> Vec<Option<Result<T, A2sError>>>
+    where T: Send + 'static, F: Fn(SocketAddr) -> Result<T, A2sError> + Send + Sync + 'static {
+    let now = Instant::now();
+    let due: Vec<(usize, SocketAddr)> = {
+        let failures = FAILURES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        addresses.iter().cloned().enumerate().filter(|&(_, a)| match failures.get(&(kind, a)) {
+            Some(&(failed_at, count)) => now.duration_since(failed_at) >= backoff(count),
+            None => true,
+        }).collect()
+    };
+
+    let work = Arc::new(Mutex::new(due.into_iter()));
+    let query = Arc::new(query);
+    let (sender, receiver) = mpsc::channel();
+    let workers: Vec<thread::JoinHandle<()>> = (0..QUERY_THREADS).map(|_| {
+        let (work, query, sender) = (work.clone(), query.clone(), sender.clone());
+        thread::spawn(move || loop {
+            let next = work.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).next();
+            match next {
+                Some((i, address)) => {
+                    if sender.send((i, address, query(address))).is_err() {
+                        return;
+                    }
+                },
+                None => return,
+            }
+        })
+    }).collect();
+    drop(sender);
+
+    let mut answers: Vec<Option<Result<T, A2sError>>> = addresses.iter().map(|_| None).collect();
+    for (i, address, answer) in receiver {
+        let mut failures = FAILURES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if answer.is_ok() {
+            failures.remove(&(kind, address));
+        } else {
+            let count = failures.get(&(kind, address)).map_or(0, |&(_, c)| c);
+            failures.insert((kind, address), (Instant::now(), count + 1));
+        }
+        answers[i] = Some(answer);
+    }
+    for worker in workers {
+        let _ = worker.join();
+    }
+    answers
+}
+
+/// How long to skip a server after `failures` failures in a row.
+fn backoff(failures: u32) -> Duration {
+    let factor = 1u64 << (if failures > 16 { 16 } else { failures.saturating_sub(1) });
+    let secs = BACKOFF_SECS.saturating_mul(factor);
+    Duration::from_secs(if secs > MAX_BACKOFF_SECS { MAX_BACKOFF_SECS } else { secs })
+}
+
+/// Sends a query, answering challenges until the server replies with `reply_kind`, for at
+/// most `timeout` in all. Returns the reply without its header and type.
+fn query(address: SocketAddr, timeout: Duration, kind: u8, payload: &[u8], reply_kind: u8)
+         -> Result<Vec<u8>, A2sError> {
+    let bind_address = if address.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+    let socket = try!(UdpSocket::bind(bind_address));
+    try!(socket.set_write_timeout(Some(timeout)));
+    try!(socket.connect(address));
+
+    let started = Instant::now();
+    let mut challenge = le_bytes(-1);
+    for _ in 0..MAX_CHALLENGES {
+        let mut request = le_bytes(SINGLE_PACKET).to_vec();
+        request.push(kind);
+        request.extend_from_slice(payload);
+        request.extend_from_slice(&challenge);
+        try!(socket.send(&request));
+
+        let reply = try!(receive(&socket, started, timeout));
+        match reply.first() {
+            Some(&k) if k == reply_kind => return Ok(reply[1..].to_vec()),
+            Some(&S2C_CHALLENGE) if reply.len() >= 5 => {
+                challenge = [reply[1], reply[2], reply[3], reply[4]];
+            },
+            Some(&k) => return Err(A2sError::Protocol(format!("unexpected reply type 0x{:02X}", k))),
+            None => return Err(A2sError::Protocol("empty reply".into())),
+        }
+    }
+    Err(A2sError::Protocol("the server kept sending challenges".into()))
+}
+
+/// Receives a whole reply, putting split packets back together, unless `timeout` has passed
+/// since `started`. Returns the reply from its type byte on.
+fn receive(socket: &UdpSocket, started: Instant, timeout: Duration) -> Result<Vec<u8>, A2sError> {
+    let mut buf = [0u8; MAX_PACKET_SIZE];
+    let (reply_id, total, mut part) = {
+        let len = try!(recv_within(socket, &mut buf, started, timeout));
+        let mut reader = Reader { data: &buf[..len], pos: 0 };
+        match try!(reader.i32()) {
+            SINGLE_PACKET => return Ok(reader.rest().to_vec()),
+            SPLIT_PACKET => {},
+            h => return Err(A2sError::Protocol(format!("unknown packet header {}", h))),
+        }
+        let reply_id = try!(reader.i32());
+        if reply_id as u32 & 0x80000000 != 0 {
+            return Err(A2sError::Protocol("compressed replies are not supported".into()));
+        }
+        let total = try!(reader.u8()) as usize;
+        if total == 0 || total > MAX_SPLIT_PACKETS {
+            return Err(A2sError::Protocol(format!("reply split over {} packets", total)));
+        }
+        (reply_id, total, (try!(reader.u8()) as usize, reader.rest_after(2)))
+    };
+    let mut parts: Vec<Option<Vec<u8>>> = vec![None; total];
+    // Parts sent more than once count too, so a server can not keep one talking.
+    let mut received = 1;
+    loop {
+        let (number, data) = part;
+        match parts.get_mut(number) {
+            Some(slot) => *slot = Some(data),
+            None => return Err(A2sError::Protocol(format!("split packet {} of {}", number, total))),
+        }
+        if parts.iter().all(|p| p.is_some()) {
+            break;
+        }
+        if received == MAX_SPLIT_PACKETS {
+            return Err(A2sError::Protocol("too many split packets".into()));
+        }
+
+        received += 1;
+        part = {
+            let len = try!(recv_within(socket, &mut buf, started, timeout));
+            let mut reader = Reader { data: &buf[..len], pos: 0 };
+            if try!(reader.i32()) != SPLIT_PACKET || try!(reader.i32()) != reply_id {
+                return Err(A2sError::Protocol("interleaved replies".into()));
+            }
+            try!(reader.u8());
+            (try!(reader.u8()) as usize, reader.rest_after(2))
+        };
+    }
+
+    let whole: Vec<u8> = parts.into_iter().flat_map(|p| p.unwrap_or(vec![])).collect();
+    let mut reader = Reader { data: &whole, pos: 0 };
+    if try!(reader.i32()) != SINGLE_PACKET {
+        return Err(A2sError::Protocol("split reply has no header".into()));
+    }
+    Ok(reader.rest().to_vec())
+}
+
+/// Receives one packet, waiting no longer than what is left of `timeout` since `started`.
+fn recv_within(socket: &UdpSocket, buf: &mut [u8], started: Instant, timeout: Duration)
+               -> Result<usize, A2sError> {
+    let elapsed = started.elapsed();
+    if elapsed >= timeout {
+        return Err(A2sError::Io(io::Error::new(io::ErrorKind::TimedOut,
+                                               "the server took too long to respond")));
+    }
+    try!(socket.set_read_timeout(Some(timeout - elapsed)));
+    Ok(try!(socket.recv(buf)))
+}
+
+/// Reads the little endian values of a reply.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], A2sError> {
+        if self.pos + n > self.data.len() {
+            return Err(A2sError::Protocol("reply is too short".into()));
+        }
+        let bytes = &self.data[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, A2sError> {
+        self.take(1).map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, A2sError> {
+        self.take(2).map(|b| (b[0] as u16) | (b[1] as u16) << 8)
+    }
+
+    fn i32(&mut self) -> Result<i32, A2sError> {
+        self.take(4).map(|b| {
+            (b[0] as i32) | (b[1] as i32) << 8 | (b[2] as i32) << 16 | (b[3] as i32) << 24
+        })
+    }
+
+    fn f32(&mut self) -> Result<f32, A2sError> {
+        let bits = try!(self.i32()) as u32;
+        Ok(unsafe { mem::transmute::<u32, f32>(bits) })
+    }
+
+    /// Reads a NUL terminated string, replacing anything that is not UTF-8.
+    fn string(&mut self) -> Result<String, A2sError> {
+        let rest = &self.data[self.pos..];
+        match rest.iter().position(|&b| b == 0) {
+            Some(end) => {
+                self.pos += end + 1;
+                Ok(String::from_utf8_lossy(&rest[..end]).into_owned())
+            },
+            None => Err(A2sError::Protocol("string is never terminated".into())),
+        }
+    }
+
+    fn rest(&self) -> &'a [u8] {
+        &self.data[self.pos..]
+    }
+
+    /// The rest of the data after skipping `n` bytes.
+    fn rest_after(&self, n: usize) -> Vec<u8> {
+        if self.pos + n >= self.data.len() {
+            return vec![];
+        }
+        self.data[self.pos + n..].to_vec()
+    }
+}
+
+fn le_bytes(n: i32) -> [u8; 4] {
+    [n as u8, (n >> 8) as u8, (n >> 16) as u8, (n >> 24) as u8]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::net::{SocketAddr, UdpSocket};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    #[test]
+    fn query_all_skips_servers_that_just_failed() {
+        let addresses: Vec<SocketAddr> = (1..41).map(|p| SocketAddr::from(([192, 0, 2, 1], p)))
+                                                .collect();
+        let answer_even = |a: SocketAddr| if a.port() % 2 == 0 {
+            Ok(a.port())
+        } else {
+            Err(A2sError::Io(io::Error::new(io::ErrorKind::TimedOut, "no answer")))
+        };
+
+        let first = query_all("test", &addresses, answer_even);
+        for (address, answer) in addresses.iter().zip(first) {
+            match answer {
+                Some(Ok(p)) => assert_eq!(p, address.port()),
+                Some(Err(_)) => assert!(address.port() % 2 == 1),
+                None => panic!("{} was skipped before it ever failed", address),
+            }
+        }
+        let again = query_all("test", &addresses, answer_even);
+        for (address, answer) in addresses.iter().zip(again) {
+            assert_eq!(answer.is_none(), address.port() % 2 == 1);
+        }
+        // Other kinds of queries are not held against a server.
+        assert!(query_all("other", &addresses, answer_even).iter().all(|a| a.is_some()));
+    }
+
+    /// A client socket, and a thread sending it the first of two parts of a split reply
+    /// `times` times, `every` apart.
+    fn split_sender(times: usize, every: Duration) -> UdpSocket {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client.connect(server.local_addr().unwrap()).unwrap();
+        let client_address = client.local_addr().unwrap();
+        thread::spawn(move || {
+            let mut packet = le_bytes(SPLIT_PACKET).to_vec();
+            packet.extend_from_slice(&le_bytes(7));
+            packet.extend_from_slice(&[2, 0, 0xE0, 0x04]);
+            packet.extend_from_slice(&le_bytes(SINGLE_PACKET));
+            for _ in 0..times {
+                if server.send_to(&packet, client_address).is_err() {
+                    return;
+                }
+                thread::sleep(every);
+            }
+        });
+        client
+    }
+
+    #[test]
+    fn receive_stops_at_the_timeout() {
+        let client = split_sender(1000, Duration::from_millis(50));
+        let started = Instant::now();
+        match receive(&client, started, Duration::from_millis(300)) {
+            Err(A2sError::Io(_)) => {},
+            Err(e) => panic!("expected a timeout, got {}", e),
+            Ok(_) => panic!("expected a timeout, got a reply"),
+        }
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn receive_stops_at_too_many_packets() {
+        let client = split_sender(MAX_SPLIT_PACKETS + 1, Duration::from_millis(1));
+        match receive(&client, Instant::now(), Duration::from_secs(10)) {
+            Err(A2sError::Protocol(_)) => {},
+            Err(e) => panic!("expected a protocol error, got {}", e),
+            Ok(_) => panic!("expected a protocol error, got a reply"),
+        }
+    }
+}
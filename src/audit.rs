@@ -0,0 +1,21 @@
+//! The audit trail of sensitive actions, such as commands sent to servers over RCON.
+
+use diesel;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+
+use ::models::NewAuditEntry;
+
+/// Records that a user did something, possibly to a server.
+pub fn record(conn: &PgConnection, user_id: Option<i32>, server_id: Option<i32>,
+              action: &str, detail: &str) -> QueryResult<()> {
+    use ::schema::audit_log;
+
+    let entry = NewAuditEntry {
+        user_id: user_id,
+        server_id: server_id,
+        action: action.into(),
+        detail: detail.into(),
+    };
+    diesel::insert(&entry).into(audit_log::table).execute(conn).map(|_| ())
+}
@@ -0,0 +1,320 @@
+//! Networks (CIDR ranges), such as known abusive hosting ranges, whose servers can not be
+//! listed, or whose clients can not call the API. Servers already listed in a network when
+//! it is blocked are held for review.
+//!
+//! Hits are recorded for reporting, as one row per network, address and kind that counts
+//! them. Blocked clients are counted in memory and written by `flush_hits` every minute, so
+//! that their requests do not each write to the DB. Hits not seen for `HIT_RETENTION` are
+//! deleted.
+//!
+//! `blocked_networks` and `blocklist_hits` have `cidr` and `inet` columns, which
+//! `infer_schema!` can not map, so they are only queried with SQL here.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::mem;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use diesel::expression::dsl::sql;
+use diesel::types::{BigInt, Bool, Integer, Text};
+use rustful::{Context, StatusCode};
+use rustful::filter::{ContextAction, ContextFilter, FilterContext};
+
+use ::establish_connection;
+use ::in_transaction;
+use ::models::{BlockedNetwork, BlocklistHit, GameServer};
+use ::moderation;
+use ::routes::is_admin;
+use ::search::quote_literal;
+
+/// How long the client filter uses the networks it loaded before loading them again, or
+/// waits to try again when loading them failed.
+const CACHE_SECS: u64 = 60;
+/// Hits listed by `load_hits`.
+const HITS_SHOWN: i64 = 200;
+/// How long hits are kept after the last one.
+const HIT_RETENTION: &'static str = "30 days";
+/// Clients whose hits are counted until the next `flush_hits`. Hits of more are not counted.
+const MAX_PENDING_HITS: usize = 10000;
+
+/// What a blocked network was kept from doing.
+pub const LISTING: &'static str = "listing";
+pub const CLIENT: &'static str = "client";
+
+lazy_static! {
+    /// The networks blocked from calling the API, and when loading them was last tried.
+    static ref CLIENT_CACHE: Mutex<(Option<Instant>, Vec<(i32, Network)>)> =
+        Mutex::new((None, vec![]));
+    /// Client hits not written yet, by network and address: how many, and the last request.
+    static ref PENDING_HITS: Mutex<HashMap<(i32, IpAddr), (i32, String)>> =
+        Mutex::new(HashMap::new());
+}
+
+/// An IPv4 or IPv6 network, such as `192.0.2.0/24`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Network {
+    pub address: IpAddr,
+    pub prefix: u8,
+}
+
+impl Network {
+    /// Parses `address/prefix`, or a single address. Bits after the prefix are cleared, so
+    /// `192.0.2.7/24` is `192.0.2.0/24`.
+    pub fn parse(s: &str) -> Result<Network, String> {
+        let s = s.trim();
+        let (address, prefix) = match s.find('/') {
+            Some(i) => (&s[..i], Some(&s[i + 1..])),
+            None => (s, None),
+        };
+        let address: IpAddr = match address.parse() {
+            Ok(a) => a,
+            Err(_) => return Err(format!("`{}` is not an IPv4 or IPv6 address", address)),
+        };
+        let max_prefix = if address.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix.map(|p| p.parse::<u8>()) {
+            Some(Ok(p)) if p <= max_prefix => p,
+            Some(_) => return Err(format!("the prefix of `{}` must be between 0 and {}", s, max_prefix)),
+            None => max_prefix,
+        };
+        let mut octets = octets(address);
+        for (i, octet) in octets.iter_mut().enumerate() {
+            *octet &= mask(prefix, i);
+        }
+        Ok(Network { address: from_octets(&octets), prefix: prefix })
+    }
+
+    pub fn contains(&self, address: IpAddr) -> bool {
+        let (network, address) = (octets(self.address), octets(unmap(address)));
+        network.len() == address.len()
+        && network.iter().zip(address.iter()).enumerate()
+                  .all(|(i, (n, a))| a & mask(self.prefix, i) == *n)
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.address, self.prefix)
+    }
+}
+
+fn octets(address: IpAddr) -> Vec<u8> {
+    match address {
+        IpAddr::V4(a) => a.octets().to_vec(),
+        IpAddr::V6(a) => a.octets().to_vec(),
+    }
+}
+
+fn from_octets(octets: &[u8]) -> IpAddr {
+    if octets.len() == 4 {
+        IpAddr::V4([octets[0], octets[1], octets[2], octets[3]].into())
+    } else {
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(octets);
+        IpAddr::V6(bytes.into())
+    }
+}
+
+/// The bits of the `i`th octet inside a prefix.
+fn mask(prefix: u8, i: usize) -> u8 {
+    let bits = (prefix as usize).saturating_sub(i * 8);
+    if bits >= 8 { 0xFF } else { !(0xFFu8 >> bits) }
+}
+
+/// IPv4 clients of a server listening on IPv6 show up as `::ffff:a.b.c.d`.
+fn unmap(address: IpAddr) -> IpAddr {
+    match address {
+        IpAddr::V6(a) => match a.segments() {
+            [0, 0, 0, 0, 0, 0xFFFF, hi, lo] => {
+                IpAddr::V4([(hi >> 8) as u8, hi as u8, (lo >> 8) as u8, lo as u8].into())
+            },
+            _ => address,
+        },
+        _ => address,
+    }
+}
+
+/// Every blocked network, with how often it was hit, most specific first.
+pub fn load(conn: &PgConnection) -> QueryResult<Vec<BlockedNetwork>> {
+    sql::<(Integer, Text, Text, Bool, Bool, BigInt, BigInt)>(
+        "SELECT n.id, n.network::text, n.reason, n.block_listings, n.block_clients,
+                (SELECT coalesce(sum(h.hits), 0)::bigint FROM blocklist_hits AS h
+                 WHERE h.network_id = n.id),
+                extract(epoch FROM n.created_at)::bigint
+         FROM blocked_networks AS n
+         ORDER BY masklen(n.network) DESC, n.network").load(conn)
+}
+
+/// Blocks a network. When it blocks listings, the approved servers already listed in it are
+/// held for review. Returns how many were, or `None` when the network was already blocked.
+pub fn add(conn: &PgConnection, network: &Network, reason: &str, listings: bool, clients: bool)
+           -> QueryResult<Option<usize>> {
+    use ::schema::game_servers::dsl::*;
+
+    let held = try!(in_transaction(conn, || {
+        let added = try!(conn.execute(&format!(
+            "INSERT INTO blocked_networks (network, reason, block_listings, block_clients)
+             VALUES ({}::cidr, {}, {}, {})
+             ON CONFLICT (network) DO NOTHING",
+            quote_literal(&network.to_string()), quote_literal(reason), listings, clients)));
+        if added == 0 {
+            return Ok(None);
+        }
+        if !listings {
+            return Ok(Some(0));
+        }
+        let listed = try!(game_servers.filter(status.eq(moderation::APPROVED))
+                                      .filter(sql::<Bool>(&format!(
+                                          "address <<= {}::cidr",
+                                          quote_literal(&network.to_string()))))
+                                      .load::<GameServer>(conn));
+        let why = format!("its network `{}` was blocked", network);
+        for server in &listed {
+            try!(moderation::hold_for_review(conn, server, &why));
+        }
+        Ok(Some(listed.len()))
+    }));
+    forget_clients();
+    Ok(held)
+}
+
+/// Unblocks a network. Returns false when there was no such network.
+pub fn delete(conn: &PgConnection, network_id: i32) -> QueryResult<bool> {
+    let deleted = try!(conn.execute(&format!("DELETE FROM blocked_networks WHERE id = {}", network_id)));
+    forget_clients();
+    Ok(deleted > 0)
+}
+
+/// The latest hits, newest first, optionally only those of one network.
+pub fn load_hits(conn: &PgConnection, network: Option<i32>) -> QueryResult<Vec<BlocklistHit>> {
+    let condition = match network {
+        Some(n) => format!("WHERE network_id = {}", n),
+        None => String::new(),
+    };
+    sql::<(Integer, Integer, Text, Text, Text, Integer, BigInt, BigInt)>(&format!(
+        "SELECT id, network_id, host(address), kind, detail, hits,
+                extract(epoch FROM created_at)::bigint, extract(epoch FROM last_at)::bigint
+         FROM blocklist_hits
+         {}
+         ORDER BY last_at DESC, id DESC
+         LIMIT {}", condition, HITS_SHOWN)).load(conn)
+}
+
+/// Adds `hits` to the hits of an address in a network, unless the network was unblocked
+/// in the meantime.
+fn record_hit(conn: &PgConnection, network_id: i32, address: IpAddr, kind: &str, detail: &str,
+              hits: i32) -> QueryResult<()> {
+    conn.execute(&format!(
+        "INSERT INTO blocklist_hits (network_id, address, kind, detail, hits)
+         SELECT id, {}::inet, {}, {}, {} FROM blocked_networks WHERE id = {}
+         ON CONFLICT (network_id, address, kind) DO UPDATE
+         SET hits = blocklist_hits.hits + excluded.hits, detail = excluded.detail, last_at = now()",
+        quote_literal(&address.to_string()), quote_literal(kind), quote_literal(detail), hits,
+        network_id)).map(|_| ())
+}
+
+/// Counts a blocked client request, to be written by `flush_hits`.
+fn queue_hit(network_id: i32, address: IpAddr, detail: String) {
+    let mut pending = PENDING_HITS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if pending.len() >= MAX_PENDING_HITS && !pending.contains_key(&(network_id, address)) {
+        return;
+    }
+    let entry = pending.entry((network_id, address)).or_insert((0, String::new()));
+    entry.0 = entry.0.saturating_add(1);
+    entry.1 = detail;
+}
+
+/// Writes the client hits counted since the last run, and deletes old hits. Run as a job.
+pub fn flush_hits(conn: &PgConnection) -> QueryResult<()> {
+    let pending = {
+        let mut pending = PENDING_HITS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        mem::replace(&mut *pending, HashMap::new())
+    };
+    for ((network_id, address), (hits, detail)) in pending {
+        try!(record_hit(conn, network_id, address, CLIENT, &detail, hits));
+    }
+    try!(conn.execute(&format!(
+        "DELETE FROM blocklist_hits WHERE last_at < now() - interval '{}'", HIT_RETENTION)));
+    Ok(())
+}
+
+/// The blocked network a server at `address` can not be listed from, if any. The hit is
+/// recorded, with `endpoint` as its detail.
+pub fn blocking_listing(conn: &PgConnection, address: IpAddr, endpoint: &str) -> QueryResult<Option<String>> {
+    let found: Option<(i32, String)> = try!(sql::<(Integer, Text)>(&format!(
+        "SELECT id, network::text FROM blocked_networks
+         WHERE block_listings AND {}::inet <<= network
+         ORDER BY masklen(network) DESC
+         LIMIT 1", quote_literal(&unmap(address).to_string()))).load(conn)).into_iter().next();
+    match found {
+        Some((network_id, network)) => {
+            try!(record_hit(conn, network_id, address, LISTING, endpoint, 1));
+            Ok(Some(network))
+        },
+        None => Ok(None),
+    }
+}
+
+/// Makes the next request load the client networks again, so that changes apply to it.
+fn forget_clients() {
+    CLIENT_CACHE.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).0 = None;
+}
+
+/// The blocked network a client at `address` belongs to, if any.
+fn blocking_client(address: IpAddr) -> Option<i32> {
+    // One request loads the networks when they are stale, while the others keep using the
+    // ones loaded before, so that no request waits on the DB behind the lock.
+    let stale = {
+        let mut cache = CLIENT_CACHE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let stale = cache.0.map_or(true, |tried_at| {
+            tried_at.elapsed() >= Duration::from_secs(CACHE_SECS)
+        });
+        if stale {
+            cache.0 = Some(Instant::now());
+        }
+        stale
+    };
+    if stale {
+        match load_client_networks() {
+            Ok(networks) => {
+                CLIENT_CACHE.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).1 = networks;
+            },
+            // The networks loaded last stay blocked until loading works again.
+            Err(e) => error!("Could not load the client blocklist: {}", e),
+        }
+    }
+    let cache = CLIENT_CACHE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    cache.1.iter().find(|&&(_, n)| n.contains(address)).map(|&(i, _)| i)
+}
+
+/// The networks blocked from calling the API, most specific first.
+fn load_client_networks() -> Result<Vec<(i32, Network)>, String> {
+    let conn = try!(establish_connection());
+    let networks: Vec<(i32, String)> = try!(sql::<(Integer, Text)>(
+        "SELECT id, network::text FROM blocked_networks
+         WHERE block_clients
+         ORDER BY masklen(network) DESC").load(&conn).map_err(|e| format!("{:?}", e)));
+    Ok(networks.into_iter().filter_map(|(i, n)| Network::parse(&n).ok().map(|n| (i, n))).collect())
+}
+
+/// Refuses requests from blocked networks with `403 Forbidden`. Admins are let through, so
+/// that they can not lock themselves out.
+pub struct ClientFilter;
+
+impl ContextFilter for ClientFilter {
+    fn modify(&self, _ctx: FilterContext, context: &mut Context) -> ContextAction {
+        let address = context.address.ip();
+        let network_id = match blocking_client(address) {
+            Some(n) => n,
+            None => return ContextAction::next(),
+        };
+        if is_admin(context) {
+            return ContextAction::next();
+        }
+        queue_hit(network_id, address, format!("{} {:?}", context.method, context.uri_path));
+        ContextAction::abort(StatusCode::Forbidden)
+    }
+}
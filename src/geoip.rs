@@ -0,0 +1,87 @@
+//! Locating IP addresses with a local MaxMind database, and distances between regions.
+
+use std::cmp::Ordering;
+use std::env;
+use std::net::{IpAddr, SocketAddr};
+
+use maxminddb::{self, geoip2};
+
+use ::models::Region;
+
+/// Mean radius of the earth in kilometers.
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+lazy_static! {
+    /// The database at `GEOIP_DATABASE`, if one is configured and could be opened.
+    static ref READER: Option<maxminddb::Reader> = open_reader();
+}
+
+fn open_reader() -> Option<maxminddb::Reader> {
+    let path = match env::var("GEOIP_DATABASE") {
+        Ok(p) => p,
+        Err(_) => {
+            info!("GEOIP_DATABASE is not set, region inference is disabled.");
+            return None;
+        }
+    };
+    match maxminddb::Reader::open(&path) {
+        Ok(r) => Some(r),
+        Err(e) => {
+            error!("Could not open GeoIP database at `{}`: {:?}", path, e);
+            None
+        }
+    }
+}
+
+/// Parses the host part of a `GameServer` ip, which may or may not include a port.
+pub fn parse_host(address: &str) -> Option<IpAddr> {
+    address.parse::<SocketAddr>().map(|a| a.ip()).ok()
+           .or_else(|| address.parse::<IpAddr>().ok())
+}
+
+/// Looks up the (latitude, longitude) of an IP address.
+pub fn locate(ip: IpAddr) -> Option<(f64, f64)> {
+    let reader = match *READER {
+        Some(ref r) => r,
+        None => return None,
+    };
+    let city: geoip2::City = match reader.lookup(ip) {
+        Ok(c) => c,
+        Err(e) => {
+            debug!("Could not find {} in the GeoIP database: {:?}", ip, e);
+            return None;
+        }
+    };
+    city.location.and_then(|l| match (l.latitude, l.longitude) {
+        (Some(lat), Some(lon)) => Some((lat, lon)),
+        _ => None,
+    })
+}
+
+/// Great-circle distance in kilometers between two (latitude, longitude) points.
+pub fn distance_km(from: (f64, f64), to: (f64, f64)) -> f64 {
+    let (lat1, lon1) = (from.0.to_radians(), from.1.to_radians());
+    let (lat2, lon2) = (to.0.to_radians(), to.1.to_radians());
+    let a = ((lat2 - lat1) / 2.0).sin().powi(2)
+            + lat1.cos() * lat2.cos() * ((lon2 - lon1) / 2.0).sin().powi(2);
+    // Rounding can take `a` just past 1 for points on opposite sides of the earth, where
+    // `asin` has no answer.
+    let a = a.max(0.0).min(1.0);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+/// Orders the regions that have coordinates by their distance to `point`, nearest first.
+/// Regions without coordinates are left out.
+pub fn regions_by_distance(point: (f64, f64), regions: Vec<Region>) -> Vec<(Region, f64)> {
+    let mut located: Vec<(Region, f64)> = regions.into_iter().filter_map(|r| {
+        match (r.latitude, r.longitude) {
+            (Some(lat), Some(lon)) => {
+                let distance = distance_km(point, (lat, lon));
+                Some((r, distance))
+            },
+            _ => None,
+        }
+    }).collect();
+    located.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal));
+    located
+}
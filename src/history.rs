@@ -0,0 +1,137 @@
+//! Recording of observed player counts, and their rollup into hourly and daily buckets.
+//!
+//! Every observation is kept as a raw sample for a short while. The `jobs` thread rolls
+//! raw samples up into hourly buckets, and hourly buckets into daily ones, throwing away
+//! whatever is older than the retention of its resolution.
+
+use diesel;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use diesel::expression::dsl::sql;
+use diesel::types::{BigInt, Float, Integer};
+
+use ::in_transaction;
+use ::models::{HistoryBucket, NewPlayerCount};
+
+/// How long raw samples are kept around after being rolled up.
+const RAW_RETENTION: &'static str = "2 days";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Resolution {
+    Hour,
+    Day,
+}
+
+impl Resolution {
+    pub fn from_str(s: &str) -> Option<Resolution> {
+        match s {
+            "hour" => Some(Resolution::Hour),
+            "day" => Some(Resolution::Day),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Resolution::Hour => "hour",
+            Resolution::Day => "day",
+        }
+    }
+
+    /// How many buckets are returned when the client does not ask for a number.
+    pub fn default_buckets(&self) -> i32 {
+        match *self {
+            Resolution::Hour => 24,
+            Resolution::Day => 30,
+        }
+    }
+
+    /// How many buckets of this resolution are kept, and so the most that can be asked for.
+    pub fn retained_buckets(&self) -> i32 {
+        match *self {
+            Resolution::Hour => 31 * 24,
+            Resolution::Day => 400,
+        }
+    }
+}
+
+/// Stores a single observation of the players on a server.
+pub fn record(conn: &PgConnection, server_id: i32, region: &str, current_users: i32) -> QueryResult<()> {
+    use ::schema::player_counts;
+
+    let sample = NewPlayerCount {
+        server_id: server_id,
+        region: region.into(),
+        current_users: current_users,
+    };
+    diesel::insert(&sample).into(player_counts::table).execute(conn).map(|_| ())
+}
+
+/// Recomputes the current and previous hourly and daily buckets, and drops expired data.
+pub fn roll_up(conn: &PgConnection) -> QueryResult<()> {
+    in_transaction(conn, || {
+        try!(conn.execute("
+            INSERT INTO player_count_rollups
+                (server_id, region, resolution, bucket, average_users, min_users, max_users, samples)
+            SELECT server_id, max(region), 'hour', date_trunc('hour', recorded_at),
+                   avg(current_users)::real, min(current_users), max(current_users), count(*)::int
+            FROM player_counts
+            WHERE recorded_at >= date_trunc('hour', now()) - interval '1 hour'
+            GROUP BY server_id, date_trunc('hour', recorded_at)
+            ON CONFLICT (server_id, resolution, bucket) DO UPDATE SET
+                region = EXCLUDED.region,
+                average_users = EXCLUDED.average_users,
+                min_users = EXCLUDED.min_users,
+                max_users = EXCLUDED.max_users,
+                samples = EXCLUDED.samples"));
+        try!(conn.execute("
+            INSERT INTO player_count_rollups
+                (server_id, region, resolution, bucket, average_users, min_users, max_users, samples)
+            SELECT server_id, max(region), 'day', date_trunc('day', bucket),
+                   (sum(average_users * samples) / sum(samples))::real,
+                   min(min_users), max(max_users), sum(samples)::int
+            FROM player_count_rollups
+            WHERE resolution = 'hour' AND bucket >= date_trunc('day', now()) - interval '1 day'
+            GROUP BY server_id, date_trunc('day', bucket)
+            ON CONFLICT (server_id, resolution, bucket) DO UPDATE SET
+                region = EXCLUDED.region,
+                average_users = EXCLUDED.average_users,
+                min_users = EXCLUDED.min_users,
+                max_users = EXCLUDED.max_users,
+                samples = EXCLUDED.samples"));
+
+        try!(conn.execute(&format!(
+            "DELETE FROM player_counts WHERE recorded_at < now() - interval '{}'", RAW_RETENTION)));
+        for res in &[Resolution::Hour, Resolution::Day] {
+            try!(conn.execute(&format!(
+                "DELETE FROM player_count_rollups
+                 WHERE resolution = '{0}' AND bucket < date_trunc('{0}', now()) - interval '{1} {0}s'",
+                res.as_str(), res.retained_buckets())));
+        }
+        Ok(())
+    })
+}
+
+/// The last `buckets` buckets of a single server's player counts, oldest first.
+pub fn server_history(conn: &PgConnection, server_id: i32, resolution: Resolution, buckets: i32)
+                      -> QueryResult<Vec<HistoryBucket>> {
+    sql::<(BigInt, Float, Integer, Integer)>(&format!(
+        "SELECT extract(epoch FROM bucket)::bigint, average_users, min_users, max_users
+         FROM player_count_rollups
+         WHERE server_id = {} AND resolution = '{1}'
+           AND bucket > date_trunc('{1}', now()) - interval '{2} {1}s'
+         ORDER BY bucket", server_id, resolution.as_str(), buckets)).load(conn)
+}
+
+/// The last `buckets` buckets of the summed player counts of all servers in a region.
+pub fn region_history(conn: &PgConnection, region_id: i32, resolution: Resolution, buckets: i32)
+                      -> QueryResult<Vec<HistoryBucket>> {
+    sql::<(BigInt, Float, Integer, Integer)>(&format!(
+        "SELECT extract(epoch FROM bucket)::bigint, sum(average_users)::real,
+                sum(min_users)::int, sum(max_users)::int
+         FROM player_count_rollups
+         WHERE region = (SELECT name FROM regions WHERE id = {})
+           AND resolution = '{1}' AND bucket > date_trunc('{1}', now()) - interval '{2} {1}s'
+         GROUP BY bucket
+         ORDER BY bucket", region_id, resolution.as_str(), buckets)).load(conn)
+}
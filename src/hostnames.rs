@@ -0,0 +1,213 @@
+//! Servers listed by host name, such as `play.example.com:27015`.
+//!
+//! Host names are resolved with the system resolver, so `/etc/hosts` is honoured. The address
+//! a host name resolved to is stored with the server, and kept up to date by the `jobs` thread.
+//! Servers can only be listed at public addresses, see `net::is_public`, however they are
+//! given.
+
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+
+use diesel;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use diesel::result::OptionalExtension;
+
+use ::blocklist;
+use ::models::{parse_endpoint, GameServer, DEFAULT_GAME_PORT};
+use ::net;
+
+/// The longest host name DNS allows.
+const MAX_HOSTNAME_LENGTH: usize = 253;
+/// The longest label (part between dots) DNS allows.
+const MAX_LABEL_LENGTH: usize = 63;
+
+/// Parses the address of a server like `parse_endpoint`, but also accepts host names, which
+/// are resolved. Returns the address and the lowercased host name, if one was given.
+/// Addresses that are not public are refused.
+pub fn resolve_endpoint(address: &str, port: Option<i32>) -> Result<(SocketAddr, Option<String>), String> {
+    let (endpoint, hostname) = match parse_endpoint(address, port) {
+        Ok(a) => (a, None),
+        Err(error) => {
+            let (hostname, port) = match split_hostname(address.trim(), port) {
+                Some(Ok(h)) => h,
+                Some(Err(e)) => return Err(e),
+                None => return Err(error),
+            };
+            match resolve(&hostname, port) {
+                Some(ip) => (SocketAddr::new(ip, port), Some(hostname)),
+                None => return Err(format!("`{}` does not resolve to an address", hostname)),
+            }
+        }
+    };
+    if !net::is_public(endpoint.ip()) {
+        return Err(match hostname {
+            Some(h) => {
+                format!("`{}` resolves to {}, which is not a public address", h, endpoint.ip())
+            },
+            None => format!("`{}` is not a public address", endpoint.ip()),
+        });
+    }
+    Ok((endpoint, hostname))
+}
+
+/// Splits `host` or `host:port` into a lowercased host name and port. Returns `None` when
+/// the address is not a host name at all.
+fn split_hostname(address: &str, port: Option<i32>) -> Option<Result<(String, u16), String>> {
+    let (host, given_port) = match address.rfind(':') {
+        Some(i) => (&address[..i], Some(&address[i + 1..])),
+        None => (address, None),
+    };
+    if !is_hostname(host) {
+        return None;
+    }
+    let port = match (given_port.map(|p| p.parse::<u16>()), port) {
+        (Some(Ok(p)), Some(q)) if p as i32 != q => {
+            return Some(Err(format!("`{}` has a port other than {}", address, q)));
+        },
+        (Some(Ok(0)), _) | (Some(Err(_)), _) => {
+            return Some(Err(format!("`{}` has no valid port", address)));
+        },
+        (Some(Ok(p)), _) => p,
+        (None, Some(q)) if q >= 1 && q <= 65535 => q as u16,
+        (None, Some(q)) => return Some(Err(format!("{} is not a port number", q))),
+        (None, None) => DEFAULT_GAME_PORT,
+    };
+    Some(Ok((host.to_lowercase(), port)))
+}
+
+/// Whether `host` is a DNS name: dot separated labels of letters, digits and dashes, with
+/// at least one letter so that malformed IP addresses are not taken for names.
+fn is_hostname(host: &str) -> bool {
+    let host = host.trim_right_matches('.');
+    if host.is_empty() || host.len() > MAX_HOSTNAME_LENGTH
+       || !host.chars().any(is_letter) {
+        return false;
+    }
+    host.split('.').all(|label| {
+        !label.is_empty() && label.len() <= MAX_LABEL_LENGTH
+        && !label.starts_with('-') && !label.ends_with('-')
+        && label.chars().all(|c| is_letter(c) || c.is_digit(10) || c == '-')
+    })
+}
+
+fn is_letter(c: char) -> bool {
+    match c {
+        'a'...'z' | 'A'...'Z' => true,
+        _ => false,
+    }
+}
+
+/// Resolves a host name, preferring IPv4 addresses since not every client speaks IPv6.
+pub fn resolve(hostname: &str, port: u16) -> Option<IpAddr> {
+    let addresses: Vec<SocketAddr> = match (hostname, port).to_socket_addrs() {
+        Ok(a) => a.collect(),
+        Err(e) => {
+            debug!("Could not resolve `{}`: {:?}", hostname, e);
+            return None;
+        }
+    };
+    addresses.iter().find(|a| a.is_ipv4()).or(addresses.first()).map(|a| a.ip())
+}
+
+/// Resolves the host names of every server listed by one again, and moves the servers
+/// whose address changed. Servers whose name no longer resolves keep their last address.
+pub fn refresh_all(conn: &PgConnection) -> QueryResult<()> {
+    use ::schema::game_servers::dsl::*;
+
+    let servers = try!(game_servers.filter(hostname.is_not_null()).load::<GameServer>(conn));
+    for server in &servers {
+        let name = match server.hostname {
+            Some(ref n) => n,
+            None => continue,
+        };
+        let resolved = match resolve(name, server.port as u16) {
+            Some(ip) => ip,
+            None => continue,
+        };
+        let ip = resolved.to_string();
+        if ip == server.address {
+            continue;
+        }
+        if !net::is_public(resolved) {
+            warn!("`{}` of server {} now resolves to {}, which is not a public address.",
+                  name, server.id, ip);
+            continue;
+        }
+        let endpoint = format!("{}:{}", ip, server.port);
+        if let Some(network) = try!(blocklist::blocking_listing(conn, resolved, &endpoint)) {
+            warn!("`{}` of server {} now resolves to {}, which is in blocked network {}.",
+                  name, server.id, ip, network);
+            continue;
+        }
+        let taken = try!(game_servers.select(id)
+                                     .filter(address.eq(&ip))
+                                     .filter(port.eq(server.port))
+                                     .first::<i32>(conn).optional());
+        if let Some(other) = taken {
+            warn!("`{}` of server {} now resolves to {}, where server {} is listed.",
+                  name, server.id, ip, other);
+            continue;
+        }
+        info!("`{}` of server {} moved from {} to {}", name, server.id, server.address, ip);
+        try!(diesel::update(game_servers.filter(id.eq(server.id)))
+                    .set(address.eq(&ip))
+                    .execute(conn));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_hostname, resolve, resolve_endpoint, split_hostname};
+
+    #[test]
+    fn host_names_are_told_from_addresses() {
+        assert!(is_hostname("play.example.com"));
+        assert!(is_hostname("cs-1.example.com."));
+        assert!(!is_hostname("10.0.0.1"));
+        assert!(!is_hostname("10.0.0.1.2"));
+        assert!(!is_hostname("-bad.example.com"));
+        assert!(!is_hostname("under_score.example.com"));
+    }
+
+    #[test]
+    fn host_names_are_split_from_ports() {
+        assert_eq!(split_hostname("Play.Example.com:27016", None),
+                   Some(Ok(("play.example.com".to_string(), 27016))));
+        assert_eq!(split_hostname("play.example.com", Some(27017)),
+                   Some(Ok(("play.example.com".to_string(), 27017))));
+        assert!(split_hostname("play.example.com:27016", Some(27017)).unwrap().is_err());
+        assert!(split_hostname("play.example.com:0", None).unwrap().is_err());
+        assert_eq!(split_hostname("10.0.0.1:27015", None), None);
+    }
+
+    // `localhost` is in `/etc/hosts` everywhere, so these do not need a DNS server.
+    #[test]
+    fn host_names_resolve_through_etc_hosts() {
+        assert!(resolve("localhost", 27015).map_or(false, |ip| ip.is_loopback()));
+    }
+
+    #[test]
+    fn addresses_that_are_not_public_are_refused() {
+        let error = resolve_endpoint("localhost:27015", None).unwrap_err();
+        assert!(error.contains("`localhost` resolves to"), "{}", error);
+        assert!(error.contains("not a public address"), "{}", error);
+        assert!(resolve_endpoint("10.0.0.1", Some(27015)).is_err());
+        assert!(resolve_endpoint("[fe80::1]:27015", None).is_err());
+        assert!(resolve_endpoint("192.168.1.20:27015", None).is_err());
+    }
+
+    #[test]
+    fn public_addresses_are_taken_as_they_are() {
+        assert_eq!(resolve_endpoint("8.8.8.8:27016", None),
+                   Ok(("8.8.8.8:27016".parse().unwrap(), None)));
+        assert_eq!(resolve_endpoint("2606:4700::1111", Some(27015)),
+                   Ok(("[2606:4700::1111]:27015".parse().unwrap(), None)));
+    }
+
+    #[test]
+    fn unknown_host_names_are_refused() {
+        let error = resolve_endpoint("nothing.invalid", None).unwrap_err();
+        assert!(error.contains("does not resolve"), "{}", error);
+    }
+}
@@ -0,0 +1,82 @@
+//! The Postgres `inet` type, which diesel does not know about. Values are read and written
+//! as the text of an address, such as `10.0.0.1` or `2001:db8::1`.
+
+use std::error::Error;
+use std::io::Write;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use diesel::expression::AsExpression;
+use diesel::expression::bound::Bound;
+use diesel::pg::{Pg, PgTypeMetadata};
+use diesel::types::{FromSql, HasSqlType, IsNull, NotNull, ToSql};
+
+/// Address families of the binary `inet` format.
+const PGSQL_AF_INET: u8 = 2;
+const PGSQL_AF_INET6: u8 = 3;
+
+pub struct Inet;
+
+impl HasSqlType<Inet> for Pg {
+    fn metadata() -> PgTypeMetadata {
+        PgTypeMetadata {
+            oid: 869,
+            array_oid: 1041,
+        }
+    }
+}
+
+impl NotNull for Inet {}
+
+impl FromSql<Inet, Pg> for String {
+    fn from_sql(bytes: Option<&[u8]>) -> Result<Self, Box<Error + Send + Sync>> {
+        let bytes = match bytes {
+            Some(b) => b,
+            None => return Err("Unexpected null for non-null column".into()),
+        };
+        // Family, netmask bits, whether it is a cidr, and the length of the address.
+        if bytes.len() < 4 || bytes.len() != 4 + bytes[3] as usize {
+            return Err("Invalid inet value".into());
+        }
+        let octets = &bytes[4..];
+        let ip = match (bytes[0], octets.len()) {
+            (PGSQL_AF_INET, 4) => IpAddr::V4(Ipv4Addr::new(octets[0], octets[1], octets[2], octets[3])),
+            (PGSQL_AF_INET6, 16) => {
+                let mut v6 = [0u8; 16];
+                v6.copy_from_slice(octets);
+                IpAddr::V6(Ipv6Addr::from(v6))
+            },
+            _ => return Err("Invalid inet value".into()),
+        };
+        Ok(ip.to_string())
+    }
+}
+
+impl ToSql<Inet, Pg> for String {
+    fn to_sql<W: Write>(&self, out: &mut W) -> Result<IsNull, Box<Error + Send + Sync>> {
+        let ip: IpAddr = try!(self.parse());
+        let (family, octets) = match ip {
+            IpAddr::V4(v4) => (PGSQL_AF_INET, v4.octets().to_vec()),
+            IpAddr::V6(v6) => (PGSQL_AF_INET6, v6.octets().to_vec()),
+        };
+        let bits = (octets.len() * 8) as u8;
+        try!(out.write_all(&[family, bits, 0, octets.len() as u8]));
+        try!(out.write_all(&octets));
+        Ok(IsNull::No)
+    }
+}
+
+impl AsExpression<Inet> for String {
+    type Expression = Bound<Inet, String>;
+
+    fn as_expression(self) -> Self::Expression {
+        Bound::new(self)
+    }
+}
+
+impl<'a> AsExpression<Inet> for &'a String {
+    type Expression = Bound<Inet, &'a String>;
+
+    fn as_expression(self) -> Self::Expression {
+        Bound::new(self)
+    }
+}
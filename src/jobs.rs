@@ -0,0 +1,71 @@
+//! Periodic maintenance work, run on a background thread.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use diesel::QueryResult;
+use diesel::pg::PgConnection;
+
+use ::blocklist;
+use ::establish_connection;
+use ::history;
+use ::hostnames;
+use ::notifications;
+use ::ranking;
+use ::rules;
+use ::server_info;
+use ::trust;
+
+/// How often the job thread wakes up to see if a job is due.
+const TICK_SECS: u64 = 10;
+
+struct Job {
+    name: &'static str,
+    every_secs: u64,
+    run: fn(&PgConnection) -> QueryResult<()>,
+}
+
+const JOBS: &'static [Job] = &[
+    Job { name: "player count rollup", every_secs: 5 * 60, run: history::roll_up },
+    Job { name: "server rules refresh", every_secs: 10 * 60, run: rules::refresh_all },
+    Job { name: "server info refresh", every_secs: 2 * 60, run: server_info::refresh_all },
+    Job { name: "hostname resolution", every_secs: 15 * 60, run: hostnames::refresh_all },
+    Job { name: "notification delivery", every_secs: 60, run: notifications::deliver_all },
+    Job { name: "trust scoring", every_secs: 30 * 60, run: trust::score_all },
+    Job { name: "ranking", every_secs: 10 * 60, run: ranking::refresh_all },
+    Job { name: "blocklist hits", every_secs: 60, run: blocklist::flush_hits },
+];
+
+/// Starts the job thread. Every job runs once at startup, then every `every_secs` seconds.
+pub fn spawn() -> thread::JoinHandle<()> {
+    thread::spawn(|| {
+        let mut last_run: Vec<Option<Instant>> = JOBS.iter().map(|_| None).collect();
+        loop {
+            for (job, last) in JOBS.iter().zip(last_run.iter_mut()) {
+                let due = match *last {
+                    Some(t) => t.elapsed() >= Duration::from_secs(job.every_secs),
+                    None => true,
+                };
+                if due {
+                    run_job(job);
+                    *last = Some(Instant::now());
+                }
+            }
+            thread::sleep(Duration::from_secs(TICK_SECS));
+        }
+    })
+}
+
+fn run_job(job: &Job) {
+    let conn = match establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Could not connect to the DB to run job `{}`: {}", job.name, e);
+            return;
+        }
+    };
+    debug!("Running job `{}`", job.name);
+    if let Err(e) = (job.run)(&conn) {
+        error!("Job `{}` failed: {:?}", job.name, e);
+    }
+}
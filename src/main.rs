@@ -7,11 +7,17 @@ extern crate rustful;
 extern crate log;
 #[macro_use]
 extern crate diesel;
+#[macro_use]
+extern crate lazy_static;
 extern crate dotenv;
 
 extern crate env_logger;
 extern crate hyper;
 extern crate rustc_serialize;
+extern crate maxminddb;
+extern crate rand;
+extern crate crypto;
+extern crate unicode_normalization;
 
 use std::error::Error;
 use std::env;
@@ -24,11 +30,52 @@ use diesel::pg::PgConnection;
 use dotenv::dotenv;
 
 use routes::server::{get_all_servers, add_server, update_server, search_servers, delete_server};
-use routes::region::{add_region, get_all_regions};
+use routes::server::{heartbeat, server_history, server_players, search_servers_query};
+use routes::server::{get_server, set_server_rules, set_map_rotation};
+use routes::server::{set_rcon_password, rcon_command};
+use routes::server::{get_verification, check_verification, report_server};
+use routes::server::{get_reviews, review_server, delete_review, reply_to_review};
+use routes::region::{add_region, get_all_regions, region_history};
+use routes::map::get_maps;
+use routes::stats::get_stats;
+use routes::user::{register_user, get_favorites, add_favorite, delete_favorite};
+use routes::user::{get_play_history, add_play_history, delete_play_history};
+use routes::user::{import_favorites_vdf, export_favorites_vdf};
+use routes::user::{get_notifications, set_contact};
+use routes::moderation::{get_moderation_queue, moderate_server, get_suspicious_servers};
+use routes::moderation::moderate_review;
+use routes::report::{get_reports, get_report_counts, resolve_report};
+use routes::blocklist::{get_blocklist, add_blocked_network, delete_blocked_network};
+use routes::blocklist::get_blocklist_hits;
+use routes::region::{get_all_region_aliases, add_region_alias, delete_region_alias};
 mod schema;
 mod models;
 mod routes;
-mod then_impl;
+mod a2s;
+mod audit;
+mod blocklist;
+mod geoip;
+mod history;
+mod hostnames;
+mod inet;
+mod jobs;
+mod master_server;
+mod moderation;
+mod names;
+mod net;
+mod notifications;
+mod players;
+mod ranking;
+mod rcon;
+mod reports;
+mod reviews;
+mod rules;
+mod search;
+mod secrets;
+mod server_info;
+mod trust;
+mod vdf;
+mod verification;
 
 // TODO: Documentation? Doc comments would be nice.
 
@@ -53,8 +100,21 @@ fn establish_connection() -> Result<PgConnection, &'static str> {
     }
 }
 
+/// Runs `f` in a transaction, which is rolled back when `f` fails.
+fn in_transaction<T, F>(conn: &PgConnection, f: F) -> QueryResult<T>
+    where F: FnOnce() -> QueryResult<T> {
+    conn.transaction(f).map_err(|e| match e {
+        diesel::result::TransactionError::UserReturnedError(e) => e,
+        diesel::result::TransactionError::CouldntCreateTransaction(e) => e,
+    })
+}
+
 fn main() {
     env_logger::init().expect("env_logger init");
+    dotenv().ok();
+
+    jobs::spawn();
+    master_server::spawn();
 
     let server = Server {
         host: 8080.into(),
@@ -66,6 +126,7 @@ fn main() {
                         Get: get_all_servers as fn(Context, Response),
                     },
                     "search" => {
+                        Get: search_servers_query as fn(Context, Response),
                         Post: search_servers as fn(Context, Response),
                     },
                     "add" => {
@@ -76,7 +137,120 @@ fn main() {
                     },
                     "delete/:id" => {
                         Post: delete_server as fn(Context, Response),
-                    }
+                    },
+                    "heartbeat/:id" => {
+                        Post: heartbeat as fn(Context, Response),
+                    },
+                    ":id" => {
+                        Get: get_server as fn(Context, Response),
+                    },
+                    ":id/history" => {
+                        Get: server_history as fn(Context, Response),
+                    },
+                    ":id/players" => {
+                        Get: server_players as fn(Context, Response),
+                    },
+                    ":id/maps" => {
+                        Post: set_map_rotation as fn(Context, Response),
+                    },
+                    ":id/rules" => {
+                        Post: set_server_rules as fn(Context, Response),
+                    },
+                    ":id/report" => {
+                        Post: report_server as fn(Context, Response),
+                    },
+                    ":id/reviews" => {
+                        Get: get_reviews as fn(Context, Response),
+                        Post: review_server as fn(Context, Response),
+                        "delete" => {
+                            Post: delete_review as fn(Context, Response),
+                        },
+                        ":review_id/reply" => {
+                            Post: reply_to_review as fn(Context, Response),
+                        },
+                    },
+                    ":id/verification" => {
+                        Get: get_verification as fn(Context, Response),
+                        Post: check_verification as fn(Context, Response),
+                    },
+                    ":id/rcon" => {
+                        Post: rcon_command as fn(Context, Response),
+                        "password" => {
+                            Post: set_rcon_password as fn(Context, Response),
+                        },
+                    },
+                },
+                "user" => {
+                    "register" => {
+                        Post: register_user as fn(Context, Response),
+                    },
+                    "favorites" => {
+                        Get: get_favorites as fn(Context, Response),
+                        "add" => {
+                            Post: add_favorite as fn(Context, Response),
+                        },
+                        "delete/:id" => {
+                            Post: delete_favorite as fn(Context, Response),
+                        },
+                        "vdf" => {
+                            Get: export_favorites_vdf as fn(Context, Response),
+                            Post: import_favorites_vdf as fn(Context, Response),
+                        },
+                    },
+                    "history" => {
+                        Get: get_play_history as fn(Context, Response),
+                        "add" => {
+                            Post: add_play_history as fn(Context, Response),
+                        },
+                        "delete/:id" => {
+                            Post: delete_play_history as fn(Context, Response),
+                        },
+                    },
+                    "notifications" => {
+                        Get: get_notifications as fn(Context, Response),
+                    },
+                    "contact" => {
+                        Post: set_contact as fn(Context, Response),
+                    },
+                },
+                "map" => {
+                    Get: get_maps as fn(Context, Response),
+                },
+                "report" => {
+                    Get: get_reports as fn(Context, Response),
+                    "servers" => {
+                        Get: get_report_counts as fn(Context, Response),
+                    },
+                    ":id" => {
+                        Post: resolve_report as fn(Context, Response),
+                    },
+                },
+                "blocklist" => {
+                    Get: get_blocklist as fn(Context, Response),
+                    "add" => {
+                        Post: add_blocked_network as fn(Context, Response),
+                    },
+                    "delete/:id" => {
+                        Post: delete_blocked_network as fn(Context, Response),
+                    },
+                    "hits" => {
+                        Get: get_blocklist_hits as fn(Context, Response),
+                    },
+                },
+                "moderation" => {
+                    Get: get_moderation_queue as fn(Context, Response),
+                    "suspicious" => {
+                        Get: get_suspicious_servers as fn(Context, Response),
+                    },
+                    "reviews/:id" => {
+                        Post: moderate_review as fn(Context, Response),
+                    },
+                    ":id" => {
+                        Post: moderate_server as fn(Context, Response),
+                    },
+                },
+                "stats" => {
+                    Get: get_stats as fn(Context, Response),
                 },
                 "region" => {
                     Get: get_all_regions as fn(Context, Response),
@@ -86,9 +260,22 @@ fn main() {
                     "add" => {
                         Post: add_region as fn(Context, Response),
                     },
+                    ":id/history" => {
+                        Get: region_history as fn(Context, Response),
+                    },
+                    "alias" => {
+                        Get: get_all_region_aliases as fn(Context, Response),
+                        "add" => {
+                            Post: add_region_alias as fn(Context, Response),
+                        },
+                        "delete/:id" => {
+                            Post: delete_region_alias as fn(Context, Response),
+                        },
+                    },
                 }
             }
         },
+        context_filters: vec![Box::new(blocklist::ClientFilter)],
         ..Server::default()
     }.run();
 
@@ -0,0 +1,317 @@
+//! An optional UDP frontend speaking the Valve master server query protocol, so that game
+//! clients and third party tools can discover the servers listed in fula.
+//!
+//! A query is the byte `0x31`, a region code byte, the last address of the previous page
+//! (`0.0.0.0:0` for the first page) and a filter string such as `\gamedir\csgo\empty\1`,
+//! the last two being NUL terminated. Every reply is a single packet of addresses; the last
+//! page ends with `0.0.0.0:0`.
+//!
+//! Replies are much larger than queries, and UDP sources are easily spoofed, so every source
+//! address is only answered a few queries a second, to keep the master server from being
+//! used to flood others.
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::thread;
+use std::time::Instant;
+
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+
+use ::establish_connection;
+use ::models::{GameServer, Region, STEAM_GAMES};
+use ::search::{self, Comparison, SearchFilters};
+use ::trust;
+
+const QUERY_HEADER: u8 = 0x31;
+const REPLY_HEADER: [u8; 6] = [0xFF, 0xFF, 0xFF, 0xFF, 0x66, 0x0A];
+/// Addresses per reply, keeping replies within a single unfragmented packet.
+const PAGE_SIZE: usize = 230;
+/// Queries a source can send at once, enough to page through thousands of servers, and how
+/// many more it can send every second after that.
+const QUERY_BURST: f64 = 20.0;
+const QUERIES_PER_SEC: f64 = 2.0;
+/// Sources tracked by the rate limit. When there are more, queries from new sources are
+/// dropped until the oldest have refilled.
+const MAX_SOURCES: usize = 10000;
+
+/// Starts the master server on `MASTER_SERVER_PORT`, if that is set.
+pub fn spawn() -> Option<thread::JoinHandle<()>> {
+    let port: u16 = match env::var("MASTER_SERVER_PORT") {
+        Ok(p) => match p.parse() {
+            Ok(p) => p,
+            Err(e) => {
+                error!("MASTER_SERVER_PORT must be a port number: {:?}", e);
+                return None;
+            }
+        },
+        Err(_) => return None,
+    };
+    let socket = match UdpSocket::bind(("0.0.0.0", port)) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Could not bind the master server to port {}: {:?}", port, e);
+            return None;
+        }
+    };
+    info!("Master server listening on UDP port {}", port);
+    Some(thread::spawn(move || serve(socket)))
+}
+
+fn serve(socket: UdpSocket) {
+    let mut buf = [0u8; 1400];
+    let mut limiter = RateLimiter::new();
+    // Reused for every query, and made again after it fails.
+    let mut connection: Option<PgConnection> = None;
+    loop {
+        let (len, client) = match socket.recv_from(&mut buf) {
+            Ok(r) => r,
+            Err(e) => {
+                error!("Master server could not receive a query: {:?}", e);
+                continue;
+            }
+        };
+        if !limiter.allow(client.ip(), Instant::now()) {
+            debug!("Dropping a master server query from {}, which sends too many", client);
+            continue;
+        }
+        let query = match Query::parse(&buf[..len]) {
+            Some(q) => q,
+            None => {
+                debug!("Ignoring a malformed master server query from {}", client);
+                continue;
+            }
+        };
+        if connection.is_none() {
+            connection = match establish_connection() {
+                Ok(c) => Some(c),
+                Err(e) => {
+                    error!("Master server could not connect to the DB: {}", e);
+                    continue;
+                }
+            };
+        }
+        let reply = match connection.as_ref().map(|conn| page(conn, &query)) {
+            Some(Ok(r)) => r,
+            Some(Err(e)) => {
+                error!("Master server query failed: {:?}", e);
+                connection = None;
+                continue;
+            },
+            None => continue,
+        };
+        if let Err(e) = socket.send_to(&reply, client) {
+            error!("Master server could not reply to {}: {:?}", client, e);
+        }
+    }
+}
+
+/// A token bucket for every source address.
+struct RateLimiter {
+    sources: HashMap<IpAddr, (Instant, f64)>,
+}
+
+impl RateLimiter {
+    fn new() -> RateLimiter {
+        RateLimiter { sources: HashMap::new() }
+    }
+
+    /// Whether a query from `source` at `now` is answered.
+    fn allow(&mut self, source: IpAddr, now: Instant) -> bool {
+        if self.sources.len() >= MAX_SOURCES && !self.sources.contains_key(&source) {
+            // Sources that have refilled are no different from new ones.
+            self.sources.retain(|_, &mut (last, tokens)| refill(last, tokens, now) < QUERY_BURST);
+            if self.sources.len() >= MAX_SOURCES {
+                return false;
+            }
+        }
+        let tokens = match self.sources.get(&source) {
+            Some(&(last, tokens)) => refill(last, tokens, now),
+            None => QUERY_BURST,
+        };
+        if tokens < 1.0 {
+            return false;
+        }
+        self.sources.insert(source, (now, tokens - 1.0));
+        true
+    }
+}
+
+/// The tokens a source has at `now`, when it had `tokens` at `last`.
+fn refill(last: Instant, tokens: f64, now: Instant) -> f64 {
+    let elapsed = now.duration_since(last);
+    let seconds = elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1e9;
+    (tokens + seconds * QUERIES_PER_SEC).min(QUERY_BURST)
+}
+
+struct Query {
+    region_code: u8,
+    seed: SocketAddrV4,
+    filter: String,
+}
+
+impl Query {
+    fn parse(packet: &[u8]) -> Option<Query> {
+        if packet.len() < 2 || packet[0] != QUERY_HEADER {
+            return None;
+        }
+        let mut parts = packet[2..].split(|&b| b == 0);
+        let seed = match parts.next().and_then(|s| String::from_utf8(s.to_vec()).ok()) {
+            Some(s) => match s.parse() {
+                Ok(a) => a,
+                Err(_) => return None,
+            },
+            None => return None,
+        };
+        let filter = parts.next().map(|f| String::from_utf8_lossy(f).into_owned())
+                          .unwrap_or(String::new());
+        Some(Query { region_code: packet[1], seed: seed, filter: filter })
+    }
+}
+
+/// The default fula regions in each region of the master server protocol.
+/// `None` stands for the whole world. Regions that do not exist are left out, and a region
+/// with none left is answered with no servers.
+fn region_names(code: u8) -> Option<&'static [&'static str]> {
+    match code {
+        0x00 => Some(&["naeast"]),
+        0x01 => Some(&["nawest"]),
+        0x02 => Some(&["sanorth", "sasouth"]),
+        0x03 => Some(&["eueast", "euwest"]),
+        0x04 => Some(&["aswest", "aseast"]),
+        0x05 => Some(&["auwest", "aueast"]),
+        // The Middle East, which has no default region but can be added as `mideast`.
+        0x06 => Some(&["mideast"]),
+        0x07 => Some(&["afnorth", "afsouth"]),
+        _ => None,
+    }
+}
+
+/// Maps a filter string onto search filters. Filters that fula can not express are ignored,
+/// as master servers do with filters they do not know.
+fn parse_filter(filter: &str) -> SearchFilters {
+    let mut filters = SearchFilters::default();
+    let parts: Vec<&str> = filter.trim_matches('\\').split('\\').collect();
+    for pair in parts.chunks(2) {
+        let (key, value) = match (pair.get(0), pair.get(1)) {
+            (Some(k), Some(v)) => (*k, *v),
+            _ => continue,
+        };
+        match key {
+            "gametype" => {
+                filters.tags.extend(value.split(',').filter(|t| !t.is_empty()).map(|t| t.to_string()));
+            },
+            "gamedir" => {
+                let game_type = STEAM_GAMES.iter().find(|&&(_, _, dir)| dir == value)
+                                           .map(|&(g, _, _)| g).unwrap_or(value);
+                filters.game_types.push(game_type.into());
+            },
+            "appid" => {
+                let games: Vec<String> = STEAM_GAMES.iter()
+                                                    .filter(|&&(_, app, _)| app.to_string() == value)
+                                                    .map(|&(g, _, _)| g.to_string()).collect();
+                // An unknown app id must still filter, so it is kept as a game type nothing has.
+                if games.is_empty() {
+                    filters.game_types.push(value.into());
+                }
+                filters.game_types.extend(games);
+            },
+            "empty" if value == "1" => filters.players.push((Comparison::Greater, 0)),
+            "noplayers" if value == "1" => filters.players.push((Comparison::Equal, 0)),
+            "full" if value == "1" => filters.not_full = true,
+            "map" => filters.maps.push(value.into()),
+            "secure" if value == "1" => filters.vac_secured = Some(true),
+            "dedicated" if value == "1" => filters.dedicated = Some(true),
+            "linux" if value == "1" => filters.operating_systems.push("linux".into()),
+            "password" if value == "0" => filters.password_protected = Some(false),
+            "version_match" => filters.versions.push(value.into()),
+            "name_match" => {
+                filters.names.extend(value.split('*').filter(|n| !n.is_empty()).map(|n| n.to_string()));
+            },
+            _ => debug!("Ignoring unsupported master server filter `{}`", key),
+        }
+    }
+    filters
+}
+
+/// The address a listed server is reachable at, if it is an IPv4 address.
+fn server_address(server: &GameServer) -> Option<SocketAddrV4> {
+    match server.socket_address() {
+        Some(SocketAddr::V4(a)) => Some(a),
+        _ => None,
+    }
+}
+
+/// Builds the reply to a query: the page of addresses after the seed.
+fn page(conn: &PgConnection, query: &Query) -> io::Result<Vec<u8>> {
+    let mut filters = parse_filter(&query.filter);
+    let mut reply = REPLY_HEADER.to_vec();
+    let terminator = SocketAddrV4::new(Ipv4Addr::new(0, 0, 0, 0), 0);
+
+    if let Some(names) = region_names(query.region_code) {
+        let existing: HashSet<String> = match ::schema::regions::table.load::<Region>(conn) {
+            Ok(r) => r.into_iter().map(|r| r.name).collect(),
+            Err(e) => return Err(io::Error::new(io::ErrorKind::Other, format!("{:?}", e))),
+        };
+        filters.regions = names.iter().filter(|n| existing.contains(**n)).map(|n| n.to_string()).collect();
+        if filters.regions.is_empty() {
+            push_address(&mut reply, &terminator);
+            return Ok(reply);
+        }
+    }
+
+    let mut servers = match search::load(conn, &filters) {
+        Ok(r) => r.servers,
+        Err(e) => return Err(io::Error::new(io::ErrorKind::Other, format!("{:?}", e))),
+    };
+    // Pages are found by the last address of the previous one, so the order has to be stable.
+    let demote = trust::demote_suspicious();
+    servers.sort_by_key(|s| (demote && s.suspicious, s.id));
+    let addresses: Vec<SocketAddrV4> = servers.iter().filter_map(server_address).collect();
+
+    let start = if query.seed == terminator {
+        0
+    } else {
+        addresses.iter().position(|a| *a == query.seed).map(|i| i + 1).unwrap_or(addresses.len())
+    };
+    let page: Vec<&SocketAddrV4> = addresses[start..].iter().take(PAGE_SIZE).collect();
+    for address in &page {
+        push_address(&mut reply, address);
+    }
+    if start + page.len() >= addresses.len() {
+        push_address(&mut reply, &terminator);
+    }
+    Ok(reply)
+}
+
+fn push_address(reply: &mut Vec<u8>, address: &SocketAddrV4) {
+    reply.extend_from_slice(&address.ip().octets());
+    reply.push((address.port() >> 8) as u8);
+    reply.push((address.port() & 0xFF) as u8);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+    use std::time::{Duration, Instant};
+
+    use super::{RateLimiter, QUERY_BURST};
+
+    #[test]
+    fn rate_limits_every_source_on_its_own() {
+        let mut limiter = RateLimiter::new();
+        let flood: IpAddr = "192.0.2.1".parse().unwrap();
+        let other: IpAddr = "192.0.2.2".parse().unwrap();
+        let start = Instant::now();
+        for _ in 0..QUERY_BURST as usize {
+            assert!(limiter.allow(flood, start));
+        }
+        assert!(!limiter.allow(flood, start));
+        assert!(limiter.allow(other, start));
+        // Two queries a second come back.
+        assert!(limiter.allow(flood, start + Duration::from_millis(500)));
+        assert!(!limiter.allow(flood, start + Duration::from_millis(500)));
+    }
+}
@@ -1,38 +1,142 @@
 //! Models of the various data structure used in the codebase.
 
 use std::default::Default;
+use std::net::{IpAddr, SocketAddr};
 
-use rustc_serialize::{Decoder, Decodable, DecoderHelpers};
+use rustc_serialize::{Decoder, Decodable, DecoderHelpers, Encoder, Encodable};
 use diesel::ExpressionMethods;
 
 use ::schema::game_servers;
 use ::schema::regions;
-use ::then_impl::Then;
+use ::schema::region_aliases;
+use ::schema::player_counts;
+use ::schema::users;
+use ::schema::favorites;
+use ::schema::play_history;
+use ::schema::audit_log;
+use ::schema::rcon_credentials;
+use ::schema::server_players;
+use ::schema::notifications;
+use ::schema::reports;
+use ::schema::reviews;
 
 #[derive(Debug, Clone, RustcEncodable, Queryable)]
 pub struct Region {
     pub id: i32,
     pub name: String,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
 }
 
 #[insertable_into(regions)]
 pub struct NewRegion {
     pub name: String,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
 }
 
+/// An alternative name for a region, such as `us-east` for `naeast`.
+/// Aliases are stored lowercased and matched case-insensitively.
 #[derive(Debug, Clone, RustcEncodable, Queryable)]
+pub struct RegionAlias {
+    pub id: i32,
+    pub alias: String,
+    pub region: String,
+}
+
+#[insertable_into(region_aliases)]
+pub struct NewRegionAlias {
+    pub alias: String,
+    pub region: String,
+}
+
+/// The `game_type`s of known Steam games, with their Steam app id and game directory.
+pub const STEAM_GAMES: &'static [(&'static str, u32, &'static str)] = &[
+    ("csgo", 730, "csgo"),
+    ("css", 240, "cstrike"),
+    ("tf2", 440, "tf"),
+    ("l4d2", 550, "left4dead2"),
+    ("gmod", 4000, "garrysmod"),
+];
+
+/// The operating systems servers run on, as stored in `GameServer::os`.
+pub const OPERATING_SYSTEMS: &'static [&'static str] = &["linux", "windows", "mac"];
+
+/// Port of servers listed without one.
+pub const DEFAULT_GAME_PORT: u16 = 27015;
+
+/// Parses the address of a server, such as `10.0.0.1`, `10.0.0.1:27016`, `2001:db8::1` or
+/// `[2001:db8::1]:27016`. `port` is used when the address has none, and must agree with it
+/// otherwise.
+pub fn parse_endpoint(address: &str, port: Option<i32>) -> Result<SocketAddr, String> {
+    let address = address.trim();
+    if let Ok(a) = address.parse::<SocketAddr>() {
+        return match port {
+            _ if a.port() == 0 => Err(format!("`{}` has no valid port", address)),
+            Some(p) if p != a.port() as i32 => Err(format!("`{}` has a port other than {}", address, p)),
+            _ => Ok(a),
+        };
+    }
+    let ip = match address.trim_left_matches('[').trim_right_matches(']').parse::<IpAddr>() {
+        Ok(ip) => ip,
+        Err(_) => return Err(format!("`{}` is not an IPv4 or IPv6 address", address)),
+    };
+    match port {
+        Some(p) if p >= 1 && p <= 65535 => Ok(SocketAddr::new(ip, p as u16)),
+        Some(p) => Err(format!("{} is not a port number", p)),
+        None => Ok(SocketAddr::new(ip, DEFAULT_GAME_PORT)),
+    }
+}
+
+/// Encoded to JSON by hand, see the `Encodable` impl below.
+#[derive(Debug, Clone, Queryable)]
 #[changeset_for(game_servers)]
 pub struct GameServer {
     pub id: i32,
     pub name: String,
     pub region: String,
     pub game_type: String,
-    pub ip: String,
     pub max_users: i32,
     pub current_users: i32,
     pub current_premium_users: Option<i32>,
     pub max_premium_users: Option<i32>,
     pub tags: Vec<String>,
+    pub motd: String,
+    pub owner_id: Option<i32>,
+    pub current_map: Option<String>,
+    pub map_rotation: Vec<String>,
+    pub password_protected: bool,
+    pub vac_secured: bool,
+    pub bots: i32,
+    pub dedicated: bool,
+    pub os: Option<String>,
+    pub version: Option<String>,
+    /// Whether another server of the same game type runs a newer version.
+    pub outdated: bool,
+    /// The IPv4 or IPv6 address of the server, without a port.
+    pub address: String,
+    pub port: i32,
+    /// The host name the server was listed by, which `address` was resolved from.
+    pub hostname: Option<String>,
+    /// Whether the server showed `verification_token`, proving it is run by whoever listed it.
+    pub verified: bool,
+    /// Only ever shown to the owner, see `verification`.
+    pub verification_token: String,
+    /// `pending`, `approved` or `rejected`, see `moderation`.
+    pub status: String,
+    /// Why the listing was rejected, if it was.
+    pub status_reason: Option<String>,
+    /// How far the reported player counts can be trusted, from 0 to 1. Like `suspicious`,
+    /// only shown to admins, see `trust`.
+    pub trust_score: f32,
+    pub suspicious: bool,
+    /// What servers are listed by, from 0 to 1, see `ranking`.
+    pub ranking_score: f32,
+    /// The average rating of the visible reviews, if there are any, see `reviews`.
+    pub rating_average: Option<f32>,
+    pub rating_count: i32,
+    /// What the game server proves itself with in heartbeats. Only ever shown to the owner.
+    pub heartbeat_secret: String,
 }
 
 #[derive(RustcEncodable)]
@@ -41,10 +145,31 @@ pub struct NewGameServer {
     pub name: String,
     pub region: String,
     pub game_type: String,
-    pub ip: String,
+    /// As given in JSON, until add_server resolves it. Resolving can take a while, so it is
+    /// not done while decoding.
+    pub address: String,
+    /// Set by add_server, from the JSON `port` or the one in `address`.
+    pub port: i32,
     pub max_users: i32,
     pub max_premium_users: Option<i32>,
     pub tags: Vec<String>,
+    pub motd: String,
+    /// Set by add_server to the signed in user, never read from JSON.
+    pub owner_id: Option<i32>,
+    pub password_protected: bool,
+    pub vac_secured: bool,
+    pub bots: i32,
+    pub dedicated: bool,
+    pub os: Option<String>,
+    pub version: Option<String>,
+    /// Set by add_server, never read from JSON.
+    pub hostname: Option<String>,
+    /// Set by add_server, never read from JSON.
+    pub verification_token: String,
+    /// Set by add_server, never read from JSON.
+    pub status: String,
+    /// Set by add_server, never read from JSON.
+    pub heartbeat_secret: String,
 }
 
 #[changeset_for(game_servers)]
@@ -52,10 +177,21 @@ pub struct UpdatedGameServer {
     pub name: Option<String>,
     pub region: Option<String>,
     pub game_type: Option<String>,
-    pub ip: Option<String>,
+    pub address: Option<String>,
+    pub port: Option<i32>,
     pub max_users: Option<i32>,
     pub max_premium_users: Option<i32>,
     pub tags: Option<Vec<String>>,
+    pub motd: Option<String>,
+    pub password_protected: Option<bool>,
+    pub vac_secured: Option<bool>,
+    pub bots: Option<i32>,
+    pub dedicated: Option<bool>,
+    pub os: Option<String>,
+    pub version: Option<String>,
+    /// `Some(None)` when the server is moved to a plain address.
+    pub hostname: Option<Option<String>>,
+    pub verified: Option<bool>,
 }
 
 impl Default for UpdatedGameServer {
@@ -64,44 +200,351 @@ impl Default for UpdatedGameServer {
             name: None,
             region: None,
             game_type: None,
-            ip: None,
+            address: None,
+            port: None,
             max_users: None,
             max_premium_users: None,
             tags: None,
+            motd: None,
+            password_protected: None,
+            vac_secured: None,
+            bots: None,
+            dedicated: None,
+            os: None,
+            version: None,
+            hostname: None,
+            verified: None,
         }
     }
 }
 
+impl UpdatedGameServer {
+    /// Whether the update leaves every column as it is.
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none() && self.region.is_none() && self.game_type.is_none() &&
+            self.address.is_none() && self.port.is_none() && self.max_users.is_none() &&
+            self.max_premium_users.is_none() && self.tags.is_none() && self.motd.is_none() &&
+            self.password_protected.is_none() && self.vac_secured.is_none() &&
+            self.bots.is_none() && self.dedicated.is_none() && self.os.is_none() &&
+            self.version.is_none() && self.hostname.is_none() && self.verified.is_none()
+    }
+}
+
 impl GameServer {
-    pub fn update(&mut self, updated: UpdatedGameServer) {
-        updated.name.then(|v| self.name = v);
-        updated.region.then(|v| self.region = v);
-        updated.game_type.then(|v| self.game_type = v);
-        updated.ip.then(|v| self.ip = v);
-        updated.max_users.then(|v| self.max_users = v);
-        updated.max_premium_users.then(|v| self.max_premium_users = Some(v));
-        updated.tags.then(|v| self.tags = v);
+    /// The Steam app id of the game the server runs, when it is a known Steam game.
+    pub fn steam_app_id(&self) -> Option<u32> {
+        let game = self.game_type.to_lowercase();
+        STEAM_GAMES.iter().find(|&&(g, _, _)| g == game).map(|&(_, app_id, _)| app_id)
+    }
+
+    /// The address and port the game server is reachable at.
+    pub fn socket_address(&self) -> Option<SocketAddr> {
+        self.address.parse::<IpAddr>().ok().map(|ip| SocketAddr::new(ip, self.port as u16))
+    }
+
+    /// The address and port of the server as one string, such as `10.0.0.1:27015` or
+    /// `[2001:db8::1]:27015`.
+    pub fn endpoint(&self) -> String {
+        match self.socket_address() {
+            Some(a) => a.to_string(),
+            None => format!("{}:{}", self.address, self.port),
+        }
+    }
+
+    /// A link that starts the game and joins the server, such as
+    /// `steam://connect/play.example.com:27015`. Host names are kept, so that the link
+    /// follows the server when its address changes.
+    pub fn connect_url(&self) -> String {
+        match self.hostname {
+            Some(ref h) => format!("steam://connect/{}:{}", h, self.port),
+            None => format!("steam://connect/{}", self.endpoint()),
+        }
+    }
+}
+
+#[insertable_into(player_counts)]
+pub struct NewPlayerCount {
+    pub server_id: i32,
+    pub region: String,
+    pub current_users: i32,
+}
+
+#[insertable_into(player_count_checks)]
+pub struct NewPlayerCountCheck {
+    pub server_id: i32,
+    pub reported_users: i32,
+    pub queried_users: i32,
+    pub max_users: i32,
+}
+
+/// Player counts over one hour or day, starting at `time` (in seconds since the epoch).
+#[derive(Debug, Clone, RustcEncodable, Queryable)]
+pub struct HistoryBucket {
+    pub time: i64,
+    pub average_users: f32,
+    pub min_users: i32,
+    pub max_users: i32,
+}
+
+/// A user, who authenticates by sending `token` in the `X-User-Token` header.
+#[derive(Debug, Clone, Queryable)]
+pub struct User {
+    pub id: i32,
+    pub name: String,
+    pub token: String,
+}
+
+#[insertable_into(users)]
+pub struct NewUser {
+    pub name: String,
+    pub token: String,
+}
+
+/// An entry of a user's favorites or play history. `ip` is the `GameServer::endpoint` of its
+/// server, and `time` is when the server was added or last played, in seconds since the epoch.
+#[derive(Debug, Clone, Queryable)]
+pub struct ServerListEntry {
+    pub id: i32,
+    pub server_id: Option<i32>,
+    pub ip: String,
+    pub name: String,
+    pub time: i64,
+}
+
+#[insertable_into(favorites)]
+pub struct NewFavorite {
+    pub user_id: i32,
+    pub server_id: Option<i32>,
+    pub ip: String,
+    pub name: String,
+}
+
+#[insertable_into(play_history)]
+pub struct NewPlayHistory {
+    pub user_id: i32,
+    pub server_id: Option<i32>,
+    pub ip: String,
+    pub name: String,
+}
+
+#[insertable_into(audit_log)]
+pub struct NewAuditEntry {
+    pub user_id: Option<i32>,
+    pub server_id: Option<i32>,
+    pub action: String,
+    pub detail: String,
+}
+
+/// How to reach the RCON of a server. The password is encrypted, see `secrets`.
+/// Without a port, RCON is on the game port of the server.
+#[derive(Debug, Clone, Queryable)]
+pub struct RconCredentials {
+    pub id: i32,
+    pub server_id: i32,
+    pub encrypted_password: Vec<u8>,
+    pub port: Option<i32>,
+    /// The address of the game server when the password was set. RCON is never sent
+    /// anywhere else.
+    pub address: String,
+}
+
+#[insertable_into(rcon_credentials)]
+pub struct NewRconCredentials {
+    pub server_id: i32,
+    pub encrypted_password: Vec<u8>,
+    pub port: Option<i32>,
+    pub address: String,
+}
+
+/// How many servers play a map right now, with how many players, and how many more
+/// servers have it in their rotation.
+#[derive(Debug, Clone, RustcEncodable, Queryable)]
+pub struct MapStats {
+    pub map: String,
+    pub servers: i64,
+    pub players: i64,
+    pub in_rotation: i64,
+}
+
+/// A message to a user, such as the decision on a server they listed. `time` is when it was
+/// sent, in seconds since the epoch, and `delivered` whether it was posted to their contact.
+#[derive(Debug, Clone, RustcEncodable, Queryable)]
+pub struct Notification {
+    pub id: i32,
+    pub server_id: Option<i32>,
+    pub message: String,
+    pub time: i64,
+    pub delivered: bool,
+}
+
+#[insertable_into(notifications)]
+pub struct NewNotification {
+    pub user_id: i32,
+    pub server_id: Option<i32>,
+    pub message: String,
+}
+
+/// A report of abuse on a server, see `reports`. `time` is when it was made, in seconds since
+/// the epoch.
+#[derive(Debug, Clone, RustcEncodable, Queryable)]
+pub struct Report {
+    pub id: i32,
+    pub server_id: i32,
+    pub reporter_id: Option<i32>,
+    pub category: String,
+    pub detail: String,
+    pub status: String,
+    pub time: i64,
+}
+
+#[insertable_into(reports)]
+pub struct NewReport {
+    pub server_id: i32,
+    pub reporter_id: Option<i32>,
+    pub category: String,
+    pub detail: String,
+}
+
+/// How many reports a server has, and how many of them are still open.
+#[derive(Debug, Clone, RustcEncodable, Queryable)]
+pub struct ReportCount {
+    pub server_id: i32,
+    pub open: i64,
+    pub total: i64,
+}
+
+/// A review of a server, see `reviews`. `time` is when it was written and `updated` when it
+/// was last edited, in seconds since the epoch.
+#[derive(Debug, Clone, RustcEncodable, Queryable)]
+pub struct Review {
+    pub id: i32,
+    pub server_id: i32,
+    pub user_id: i32,
+    pub user_name: String,
+    pub rating: i32,
+    pub body: String,
+    /// The answer of the server's owner.
+    pub reply: Option<String>,
+    pub hidden: bool,
+    pub time: i64,
+    pub updated: i64,
+}
+
+#[insertable_into(reviews)]
+pub struct NewReview {
+    pub server_id: i32,
+    pub user_id: i32,
+    pub rating: i32,
+    pub body: String,
+}
+
+/// A network in the blocklist, see `blocklist`. `listings` and `clients` are whether servers
+/// and API clients in it are blocked, `hits` is how often it was enforced and `time` is when
+/// it was added, in seconds since the epoch.
+#[derive(Debug, Clone, RustcEncodable, Queryable)]
+pub struct BlockedNetwork {
+    pub id: i32,
+    pub network: String,
+    pub reason: String,
+    pub listings: bool,
+    pub clients: bool,
+    pub hits: i64,
+    pub time: i64,
+}
+
+/// Servers or requests of a client refused because of a blocked network. `kind` is `listing`
+/// or `client`. `time` is when the first was refused, `last_time` the last.
+#[derive(Debug, Clone, RustcEncodable, Queryable)]
+pub struct BlocklistHit {
+    pub id: i32,
+    pub network_id: i32,
+    pub address: String,
+    pub kind: String,
+    pub detail: String,
+    pub hits: i32,
+    pub time: i64,
+    pub last_time: i64,
+}
+
+/// A player on a server. `duration` is how long they have been connected, in seconds.
+#[derive(Debug, Clone, RustcEncodable, Queryable)]
+pub struct Player {
+    pub name: String,
+    pub score: i32,
+    pub duration: f32,
+}
+
+#[insertable_into(server_players)]
+pub struct NewServerPlayer {
+    pub server_id: i32,
+    pub name: String,
+    pub score: i32,
+    pub duration: f32,
+}
+
+// Written out so that the JSON of a server also has its `connect_url`, and never has its
+// `verification_token` or `heartbeat_secret`.
+impl Encodable for GameServer {
+    fn encode<E: Encoder>(&self, e: &mut E) -> Result<(), E::Error> {
+        e.emit_struct("GameServer", 30, |e| {
+            try!(e.emit_struct_field("id", 0, |e| self.id.encode(e)));
+            try!(e.emit_struct_field("name", 1, |e| self.name.encode(e)));
+            try!(e.emit_struct_field("region", 2, |e| self.region.encode(e)));
+            try!(e.emit_struct_field("game_type", 3, |e| self.game_type.encode(e)));
+            try!(e.emit_struct_field("max_users", 4, |e| self.max_users.encode(e)));
+            try!(e.emit_struct_field("current_users", 5, |e| self.current_users.encode(e)));
+            try!(e.emit_struct_field("current_premium_users", 6, |e| self.current_premium_users.encode(e)));
+            try!(e.emit_struct_field("max_premium_users", 7, |e| self.max_premium_users.encode(e)));
+            try!(e.emit_struct_field("tags", 8, |e| self.tags.encode(e)));
+            try!(e.emit_struct_field("motd", 9, |e| self.motd.encode(e)));
+            try!(e.emit_struct_field("owner_id", 10, |e| self.owner_id.encode(e)));
+            try!(e.emit_struct_field("current_map", 11, |e| self.current_map.encode(e)));
+            try!(e.emit_struct_field("map_rotation", 12, |e| self.map_rotation.encode(e)));
+            try!(e.emit_struct_field("password_protected", 13, |e| self.password_protected.encode(e)));
+            try!(e.emit_struct_field("vac_secured", 14, |e| self.vac_secured.encode(e)));
+            try!(e.emit_struct_field("bots", 15, |e| self.bots.encode(e)));
+            try!(e.emit_struct_field("dedicated", 16, |e| self.dedicated.encode(e)));
+            try!(e.emit_struct_field("os", 17, |e| self.os.encode(e)));
+            try!(e.emit_struct_field("version", 18, |e| self.version.encode(e)));
+            try!(e.emit_struct_field("outdated", 19, |e| self.outdated.encode(e)));
+            try!(e.emit_struct_field("address", 20, |e| self.address.encode(e)));
+            try!(e.emit_struct_field("port", 21, |e| self.port.encode(e)));
+            try!(e.emit_struct_field("hostname", 22, |e| self.hostname.encode(e)));
+            try!(e.emit_struct_field("verified", 23, |e| self.verified.encode(e)));
+            try!(e.emit_struct_field("status", 24, |e| self.status.encode(e)));
+            try!(e.emit_struct_field("status_reason", 25, |e| self.status_reason.encode(e)));
+            try!(e.emit_struct_field("ranking_score", 26, |e| self.ranking_score.encode(e)));
+            try!(e.emit_struct_field("rating_average", 27, |e| self.rating_average.encode(e)));
+            try!(e.emit_struct_field("rating_count", 28, |e| self.rating_count.encode(e)));
+            try!(e.emit_struct_field("connect_url", 29, |e| self.connect_url().encode(e)));
+            Ok(())
+        })
     }
 }
 
 impl Decodable for NewGameServer {
     fn decode<D: Decoder>(d: &mut D) -> Result<NewGameServer, D::Error> {
-        d.read_struct("GameServer", 7, |d| {
+        d.read_struct("GameServer", 14, |d| {
             let name = match d.read_struct_field("name", 0, |d| { d.read_str()}) {
                 Ok(v) => v,
                 Err(_e) => { return Err(d.error("Couldnt Decode a name from GameServer JSON")); },
             };
+            // A missing region is left empty, so that add_server can infer one from the address.
             let region = match d.read_struct_field("region", 1, |d| { d.read_str()}) {
                 Ok(v) => v,
-                Err(_e) => { return Err(d.error("Couldnt Decode a region from GameServer JSON")); },
+                Err(_e) => String::new(),
             };
             let game_type = match d.read_struct_field("game_type", 2, |d| { d.read_str()}) {
                 Ok(v) => v,
                 Err(_e) => { return Err(d.error("Couldnt Decode a game_type from GameServer JSON")); },
             };
-            let ip = match d.read_struct_field("ip", 3, |d| { d.read_str()}) {
+            // `ip` is what `address` used to be called.
+            let address = match d.read_struct_field("address", 3, |d| { d.read_str()}) {
                 Ok(v) => v,
-                Err(_e) => { return Err(d.error("Couldnt Decode an IP from GameServer JSON")); },
+                Err(_e) => match d.read_struct_field("ip", 3, |d| { d.read_str()}) {
+                    Ok(v) => v,
+                    Err(_e) => { return Err(d.error("Couldnt Decode an address from GameServer JSON")); },
+                },
             };
             let max_users = match d.read_struct_field("max_users", 4, |d| { d.read_i32()}) {
                 Ok(v) => v,
@@ -118,15 +561,62 @@ impl Decodable for NewGameServer {
                 Ok(v) => v,
                 Err(_e) => { return Err(d.error("Could not decode tag as str."))}
             };
+            let motd: String = match d.read_struct_field("motd", 7, |d| { d.read_str()}) {
+                Ok(v) => v,
+                Err(_e) => String::new(),
+            };
+            let password_protected = match d.read_struct_field("password_protected", 8, |d| { d.read_bool()}) {
+                Ok(v) => v,
+                Err(_e) => false,
+            };
+            let vac_secured = match d.read_struct_field("vac_secured", 9, |d| { d.read_bool()}) {
+                Ok(v) => v,
+                Err(_e) => false,
+            };
+            let bots = match d.read_struct_field("bots", 10, |d| { d.read_i32()}) {
+                Ok(v) => v,
+                Err(_e) => 0,
+            };
+            let dedicated = match d.read_struct_field("dedicated", 11, |d| { d.read_bool()}) {
+                Ok(v) => v,
+                Err(_e) => true,
+            };
+            let os: Option<String> = match d.read_struct_field("os", 12, |d| { d.read_str()}) {
+                Ok(v) => {
+                    let v = v.to_lowercase();
+                    if !OPERATING_SYSTEMS.contains(&v.as_str()) {
+                        return Err(d.error("os must be one of linux, windows or mac"));
+                    }
+                    Some(v)
+                },
+                Err(_e) => None,
+            };
+            let version: Option<String> = match d.read_struct_field("version", 13, |d| { d.read_str()}) {
+                Ok(v) => Some(v),
+                Err(_e) => None,
+            };
 
             Ok(NewGameServer {
                 name: name,
                 region: region,
                 game_type: game_type,
-                ip: ip,
+                address: address,
+                port: 0,
                 max_users: max_users,
                 max_premium_users: max_premium_users,
-                tags: tags
+                tags: tags,
+                motd: motd,
+                owner_id: None,
+                password_protected: password_protected,
+                vac_secured: vac_secured,
+                bots: bots,
+                dedicated: dedicated,
+                os: os,
+                version: version,
+                hostname: None,
+                verification_token: String::new(),
+                status: String::new(),
+                heartbeat_secret: String::new(),
             })
         })
     }
@@ -139,11 +629,34 @@ Identifiable! {
         pub name: String,
         pub region: String,
         pub game_type: String,
-        pub ip: String,
         pub max_users: i32,
         pub current_users: i32,
         pub current_premium_users: Option<i32>,
         pub max_premium_users: Option<i32>,
         pub tags: Vec<String>,
+        pub motd: String,
+        pub owner_id: Option<i32>,
+        pub current_map: Option<String>,
+        pub map_rotation: Vec<String>,
+        pub password_protected: bool,
+        pub vac_secured: bool,
+        pub bots: i32,
+        pub dedicated: bool,
+        pub os: Option<String>,
+        pub version: Option<String>,
+        pub outdated: bool,
+        pub address: String,
+        pub port: i32,
+        pub hostname: Option<String>,
+        pub verified: bool,
+        pub verification_token: String,
+        pub status: String,
+        pub status_reason: Option<String>,
+        pub trust_score: f32,
+        pub suspicious: bool,
+        pub ranking_score: f32,
+        pub rating_average: Option<f32>,
+        pub rating_count: i32,
+        pub heartbeat_secret: String,
     }
 }
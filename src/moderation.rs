@@ -0,0 +1,86 @@
+//! Moderation of new listings.
+//!
+//! Servers are added `pending`, and only show up in listings and searches once an admin
+//! approves them. Owners with enough approved servers, and none rejected, are trusted and
+//! skip the queue. Owners are told about every decision through their notifications.
+
+use diesel;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use diesel::expression::dsl::sql;
+use diesel::types::BigInt;
+
+use ::audit;
+use ::in_transaction;
+use ::models::GameServer;
+use ::notifications;
+
+pub const PENDING: &'static str = "pending";
+pub const APPROVED: &'static str = "approved";
+pub const REJECTED: &'static str = "rejected";
+
+/// Approved servers an owner needs before their new servers are approved right away.
+const TRUSTED_APPROVALS: i64 = 3;
+
+/// Whether the owner of a new server is trusted to list servers without moderation.
+pub fn is_trusted(conn: &PgConnection, owner: i32) -> QueryResult<bool> {
+    let (approved, rejected): (i64, i64) = try!(sql::<(BigInt, BigInt)>(&format!(
+        "SELECT count(*) FILTER (WHERE status = '{}'), count(*) FILTER (WHERE status = '{}')
+         FROM game_servers
+         WHERE owner_id = {}", APPROVED, REJECTED, owner)).get_result(conn));
+    Ok(approved >= TRUSTED_APPROVALS && rejected == 0)
+}
+
+/// The status of a server that is being added by `owner`.
+pub fn initial_status(conn: &PgConnection, owner: Option<i32>) -> QueryResult<&'static str> {
+    match owner {
+        Some(o) if try!(is_trusted(conn, o)) => Ok(APPROVED),
+        _ => Ok(PENDING),
+    }
+}
+
+/// Approves or rejects a server, and tells its owner. The routes require a reason to reject.
+pub fn decide(conn: &PgConnection, server: &GameServer, approve: bool, reason: Option<&str>)
+              -> QueryResult<()> {
+    if approve {
+        set_status(conn, server, APPROVED, reason,
+                   &format!("Your server `{}` was approved, and is now listed.", server.name))
+    } else {
+        set_status(conn, server, REJECTED, reason,
+                   &format!("Your server `{}` was rejected: {}", server.name, reason.unwrap_or("")))
+    }
+}
+
+/// Takes a listed server out of listings and searches until an admin reviews it again.
+pub fn hold_for_review(conn: &PgConnection, server: &GameServer, reason: &str) -> QueryResult<()> {
+    set_status(conn, server, PENDING, Some(reason),
+               &format!("Your server `{}` is hidden until an admin reviews it: {}", server.name, reason))
+}
+
+/// Lists a server that was held for review again, when what held it turned out unfounded.
+pub fn restore(conn: &PgConnection, server: &GameServer) -> QueryResult<()> {
+    let message = format!("Your server `{}` is listed again, the reports on it were dismissed.",
+                          server.name);
+    set_status(conn, server, APPROVED, None, &message)
+}
+
+/// Changes the status of a server, records who did it in the audit log, and tells its owner.
+fn set_status(conn: &PgConnection, server: &GameServer, new_status: &str, reason: Option<&str>,
+              message: &str) -> QueryResult<()> {
+    use ::schema::game_servers::dsl::*;
+
+    let detail = match reason {
+        Some(r) => format!("{}: {}", new_status, r),
+        None => new_status.to_string(),
+    };
+    in_transaction(conn, || {
+        try!(diesel::update(game_servers.filter(id.eq(server.id)))
+                    .set((status.eq(new_status), status_reason.eq(reason.map(|r| r.to_string()))))
+                    .execute(conn));
+        try!(audit::record(conn, None, Some(server.id), "moderate_server", &detail));
+        match server.owner_id {
+            Some(owner) => notifications::notify(conn, owner, Some(server.id), message),
+            None => Ok(()),
+        }
+    })
+}
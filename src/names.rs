@@ -0,0 +1,247 @@
+//! The policy server names must follow, applied when servers are added or renamed.
+//!
+//! Names are normalized to NFC, stripped of control and invisible characters (such as zero
+//! width spaces and right-to-left overrides) and have their whitespace collapsed. What is
+//! left must fit the length limits and not contain a word of the blocklist at
+//! `NAME_BLOCKLIST`. Blocked words are also found when spelled with look-alike characters,
+//! as in `b4dw0rd`, `B.A.D.W.O.R.D` or `ｂａｄｗｏｒｄ`, but only as whole words: a blocked
+//! word inside a longer one, as in Scunthorpe, is fine.
+
+use std::env;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+use unicode_normalization::UnicodeNormalization;
+
+/// The shortest name allowed, in characters.
+pub const MIN_NAME_LENGTH: usize = 3;
+/// The longest name allowed, in characters.
+pub const MAX_NAME_LENGTH: usize = 64;
+
+lazy_static! {
+    /// The words of `NAME_BLOCKLIST`, already folded with `fold` and joined.
+    static ref BLOCKLIST: Vec<String> = load_blocklist();
+}
+
+/// Reads the blocklist, one word or phrase per line. Empty lines and lines starting with
+/// `#` are skipped.
+fn load_blocklist() -> Vec<String> {
+    let path = match env::var("NAME_BLOCKLIST") {
+        Ok(p) => p,
+        Err(_) => {
+            info!("NAME_BLOCKLIST is not set, server names are not checked for blocked words.");
+            return vec![];
+        }
+    };
+    let file = match File::open(&path) {
+        Ok(f) => f,
+        Err(e) => {
+            error!("Could not open the name blocklist at `{}`: {:?}", path, e);
+            return vec![];
+        }
+    };
+    BufReader::new(file).lines().filter_map(|l| l.ok())
+                        .map(|l| l.trim().to_string())
+                        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                        .map(|l| fold(&l).concat())
+                        .filter(|l| !l.is_empty())
+                        .collect()
+}
+
+/// The rule of the name policy a name broke.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NameError {
+    TooShort,
+    TooLong,
+    Blocked,
+}
+
+impl NameError {
+    /// A short name of the rule, for clients to tell errors apart.
+    pub fn rule(&self) -> &'static str {
+        match *self {
+            NameError::TooShort => "name_too_short",
+            NameError::TooLong => "name_too_long",
+            NameError::Blocked => "name_blocked_word",
+        }
+    }
+}
+
+impl fmt::Display for NameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NameError::TooShort => write!(f, "Server names must be at least {} characters long, \
+                                              not counting invisible characters.", MIN_NAME_LENGTH),
+            NameError::TooLong => write!(f, "Server names can be at most {} characters long.",
+                                         MAX_NAME_LENGTH),
+            NameError::Blocked => write!(f, "The server name contains a word that is not allowed."),
+        }
+    }
+}
+
+/// Cleans up a name, and checks it against the policy. Returns the name to store.
+pub fn sanitize(name: &str) -> Result<String, NameError> {
+    let mut cleaned = String::with_capacity(name.len());
+    let mut last_was_space = true;
+    for c in name.nfc() {
+        if c.is_whitespace() {
+            if !last_was_space {
+                cleaned.push(' ');
+            }
+            last_was_space = true;
+        } else if !c.is_control() && !is_invisible(c) {
+            cleaned.push(c);
+            last_was_space = false;
+        }
+    }
+    let cleaned = cleaned.trim_right().to_string();
+
+    let length = cleaned.chars().count();
+    if length < MIN_NAME_LENGTH {
+        return Err(NameError::TooShort);
+    }
+    if length > MAX_NAME_LENGTH {
+        return Err(NameError::TooLong);
+    }
+    if has_blocked_word(&cleaned) {
+        return Err(NameError::Blocked);
+    }
+    Ok(cleaned)
+}
+
+/// Whether a text contains a word of the blocklist, however it is spelled. Also used on
+/// other text users write, such as reviews.
+pub fn has_blocked_word(text: &str) -> bool {
+    contains_any(&fold(text), &BLOCKLIST)
+}
+
+/// Whether some run of adjacent folded words spells one of the blocked words. Runs, rather
+/// than single words, catch blocked words spelled apart, as in `b a d w o r d`.
+fn contains_any(words: &[String], blocked: &[String]) -> bool {
+    let longest = blocked.iter().map(|b| b.len()).max().unwrap_or(0);
+    for start in 0..words.len() {
+        let mut run = String::new();
+        for word in &words[start..] {
+            run.push_str(word);
+            if run.len() > longest {
+                break;
+            }
+            if blocked.iter().any(|b| *b == run) {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// Characters that take no space, or change how the text around them is shown: zero width
+/// spaces and joiners, bidirectional overrides and isolates, and fillers that render blank.
+fn is_invisible(c: char) -> bool {
+    match c {
+        '\u{00AD}' | '\u{034F}' | '\u{061C}' | '\u{115F}' | '\u{1160}' | '\u{17B4}' | '\u{17B5}' |
+        '\u{180E}' | '\u{200B}'...'\u{200F}' | '\u{202A}'...'\u{202E}' | '\u{2060}'...'\u{206F}' |
+        '\u{3164}' | '\u{FEFF}' | '\u{FFA0}' | '\u{FFF9}'...'\u{FFFB}' => true,
+        _ => false,
+    }
+}
+
+/// Folds a text into words for blocklist matching: compatibility characters (such as
+/// fullwidth letters) become plain ones, look-alike digits and symbols become the letters
+/// they stand for, invisible characters are dropped, and anything else that is not a letter
+/// separates words. Symbols at the end of a word are punctuation, not letters.
+fn fold(text: &str) -> Vec<String> {
+    let mut words = vec![];
+    // The letters of the current word, and whether each was written as a symbol.
+    let mut word: Vec<(char, bool)> = vec![];
+    for c in text.nfkc().flat_map(|c| c.to_lowercase()) {
+        if c.is_control() || is_invisible(c) {
+            continue;
+        }
+        let letter = match c {
+            '0' => Some(('o', false)),
+            '1' => Some(('i', false)),
+            '3' => Some(('e', false)),
+            '4' => Some(('a', false)),
+            '5' => Some(('s', false)),
+            '6' | '9' => Some(('g', false)),
+            '7' => Some(('t', false)),
+            '8' => Some(('b', false)),
+            '!' | '|' => Some(('i', true)),
+            '@' => Some(('a', true)),
+            '$' => Some(('s', true)),
+            '+' => Some(('t', true)),
+            c if c.is_alphabetic() => Some((c, false)),
+            _ => None,
+        };
+        match letter {
+            Some(l) => word.push(l),
+            None => end_word(&mut word, &mut words),
+        }
+    }
+    end_word(&mut word, &mut words);
+    words
+}
+
+fn end_word(word: &mut Vec<(char, bool)>, words: &mut Vec<String>) {
+    while word.last().map_or(false, |&(_, symbol)| symbol) {
+        word.pop();
+    }
+    if !word.is_empty() {
+        words.push(word.drain(..).map(|(c, _)| c).collect());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::iter::repeat;
+
+    use super::{contains_any, fold, sanitize, NameError};
+
+    fn blocked(text: &str) -> bool {
+        contains_any(&fold(text), &["badword".to_string(), "cunt".to_string()])
+    }
+
+    #[test]
+    fn sanitize_drops_invisible_characters() {
+        assert_eq!(sanitize("Good\u{202E}drawkcab\u{202C} server"),
+                   Ok("Gooddrawkcab server".to_string()));
+        assert_eq!(sanitize("zero\u{200B}\u{200D}width"), Ok("zerowidth".to_string()));
+        assert_eq!(sanitize("  lots \t of\n\n space  "), Ok("lots of space".to_string()));
+    }
+
+    #[test]
+    fn sanitize_counts_visible_characters() {
+        assert_eq!(sanitize("a\u{200B}\u{200B}\u{200B}b"), Err(NameError::TooShort));
+        let long: String = repeat('x').take(65).collect();
+        assert_eq!(sanitize(&long), Err(NameError::TooLong));
+        let accented: String = repeat('é').take(64).collect();
+        assert!(sanitize(&accented).is_ok());
+    }
+
+    #[test]
+    fn fold_undoes_look_alikes() {
+        assert_eq!(fold("ｂａｄｗｏｒｄ"), vec!["badword"]);
+        assert_eq!(fold("b4dw0rd"), vec!["badword"]);
+        assert_eq!(fold("B@DW0RD!"), vec!["badword"]);
+        assert_eq!(fold("bad\u{200B}word"), vec!["badword"]);
+        assert_eq!(fold("Best server, 4 life!"), vec!["best", "server", "a", "life"]);
+    }
+
+    #[test]
+    fn blocked_words_are_found_however_spelled() {
+        assert!(blocked("my badword server"));
+        assert!(blocked("B.A.D.W.O.R.D"));
+        assert!(blocked("b a d w o r d"));
+        assert!(blocked("bad-word"));
+        assert!(blocked("ｂａｄ ｗｏｒｄ!!!"));
+        assert!(blocked("\u{202E}b4dw0rd"));
+    }
+
+    #[test]
+    fn blocked_words_inside_other_words_are_fine() {
+        assert!(!blocked("Scunthorpe United fans"));
+        assert!(!blocked("badwordsmith"));
+        assert!(!blocked(""));
+    }
+}
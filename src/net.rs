@@ -0,0 +1,69 @@
+//! Guards for connections fula makes to addresses its users give it: game servers and
+//! notification webhooks.
+
+use std::io;
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use ::blocklist::Network;
+
+/// Networks that are not reachable on, or not meant for, the public internet: "this"
+/// network, private and shared (carrier-grade NAT) ranges, loopback, link-local, protocol
+/// assignments, documentation, benchmarking, multicast and the reserved rest.
+const NOT_PUBLIC: &'static [&'static str] = &[
+    "0.0.0.0/8", "10.0.0.0/8", "100.64.0.0/10", "127.0.0.0/8", "169.254.0.0/16",
+    "172.16.0.0/12", "192.0.0.0/24", "192.0.2.0/24", "192.168.0.0/16", "198.18.0.0/15",
+    "198.51.100.0/24", "203.0.113.0/24", "224.0.0.0/4", "240.0.0.0/4",
+    "::/96", "::1/128", "64:ff9b::/96", "100::/64", "2001:db8::/32", "fc00::/7", "fe80::/10",
+    "ff00::/8",
+];
+
+/// Whether `address` is on the public internet. IPv4 addresses mapped into IPv6 are judged
+/// as IPv4.
+pub fn is_public(address: IpAddr) -> bool {
+    !NOT_PUBLIC.iter().any(|n| Network::parse(n).map(|n| n.contains(address)).unwrap_or(false))
+}
+
+/// `TcpStream::connect` waits for as long as the OS lets it, which can be minutes for an
+/// address that drops packets. The connection is made on its own thread instead, which is
+/// given up on after `timeout`.
+pub fn connect_within(address: SocketAddr, timeout: Duration) -> io::Result<TcpStream> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        // Nobody is listening anymore when the connection took too long.
+        let _ = sender.send(TcpStream::connect(address));
+    });
+    match receiver.recv_timeout(timeout) {
+        Ok(result) => result,
+        Err(_) => Err(io::Error::new(io::ErrorKind::TimedOut, "connecting took too long")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn public(address: &str) -> bool {
+        is_public(address.parse().unwrap())
+    }
+
+    #[test]
+    fn public_addresses() {
+        for a in &["8.8.8.8", "1.1.1.1", "172.32.0.1", "100.128.0.1", "2606:4700::1111",
+                   "::ffff:8.8.8.8"] {
+            assert!(public(a), "{} is public", a);
+        }
+    }
+
+    #[test]
+    fn addresses_that_are_not_public() {
+        for a in &["0.0.0.0", "10.1.2.3", "100.64.0.1", "127.0.0.1", "169.254.169.254",
+                   "172.16.0.1", "172.31.255.255", "192.168.1.1", "192.0.2.10", "224.0.0.1",
+                   "255.255.255.255", "::", "::1", "fd00::1", "fe80::1", "ff02::1",
+                   "2001:db8::1", "::ffff:127.0.0.1", "::ffff:10.0.0.1"] {
+            assert!(!public(a), "{} is not public", a);
+        }
+    }
+}
@@ -0,0 +1,141 @@
+//! Messages to users, such as the decision on a server they listed.
+//!
+//! Every message is kept, so users can read them at `/user/notifications`. Users with a
+//! contact (an http(s) webhook URL, such as a Discord or Slack incoming webhook) also get
+//! them posted there by the `jobs` thread. A failed post is tried again, waiting twice as
+//! long after every failure, until `MAX_DELIVERY_ATTEMPTS` failed. Webhooks are only posted
+//! to on public addresses, and redirects are not followed, so that a contact can not reach
+//! services next to fula.
+
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::time::Duration;
+
+use diesel;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use diesel::expression::dsl::sql;
+use diesel::types::{Integer, Nullable, Text};
+use hyper;
+use hyper::Client;
+use hyper::client::RedirectPolicy;
+use hyper::header::ContentType;
+use hyper::net::{HttpStream, HttpsConnector, NetworkConnector, Openssl};
+use rustc_serialize::json;
+
+use ::models::NewNotification;
+use ::net;
+
+/// How long to wait on a contact webhook, to connect and for every read and write.
+const DELIVERY_TIMEOUT_SECS: u64 = 5;
+/// Notifications posted per run of the job, so that slow webhooks can not stall it for long.
+const DELIVERY_BATCH: i64 = 100;
+/// How many times a notification is posted before it is given up on, and how long to wait
+/// after the first failure. The wait doubles after every further one.
+const MAX_DELIVERY_ATTEMPTS: i32 = 8;
+const RETRY_SECS: i64 = 60;
+
+/// Whether `contact` is something notifications can be posted to.
+pub fn is_valid_contact(contact: &str) -> bool {
+    let lower = contact.to_lowercase();
+    (lower.starts_with("https://") || lower.starts_with("http://"))
+    && !contact.chars().any(char::is_whitespace)
+}
+
+/// Leaves a message for a user.
+pub fn notify(conn: &PgConnection, user_id: i32, server_id: Option<i32>, message: &str) -> QueryResult<()> {
+    use ::schema::notifications;
+
+    let notification = NewNotification {
+        user_id: user_id,
+        server_id: server_id,
+        message: message.into(),
+    };
+    diesel::insert(&notification).into(notifications::table).execute(conn).map(|_| ())
+}
+
+/// The JSON posted to a contact. Both Slack (`text`) and Discord (`content`) webhooks take it.
+#[derive(RustcEncodable)]
+struct Payload<'a> {
+    text: &'a str,
+    content: &'a str,
+    server_id: Option<i32>,
+}
+
+/// Posts the messages that are due to the contacts of their users. Messages whose webhook
+/// fails are tried again on a later run.
+pub fn deliver_all(conn: &PgConnection) -> QueryResult<()> {
+    let pending: Vec<(i32, Option<i32>, String, String)> = try!(
+        sql::<(Integer, Nullable<Integer>, Text, Text)>(&format!(
+            "SELECT n.id, n.server_id, n.message, u.contact
+             FROM notifications AS n JOIN users AS u ON u.id = n.user_id
+             WHERE n.delivered_at IS NULL AND n.next_attempt_at <= now() AND n.attempts < {}
+               AND u.contact IS NOT NULL
+             ORDER BY n.next_attempt_at, n.id
+             LIMIT {}", MAX_DELIVERY_ATTEMPTS, DELIVERY_BATCH)).load(conn));
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    let mut client = Client::with_connector(HttpsConnector::with_connector(Openssl::default(),
+                                                                           PublicConnector));
+    client.set_redirect_policy(RedirectPolicy::FollowNone);
+    client.set_read_timeout(Some(Duration::from_secs(DELIVERY_TIMEOUT_SECS)));
+    client.set_write_timeout(Some(Duration::from_secs(DELIVERY_TIMEOUT_SECS)));
+    for &(notification_id, server, ref text, ref contact) in &pending {
+        let payload = match json::encode(&Payload { text: text, content: text, server_id: server }) {
+            Ok(p) => p,
+            Err(e) => {
+                error!("Could not encode notification {} as json: {:?}", notification_id, e);
+                continue;
+            }
+        };
+        match client.post(&contact[..]).header(ContentType::json()).body(&payload[..]).send() {
+            Ok(ref r) if r.status.is_success() => {
+                try!(conn.execute(&format!(
+                    "UPDATE notifications SET delivered_at = now() WHERE id = {}", notification_id)));
+                continue;
+            },
+            Ok(r) => debug!("Contact of notification {} answered {}", notification_id, r.status),
+            Err(e) => debug!("Could not post notification {}: {:?}", notification_id, e),
+        }
+        // `attempts` on the right is the count before this one.
+        try!(conn.execute(&format!(
+            "UPDATE notifications
+             SET attempts = attempts + 1,
+                 next_attempt_at = now() + interval '{} seconds' * power(2, attempts)
+             WHERE id = {}", RETRY_SECS, notification_id)));
+    }
+    Ok(())
+}
+
+/// Connects to the host of a webhook only when every address it resolves to is public, and
+/// then to exactly the address that was checked, so that the name can not be pointed
+/// elsewhere in between. TLS is added on top by `HttpsConnector`, which always asks for
+/// `http`.
+struct PublicConnector;
+
+impl NetworkConnector for PublicConnector {
+    type Stream = HttpStream;
+
+    fn connect(&self, host: &str, port: u16, scheme: &str) -> hyper::Result<HttpStream> {
+        if scheme != "http" {
+            let e = io::Error::new(io::ErrorKind::InvalidInput, "invalid scheme for http");
+            return Err(e.into());
+        }
+        // Host names of IPv6 addresses keep their brackets in URLs.
+        let host = host.trim_left_matches('[').trim_right_matches(']');
+        let addresses: Vec<SocketAddr> = try!((host, port).to_socket_addrs()).collect();
+        if addresses.iter().any(|a| !net::is_public(a.ip())) {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied,
+                                      format!("`{}` is not a public address", host)).into());
+        }
+        let address = match addresses.into_iter().next() {
+            Some(a) => a,
+            None => return Err(io::Error::new(io::ErrorKind::NotFound,
+                                              format!("`{}` does not resolve", host)).into()),
+        };
+        let timeout = Duration::from_secs(DELIVERY_TIMEOUT_SECS);
+        Ok(HttpStream(try!(net::connect_within(address, timeout))))
+    }
+}
@@ -0,0 +1,95 @@
+//! The players on each server, as last reported in a heartbeat or fetched with A2S_PLAYER.
+//!
+//! A roster is kept for every server that was ever asked about, and is fetched from the
+//! game server again once it is older than `ROSTER_TTL_SECS`.
+
+use std::time::Duration;
+
+use diesel;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use diesel::expression::dsl::sql;
+use diesel::types::BigInt;
+
+use ::a2s::{self, A2sError};
+use ::in_transaction;
+use ::models::{GameServer, NewServerPlayer, Player};
+
+/// How long a roster is served before asking the game server again.
+pub const ROSTER_TTL_SECS: i64 = 30;
+/// How long to wait on a game server to list its players.
+const QUERY_TIMEOUT_SECS: u64 = 2;
+
+/// The players on a server, as of `fetched_at` (in seconds since the epoch).
+pub struct Roster {
+    pub fetched_at: i64,
+    /// Seconds since the roster was fetched.
+    pub age: i64,
+    pub players: Vec<Player>,
+}
+
+impl Roster {
+    pub fn is_fresh(&self) -> bool {
+        self.age <= ROSTER_TTL_SECS
+    }
+}
+
+/// The last roster of a server, best score first, if it was ever fetched.
+pub fn load(conn: &PgConnection, server: i32) -> QueryResult<Option<Roster>> {
+    use ::schema::server_players::dsl::*;
+
+    let times = try!(sql::<(BigInt, BigInt)>(&format!(
+        "SELECT extract(epoch FROM fetched_at)::bigint, extract(epoch FROM now() - fetched_at)::bigint
+         FROM player_rosters
+         WHERE server_id = {}", server)).load::<(i64, i64)>(conn));
+    let (fetched_at, age) = match times.into_iter().next() {
+        Some(t) => t,
+        None => return Ok(None),
+    };
+    let players = try!(server_players.select((name, score, duration))
+                                     .filter(server_id.eq(server))
+                                     .order(score.desc())
+                                     .load(conn));
+    Ok(Some(Roster { fetched_at: fetched_at, age: age, players: players }))
+}
+
+/// Replaces the roster of a server.
+pub fn store(conn: &PgConnection, server: i32, players: &[Player]) -> QueryResult<()> {
+    use ::schema::server_players::dsl::*;
+
+    in_transaction(conn, || {
+        try!(conn.execute(&format!(
+            "INSERT INTO player_rosters (server_id) VALUES ({})
+             ON CONFLICT (server_id) DO UPDATE SET fetched_at = now()", server)));
+        try!(diesel::delete(server_players.filter(server_id.eq(server))).execute(conn));
+        let new_players: Vec<NewServerPlayer> = players.iter().map(|p| NewServerPlayer {
+            server_id: server,
+            name: p.name.clone(),
+            score: p.score,
+            duration: p.duration,
+        }).collect();
+        if !new_players.is_empty() {
+            try!(diesel::insert(&new_players).into(server_players).execute(conn));
+        }
+        Ok(())
+    })
+}
+
+#[derive(Debug)]
+pub enum RefreshError {
+    /// The server is listed under a host name rather than an address.
+    NoAddress,
+    Query(A2sError),
+    Db(diesel::result::Error),
+}
+
+/// Asks a server for its players, and stores them as its roster.
+pub fn refresh(conn: &PgConnection, server: &GameServer) -> Result<(), RefreshError> {
+    let address = match server.socket_address() {
+        Some(a) => a,
+        None => return Err(RefreshError::NoAddress),
+    };
+    let players = try!(a2s::players(address, Duration::from_secs(QUERY_TIMEOUT_SECS))
+                           .map_err(RefreshError::Query));
+    store(conn, server.id, &players).map_err(RefreshError::Db)
+}
@@ -0,0 +1,69 @@
+//! The ranking score servers are listed by when no other order is asked for.
+//!
+//! The score blends how full a server is right now, how full it was on average over the last
+//! week, how many hours of that week it answered A2S queries, how many users made it a
+//! favorite, and how players rated it. It is then scaled by the server's trust score, so
+//! that faked player counts do not pay off, see `trust`. Scores are kept up to date by the
+//! `jobs` thread.
+
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+
+use ::reviews::{MIN_RATING, MAX_RATING};
+
+/// What each part adds to a perfect score of 1.
+const CURRENT_FILL_WEIGHT: f32 = 0.25;
+const WEEK_FILL_WEIGHT: f32 = 0.25;
+const UPTIME_WEIGHT: f32 = 0.2;
+const FAVORITES_WEIGHT: f32 = 0.15;
+const RATING_WEIGHT: f32 = 0.15;
+/// Favorites it takes for half of `FAVORITES_WEIGHT`. More favorites count for less and less.
+const FAVORITES_HALF: i32 = 10;
+/// Ratings are averaged with this many made up ratings of `PRIOR_RATING`, so that a single
+/// 5 does not beat a hundred 4s, and servers without ratings sit in the middle.
+const PRIOR_RATINGS: i32 = 5;
+const PRIOR_RATING: i32 = 3;
+/// Hours in the week looked back on.
+const WEEK_HOURS: i32 = 7 * 24;
+
+/// Recomputes the ranking score of every server.
+pub fn refresh_all(conn: &PgConnection) -> QueryResult<()> {
+    conn.execute(&format!("
+        UPDATE game_servers AS s SET ranking_score = r.score
+        FROM (
+            SELECT g.id, ((
+                {current} * CASE WHEN g.max_users > 0
+                                 THEN least(g.current_users::real / g.max_users, 1) ELSE 0 END
+              + {week} * CASE WHEN g.max_users > 0
+                              THEN least(coalesce(w.average_users, 0) / g.max_users, 1) ELSE 0 END
+              + {uptime} * least(coalesce(u.hours, 0)::real / {hours}, 1)
+              + {favorites} * coalesce(f.favorites, 0)::real / (coalesce(f.favorites, 0) + {half})
+              + {rating} * ((coalesce(g.rating_average, 0) * g.rating_count + {prior} * {priors})
+                            / (g.rating_count + {priors}) - {min}) / ({max} - {min})
+            ) * g.trust_score)::real AS score
+            FROM game_servers AS g
+            LEFT JOIN (
+                SELECT server_id, avg(average_users) AS average_users
+                FROM player_count_rollups
+                WHERE resolution = 'hour' AND bucket > now() - interval '{hours} hours'
+                GROUP BY server_id
+            ) AS w ON w.server_id = g.id
+            LEFT JOIN (
+                SELECT server_id, count(DISTINCT date_trunc('hour', checked_at)) AS hours
+                FROM player_count_checks
+                WHERE checked_at > now() - interval '{hours} hours'
+                GROUP BY server_id
+            ) AS u ON u.server_id = g.id
+            LEFT JOIN (
+                SELECT server_id, count(*) AS favorites
+                FROM favorites
+                WHERE server_id IS NOT NULL
+                GROUP BY server_id
+            ) AS f ON f.server_id = g.id
+        ) AS r
+        WHERE r.id = s.id AND s.ranking_score IS DISTINCT FROM r.score",
+        current = CURRENT_FILL_WEIGHT, week = WEEK_FILL_WEIGHT, uptime = UPTIME_WEIGHT,
+        favorites = FAVORITES_WEIGHT, half = FAVORITES_HALF, rating = RATING_WEIGHT,
+        prior = PRIOR_RATING, priors = PRIOR_RATINGS, min = MIN_RATING, max = MAX_RATING,
+        hours = WEEK_HOURS)).map(|_| ())
+}
@@ -0,0 +1,297 @@
+//! A client for the Source RCON protocol, used to run commands on game servers.
+//!
+//! Every packet is a little endian `i32` size, then an `i32` id, an `i32` type and a NUL
+//! terminated body, followed by one more NUL. Responses to a command may be split over many
+//! packets, so every command is followed by an empty `RESPONSE_VALUE` packet, which the
+//! server mirrors back once it has sent the whole response.
+
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::{Duration, Instant};
+
+use ::net::connect_within;
+
+const SERVERDATA_AUTH: i32 = 3;
+const SERVERDATA_AUTH_RESPONSE: i32 = 2;
+const SERVERDATA_EXECCOMMAND: i32 = 2;
+const SERVERDATA_RESPONSE_VALUE: i32 = 0;
+
+/// The smallest packet: id, type and two NULs.
+const MIN_PACKET_SIZE: i32 = 10;
+/// Servers split responses into packets of at most 4096 body bytes.
+const MAX_PACKET_SIZE: i32 = 4096 + MIN_PACKET_SIZE;
+/// The most output `exec` returns. Anything after it is cut off.
+pub const MAX_OUTPUT_SIZE: usize = 64 * 1024;
+
+#[derive(Debug)]
+pub enum RconError {
+    Io(io::Error),
+    /// The server refused the password.
+    AuthFailed,
+    /// The server sent something that is not the RCON protocol.
+    Protocol(String),
+}
+
+impl fmt::Display for RconError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RconError::Io(ref e) => write!(f, "RCON connection failed: {}", e),
+            RconError::AuthFailed => write!(f, "RCON password was refused"),
+            RconError::Protocol(ref msg) => write!(f, "RCON protocol error: {}", msg),
+        }
+    }
+}
+
+impl Error for RconError {
+    fn description(&self) -> &str {
+        match *self {
+            RconError::Io(ref e) => e.description(),
+            RconError::AuthFailed => "RCON password was refused",
+            RconError::Protocol(_) => "RCON protocol error",
+        }
+    }
+}
+
+impl From<io::Error> for RconError {
+    fn from(e: io::Error) -> RconError {
+        RconError::Io(e)
+    }
+}
+
+struct Packet {
+    id: i32,
+    kind: i32,
+    body: Vec<u8>,
+}
+
+/// An authenticated RCON connection.
+pub struct RconClient {
+    stream: TcpStream,
+    next_id: i32,
+    timeout: Duration,
+}
+
+impl RconClient {
+    /// Connects and authenticates. `timeout` applies to connecting, to every read and write,
+    /// and to the whole of every exchange with the server, so that a server trickling
+    /// packets can not hold on to the caller either.
+    pub fn connect(address: SocketAddr, password: &str, timeout: Duration)
+                   -> Result<RconClient, RconError> {
+        let stream = try!(connect_within(address, timeout));
+        try!(stream.set_read_timeout(Some(timeout)));
+        try!(stream.set_write_timeout(Some(timeout)));
+        let mut client = RconClient { stream: stream, next_id: 1, timeout: timeout };
+
+        let auth_id = client.take_id();
+        let started = Instant::now();
+        try!(write_packet(&mut client.stream, auth_id, SERVERDATA_AUTH, password.as_bytes()));
+        // Servers answer with an empty RESPONSE_VALUE before the AUTH_RESPONSE.
+        loop {
+            let packet = try!(client.receive(started));
+            if packet.kind != SERVERDATA_AUTH_RESPONSE {
+                continue;
+            }
+            return if packet.id == auth_id {
+                Ok(client)
+            } else {
+                Err(RconError::AuthFailed)
+            };
+        }
+    }
+
+    /// Runs a command, and returns what the server responded with, up to `MAX_OUTPUT_SIZE`
+    /// bytes.
+    pub fn exec(&mut self, command: &str) -> Result<String, RconError> {
+        let command_id = self.take_id();
+        let end_id = self.take_id();
+        let started = Instant::now();
+        let body = command.as_bytes();
+        try!(write_packet(&mut self.stream, command_id, SERVERDATA_EXECCOMMAND, body));
+        try!(write_packet(&mut self.stream, end_id, SERVERDATA_RESPONSE_VALUE, b""));
+
+        let mut output = vec![];
+        loop {
+            let packet = try!(self.receive(started));
+            if packet.id == end_id {
+                break;
+            }
+            if packet.id == command_id && packet.kind == SERVERDATA_RESPONSE_VALUE {
+                output.extend_from_slice(&packet.body);
+                if output.len() >= MAX_OUTPUT_SIZE {
+                    output.truncate(MAX_OUTPUT_SIZE);
+                    break;
+                }
+            }
+        }
+        Ok(String::from_utf8_lossy(&output).into_owned())
+    }
+
+    fn take_id(&mut self) -> i32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// Reads the next packet of an exchange that began at `started`.
+    fn receive(&mut self, started: Instant) -> Result<Packet, RconError> {
+        if started.elapsed() > self.timeout {
+            return Err(RconError::Io(io::Error::new(io::ErrorKind::TimedOut,
+                                                    "the server took too long to respond")));
+        }
+        read_packet(&mut self.stream)
+    }
+}
+
+fn write_packet<W: Write>(stream: &mut W, id: i32, kind: i32, body: &[u8])
+                          -> Result<(), RconError> {
+    if body.contains(&0) {
+        return Err(RconError::Protocol("commands can not contain NUL bytes".into()));
+    }
+    let size = body.len() as i32 + MIN_PACKET_SIZE;
+    if size > MAX_PACKET_SIZE {
+        return Err(RconError::Protocol("command is too long".into()));
+    }
+    let mut packet = Vec::with_capacity(size as usize + 4);
+    for n in &[size, id, kind] {
+        packet.extend_from_slice(&le_bytes(*n));
+    }
+    packet.extend_from_slice(body);
+    packet.extend_from_slice(&[0, 0]);
+    try!(stream.write_all(&packet));
+    Ok(())
+}
+
+fn read_packet<R: Read>(stream: &mut R) -> Result<Packet, RconError> {
+    let size = try!(read_i32(stream));
+    if size < MIN_PACKET_SIZE || size > MAX_PACKET_SIZE {
+        return Err(RconError::Protocol(format!("invalid packet size {}", size)));
+    }
+    let id = try!(read_i32(stream));
+    let kind = try!(read_i32(stream));
+    let mut body = vec![0u8; (size - 8) as usize];
+    try!(stream.read_exact(&mut body));
+    // Drop the body's NUL terminator and the empty string after it.
+    while body.last() == Some(&0) {
+        body.pop();
+    }
+    Ok(Packet { id: id, kind: kind, body: body })
+}
+
+fn read_i32<R: Read>(stream: &mut R) -> Result<i32, RconError> {
+    let mut buf = [0u8; 4];
+    try!(stream.read_exact(&mut buf));
+    Ok((buf[0] as i32) | (buf[1] as i32) << 8 | (buf[2] as i32) << 16 | (buf[3] as i32) << 24)
+}
+
+fn le_bytes(n: i32) -> [u8; 4] {
+    [n as u8, (n >> 8) as u8, (n >> 16) as u8, (n >> 24) as u8]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{SocketAddr, TcpListener, TcpStream};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    /// Runs `handler` on the first connection to a fake RCON server.
+    fn fake_server<F>(handler: F) -> SocketAddr where F: FnOnce(TcpStream) + Send + 'static {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            handler(stream);
+        });
+        address
+    }
+
+    /// Answers the AUTH packet the way Source servers do, accepting `password` only.
+    fn authenticate(stream: &mut TcpStream, password: &str) {
+        let auth = read_packet(stream).unwrap();
+        assert_eq!(auth.kind, SERVERDATA_AUTH);
+        let id = if auth.body == password.as_bytes() { auth.id } else { -1 };
+        write_packet(stream, auth.id, SERVERDATA_RESPONSE_VALUE, b"").unwrap();
+        write_packet(stream, id, SERVERDATA_AUTH_RESPONSE, b"").unwrap();
+    }
+
+    fn timeout() -> Duration {
+        Duration::from_secs(2)
+    }
+
+    #[test]
+    fn refused_password() {
+        let address = fake_server(|mut s| authenticate(&mut s, "hunter2"));
+        match RconClient::connect(address, "wrong", timeout()) {
+            Err(RconError::AuthFailed) => {},
+            Err(e) => panic!("expected AuthFailed, got {}", e),
+            Ok(_) => panic!("expected AuthFailed, got a connection"),
+        }
+    }
+
+    #[test]
+    fn output_split_over_packets() {
+        let address = fake_server(|mut s| {
+            authenticate(&mut s, "hunter2");
+            let command = read_packet(&mut s).unwrap();
+            assert_eq!(command.kind, SERVERDATA_EXECCOMMAND);
+            assert_eq!(command.body, b"status");
+            let end = read_packet(&mut s).unwrap();
+            for part in &[&b"hostname: Fula\n"[..], &b"players : 3"[..]] {
+                write_packet(&mut s, command.id, SERVERDATA_RESPONSE_VALUE, part).unwrap();
+            }
+            write_packet(&mut s, end.id, SERVERDATA_RESPONSE_VALUE, b"").unwrap();
+        });
+        let mut client = RconClient::connect(address, "hunter2", timeout()).unwrap();
+        assert_eq!(client.exec("status").unwrap(), "hostname: Fula\nplayers : 3");
+    }
+
+    #[test]
+    fn output_is_capped() {
+        let address = fake_server(|mut s| {
+            authenticate(&mut s, "hunter2");
+            let command = read_packet(&mut s).unwrap();
+            let chunk = vec![b'x'; 4096];
+            // Keeps sending until the client hangs up.
+            while write_packet(&mut s, command.id, SERVERDATA_RESPONSE_VALUE, &chunk).is_ok() {}
+        });
+        let mut client = RconClient::connect(address, "hunter2", timeout()).unwrap();
+        assert_eq!(client.exec("cvarlist").unwrap().len(), MAX_OUTPUT_SIZE);
+    }
+
+    #[test]
+    fn silent_server_times_out() {
+        let address = fake_server(|s| {
+            thread::sleep(Duration::from_secs(5));
+            drop(s);
+        });
+        let started = Instant::now();
+        match RconClient::connect(address, "hunter2", Duration::from_millis(200)) {
+            Err(RconError::Io(_)) => {},
+            Err(e) => panic!("expected a timeout, got {}", e),
+            Ok(_) => panic!("expected a timeout, got a connection"),
+        }
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn trickling_server_times_out() {
+        let address = fake_server(|mut s| {
+            authenticate(&mut s, "hunter2");
+            let command = read_packet(&mut s).unwrap();
+            // Never mirrors the end packet, but never goes quiet for long enough for a
+            // read to time out.
+            loop {
+                thread::sleep(Duration::from_millis(50));
+                if write_packet(&mut s, command.id + 100, SERVERDATA_RESPONSE_VALUE, b"").is_err() {
+                    return;
+                }
+            }
+        });
+        let timeout = Duration::from_millis(500);
+        let mut client = RconClient::connect(address, "hunter2", timeout).unwrap();
+        assert!(client.exec("status").is_err());
+    }
+}
@@ -0,0 +1,156 @@
+//! Reports of abuse on servers, such as fake player counts, scams or offensive names.
+//!
+//! Signed in users can report a server once until the report is resolved, and only so often
+//! per hour. Once enough users have open reports on a server, it is hidden until an admin
+//! reviews it, see `moderation::hold_for_review`. Only users whose accounts are older than
+//! `MIN_REPORTER_AGE` count towards that, so that a listing can not be hidden with accounts
+//! made for it. Dismissing the reports lists the server again.
+
+use std::env;
+
+use diesel;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use diesel::expression::dsl::sql;
+use diesel::result::OptionalExtension;
+use diesel::types::{BigInt, Integer, Nullable, Text};
+
+use ::in_transaction;
+use ::models::{GameServer, NewReport, Report, ReportCount};
+use ::moderation;
+use ::search::quote_literal;
+
+/// What a server can be reported for.
+pub const CATEGORIES: &'static [&'static str] = &["fake_players", "scam", "offensive_name",
+                                                  "offensive_content", "cheating", "other"];
+
+pub const OPEN: &'static str = "open";
+pub const DISMISSED: &'static str = "dismissed";
+pub const UPHELD: &'static str = "upheld";
+
+/// Reports a user can make in an hour.
+pub const REPORTS_PER_HOUR: i64 = 5;
+/// Users with open reports on a server it takes to hide it, unless `REPORT_HIDE_THRESHOLD` is set.
+const DEFAULT_HIDE_THRESHOLD: i64 = 5;
+/// How old an account has to be for its reports to count towards hiding a server.
+const MIN_REPORTER_AGE: &'static str = "7 days";
+/// Start of the status reason of servers hidden by reports.
+const HELD_FOR_REPORTS: &'static str = "reported by";
+/// The longest detail kept with a report.
+pub const MAX_DETAIL_LENGTH: usize = 1000;
+
+/// How many users with open reports hide a server.
+fn hide_threshold() -> i64 {
+    match env::var("REPORT_HIDE_THRESHOLD") {
+        Ok(t) => match t.parse() {
+            Ok(n) if n > 0 => n,
+            _ => {
+                warn!("REPORT_HIDE_THRESHOLD must be a positive integer, using {}.", DEFAULT_HIDE_THRESHOLD);
+                DEFAULT_HIDE_THRESHOLD
+            }
+        },
+        Err(_) => DEFAULT_HIDE_THRESHOLD,
+    }
+}
+
+/// How many reports a user made in the last hour.
+pub fn recent_reports(conn: &PgConnection, reporter: i32) -> QueryResult<i64> {
+    sql::<BigInt>(&format!(
+        "SELECT count(*) FROM reports
+         WHERE reporter_id = {} AND created_at > now() - interval '1 hour'", reporter)).get_result(conn)
+}
+
+/// Whether a user already has an open report on a server.
+pub fn has_open_report(conn: &PgConnection, server: i32, reporter: i32) -> QueryResult<bool> {
+    use ::schema::reports::dsl::*;
+
+    reports.select(id)
+           .filter(server_id.eq(server))
+           .filter(reporter_id.eq(reporter))
+           .filter(status.eq(OPEN))
+           .first::<i32>(conn).optional().map(|r| r.is_some())
+}
+
+/// Files a report, and hides the server when it crosses the threshold. Returns whether the
+/// server was hidden by this report.
+pub fn submit(conn: &PgConnection, server: &GameServer, report: &NewReport) -> QueryResult<bool> {
+    use ::schema::reports;
+
+    in_transaction(conn, || {
+        try!(diesel::insert(report).into(reports::table).execute(conn));
+        let reporters = try!(counted_reporters(conn, server.id));
+        if server.status != moderation::APPROVED || reporters < hide_threshold() {
+            return Ok(false);
+        }
+        try!(moderation::hold_for_review(conn, server,
+                                         &format!("{} {} users", HELD_FOR_REPORTS, reporters)));
+        Ok(true)
+    })
+}
+
+/// Users with open reports on a server whose accounts are old enough to count.
+fn counted_reporters(conn: &PgConnection, server: i32) -> QueryResult<i64> {
+    sql::<BigInt>(&format!(
+        "SELECT count(DISTINCT r.reporter_id)
+         FROM reports AS r JOIN users AS u ON u.id = r.reporter_id
+         WHERE r.server_id = {} AND r.status = '{}' AND u.created_at < now() - interval '{}'",
+        server, OPEN, MIN_REPORTER_AGE)).get_result(conn)
+}
+
+/// Reports with the given status, oldest first, optionally only those of one server.
+pub fn load(conn: &PgConnection, report_status: &str, server: Option<i32>) -> QueryResult<Vec<Report>> {
+    let condition = match server {
+        Some(s) => format!("AND server_id = {}", s),
+        None => String::new(),
+    };
+    select_reports(conn, &format!("status = {} {}", quote_literal(report_status), condition))
+}
+
+/// A report by id.
+pub fn find(conn: &PgConnection, report: i32) -> QueryResult<Option<Report>> {
+    select_reports(conn, &format!("id = {}", report)).map(|r| r.into_iter().next())
+}
+
+fn select_reports(conn: &PgConnection, condition: &str) -> QueryResult<Vec<Report>> {
+    sql::<(Integer, Integer, Nullable<Integer>, Text, Text, Text, BigInt)>(&format!(
+        "SELECT id, server_id, reporter_id, category, detail, status,
+                extract(epoch FROM created_at)::bigint
+         FROM reports
+         WHERE {}
+         ORDER BY created_at, id", condition)).load(conn)
+}
+
+/// Report counts of every reported server, those with the most open reports first.
+pub fn counts(conn: &PgConnection) -> QueryResult<Vec<ReportCount>> {
+    sql::<(Integer, BigInt, BigInt)>(&format!(
+        "SELECT server_id, count(*) FILTER (WHERE status = '{}'), count(*)
+         FROM reports
+         GROUP BY server_id
+         ORDER BY 2 DESC, 3 DESC, server_id", OPEN)).load(conn)
+}
+
+/// Closes a report as dismissed or upheld. Upholding a report rejects its server and closes
+/// every other open report on it too. A server hidden by reports is listed again once
+/// dismissals take it below the threshold.
+pub fn resolve(conn: &PgConnection, report: &Report, server: &GameServer, uphold: bool)
+               -> QueryResult<()> {
+    in_transaction(conn, || {
+        if !uphold {
+            try!(conn.execute(&format!(
+                "UPDATE reports SET status = '{}', resolved_at = now() WHERE id = {}",
+                DISMISSED, report.id)));
+            let held_for_reports = server.status == moderation::PENDING
+                                   && server.status_reason.as_ref()
+                                            .map_or(false, |r| r.starts_with(HELD_FOR_REPORTS));
+            if held_for_reports && try!(counted_reporters(conn, server.id)) < hide_threshold() {
+                try!(moderation::restore(conn, server));
+            }
+            return Ok(());
+        }
+        try!(conn.execute(&format!(
+            "UPDATE reports SET status = '{}', resolved_at = now()
+             WHERE server_id = {} AND (status = '{}' OR id = {})",
+            UPHELD, server.id, OPEN, report.id)));
+        moderation::decide(conn, server, false, Some(&format!("reported for {}", report.category)))
+    })
+}
@@ -0,0 +1,116 @@
+//! Ratings and reviews of servers by their players.
+//!
+//! A signed in user gives a server a rating from 1 to 5, with an optional short review, and
+//! can edit it as often as they like. The owner of the server can answer each review, and
+//! admins can hide reviews. The average rating of the visible reviews is kept on the
+//! server, so that it can be shown, searched for and ranked by without counting reviews.
+
+use diesel;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use diesel::expression::dsl::{now, sql};
+use diesel::types::{BigInt, Bool, Integer, Nullable, Text};
+
+use ::audit;
+use ::in_transaction;
+use ::models::{NewReview, Review};
+use ::search::quote_literal;
+
+pub const MIN_RATING: i32 = 1;
+pub const MAX_RATING: i32 = 5;
+/// The longest review, or answer to one, in characters.
+pub const MAX_REVIEW_LENGTH: usize = 500;
+
+/// Rates a server, or changes the rating the user gave it before. Returns whether the user
+/// already had a review on the server.
+pub fn save(conn: &PgConnection, server: i32, user: i32, given_rating: i32, text: &str)
+            -> QueryResult<bool> {
+    use ::schema::reviews::dsl::*;
+
+    in_transaction(conn, || {
+        let existed = try!(find_by_user(conn, server, user)).is_some();
+        if existed {
+            try!(diesel::update(reviews.filter(server_id.eq(server)).filter(user_id.eq(user)))
+                        .set((rating.eq(given_rating), body.eq(text), updated_at.eq(now)))
+                        .execute(conn));
+        } else {
+            let review = NewReview {
+                server_id: server,
+                user_id: user,
+                rating: given_rating,
+                body: text.into(),
+            };
+            try!(diesel::insert(&review).into(reviews).execute(conn));
+        }
+        try!(refresh_rating(conn, server));
+        Ok(existed)
+    })
+}
+
+/// Deletes the review a user wrote on a server. Returns false when there was none.
+pub fn delete(conn: &PgConnection, server: i32, user: i32) -> QueryResult<bool> {
+    use ::schema::reviews::dsl::*;
+
+    in_transaction(conn, || {
+        let deleted = try!(diesel::delete(reviews.filter(server_id.eq(server))
+                                                 .filter(user_id.eq(user)))
+                                   .execute(conn));
+        try!(refresh_rating(conn, server));
+        Ok(deleted > 0)
+    })
+}
+
+/// Sets the owner's answer to a review, or takes it back with `None`.
+pub fn reply(conn: &PgConnection, review: &Review, text: Option<&str>) -> QueryResult<()> {
+    let set = match text {
+        Some(t) => format!("reply = {}, replied_at = now()", quote_literal(t)),
+        None => "reply = NULL, replied_at = NULL".into(),
+    };
+    conn.execute(&format!("UPDATE reviews SET {} WHERE id = {}", set, review.id)).map(|_| ())
+}
+
+/// Hides a review from everyone but admins, or shows it again. Recorded in the audit log.
+pub fn set_hidden(conn: &PgConnection, review: &Review, hidden: bool) -> QueryResult<()> {
+    in_transaction(conn, || {
+        try!(conn.execute(&format!("UPDATE reviews SET hidden = {} WHERE id = {}", hidden, review.id)));
+        try!(refresh_rating(conn, review.server_id));
+        let action = if hidden { "hide_review" } else { "show_review" };
+        audit::record(conn, None, Some(review.server_id), action,
+                      &format!("review {} by user {}", review.id, review.user_id))
+    })
+}
+
+/// The reviews of a server, newest first. Hidden reviews are only loaded for admins.
+pub fn load(conn: &PgConnection, server: i32, include_hidden: bool) -> QueryResult<Vec<Review>> {
+    let condition = if include_hidden { "" } else { "AND NOT r.hidden" };
+    select_reviews(conn, &format!("r.server_id = {} {}", server, condition))
+}
+
+/// A review by id.
+pub fn find(conn: &PgConnection, review: i32) -> QueryResult<Option<Review>> {
+    select_reviews(conn, &format!("r.id = {}", review)).map(|r| r.into_iter().next())
+}
+
+/// The review a user wrote on a server, if any.
+pub fn find_by_user(conn: &PgConnection, server: i32, user: i32) -> QueryResult<Option<Review>> {
+    select_reviews(conn, &format!("r.server_id = {} AND r.user_id = {}", server, user))
+        .map(|r| r.into_iter().next())
+}
+
+fn select_reviews(conn: &PgConnection, condition: &str) -> QueryResult<Vec<Review>> {
+    sql::<(Integer, Integer, Integer, Text, Integer, Text, Nullable<Text>, Bool, BigInt, BigInt)>(&format!(
+        "SELECT r.id, r.server_id, r.user_id, u.name, r.rating, r.body, r.reply, r.hidden,
+                extract(epoch FROM r.created_at)::bigint, extract(epoch FROM r.updated_at)::bigint
+         FROM reviews AS r JOIN users AS u ON u.id = r.user_id
+         WHERE {}
+         ORDER BY r.created_at DESC, r.id DESC", condition)).load(conn)
+}
+
+/// Recomputes the average rating and review count kept on a server.
+fn refresh_rating(conn: &PgConnection, server: i32) -> QueryResult<()> {
+    conn.execute(&format!(
+        "UPDATE game_servers SET
+             rating_average = (SELECT avg(rating)::real FROM reviews WHERE server_id = {0} AND NOT hidden),
+             rating_count = (SELECT count(*) FROM reviews WHERE server_id = {0} AND NOT hidden)
+         WHERE id = {0}", server)).map(|_| ())
+}
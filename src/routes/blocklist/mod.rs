@@ -0,0 +1,193 @@
+//! Management of the network blocklist, see `blocklist`. Admins only.
+
+use rustc_serialize::json;
+use rustful::{Context, Response, header, StatusCode};
+
+use ::blocklist::{self, Network};
+use ::establish_connection;
+use ::routes::is_admin;
+
+/// Lists the blocked networks, with how often each was hit.
+pub fn get_blocklist(context: Context, mut response: Response) {
+    if !is_admin(&context) {
+        response.set_status(StatusCode::Forbidden);
+        return;
+    }
+    let conn = match establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Could not establish connection to DB: {}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+
+    let networks = match blocklist::load(&conn) {
+        Ok(n) => n,
+        Err(e) => {
+            error!("Could not execute query in get_blocklist: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    let encoded = match json::encode(&networks) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Could not encode the blocklist as json: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.send(format!("{{\"results\": {}, \"size\": {}}}", encoded, networks.len()));
+}
+
+/// Blocks a network, as
+/// `{"network": "192.0.2.0/24", "reason": "Fake servers.", "listings": true, "clients": false}`.
+/// `listings` and `clients` default to true. Servers already listed in a network that blocks
+/// listings are held for review.
+pub fn add_blocked_network(mut context: Context, mut response: Response) {
+    if !is_admin(&context) {
+        response.set_status(StatusCode::Forbidden);
+        return;
+    }
+    let conn = match establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Could not establish connection to DB: {}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+
+    let body = match context.body.read_json_body() {
+        Ok(b) => b,
+        Err(e) => {
+            error!("Could not read add_blocked_network json body {:?}", e);
+            response.set_status(StatusCode::BadRequest);
+            return;
+        }
+    };
+    let network = match body.find("network").and_then(|n| n.as_string()).map(Network::parse) {
+        Some(Ok(n)) => n,
+        Some(Err(e)) => {
+            response.set_status(StatusCode::BadRequest);
+            response.send(format!("{}", json::as_json(&e)));
+            return;
+        },
+        None => {
+            response.set_status(StatusCode::BadRequest);
+            response.send("\"network is required, as `192.0.2.0/24` or a single address!\"");
+            return;
+        }
+    };
+    let reason = body.find("reason").and_then(|r| r.as_string()).unwrap_or("").trim();
+    let listings = body.find("listings").and_then(|l| l.as_boolean()).unwrap_or(true);
+    let clients = body.find("clients").and_then(|c| c.as_boolean()).unwrap_or(true);
+    if !listings && !clients {
+        response.set_status(StatusCode::BadRequest);
+        response.send("\"A network has to block listings, clients or both!\"");
+        return;
+    }
+
+    match blocklist::add(&conn, &network, reason, listings, clients) {
+        Ok(Some(0)) => response.send(format!("\"Network `{}` blocked!\"", network)),
+        Ok(Some(held)) => {
+            response.send(format!("\"Network `{}` blocked! {} servers listed in it are hidden \
+                                   until an admin reviews them.\"", network, held));
+        },
+        Ok(None) => {
+            response.set_status(StatusCode::Conflict);
+            response.send(format!("\"Network `{}` is already blocked!\"", network));
+        },
+        Err(e) => {
+            error!("Could not block network {}: {:?}", network, e);
+            response.set_status(StatusCode::InternalServerError);
+        }
+    }
+}
+
+/// Unblocks a network. Its hits are deleted with it.
+pub fn delete_blocked_network(context: Context, mut response: Response) {
+    if !is_admin(&context) {
+        response.set_status(StatusCode::Forbidden);
+        return;
+    }
+    let conn = match establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Could not establish connection to DB: {}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+
+    let network_id: i32 = match context.variables.parse::<_, i32>("id") {
+        Ok(v) => v,
+        Err(Some(e)) => {
+            error!("Could not parse the ID as an i32: {:?}", e);
+            response.set_status(StatusCode::BadRequest);
+            return;
+        },
+        Err(None) => {
+            error!("Another Error occured during context variable parsing.");
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    match blocklist::delete(&conn, network_id) {
+        Ok(true) => response.send(format!("\"Network {} unblocked!\"", network_id)),
+        Ok(false) => response.set_status(StatusCode::NotFound),
+        Err(e) => {
+            error!("Could not unblock network {}: {:?}", network_id, e);
+            response.set_status(StatusCode::InternalServerError);
+        }
+    }
+}
+
+/// Lists the latest blocklist hits, newest first. `?network_id=` only lists the hits of one
+/// network.
+pub fn get_blocklist_hits(context: Context, mut response: Response) {
+    if !is_admin(&context) {
+        response.set_status(StatusCode::Forbidden);
+        return;
+    }
+    let conn = match establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Could not establish connection to DB: {}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+
+    let network = match context.query.get("network_id").map(|n| n.parse::<i32>()) {
+        Some(Ok(n)) => Some(n),
+        Some(Err(e)) => {
+            error!("network_id must be an integer: {:?}", e);
+            response.set_status(StatusCode::BadRequest);
+            return;
+        },
+        None => None,
+    };
+    let hits = match blocklist::load_hits(&conn, network) {
+        Ok(h) => h,
+        Err(e) => {
+            error!("Could not execute query in get_blocklist_hits: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    let encoded = match json::encode(&hits) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Could not encode blocklist hits as json: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.send(format!("{{\"results\": {}, \"size\": {}}}", encoded, hits.len()));
+}
@@ -0,0 +1,65 @@
+//! The catalog of maps played on listed servers.
+
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use diesel::expression::dsl::sql;
+use diesel::types::{BigInt, Text};
+use rustc_serialize::json;
+use rustful::{Context, Response, header, StatusCode};
+
+use ::establish_connection;
+use ::models::MapStats;
+use ::moderation;
+use ::search::quote_literal;
+
+/// Lists every map being played or in a rotation, busiest first.
+/// `?game_type=csgo` only counts servers of that game type.
+pub fn get_maps(context: Context, mut response: Response) {
+    let conn = match establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Could not establish connection to DB: {}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+
+    let game_type = context.query.get("game_type");
+    let maps = match map_stats(&conn, game_type.as_ref().map(|g| &g[..])) {
+        Ok(m) => m,
+        Err(e) => {
+            error!("Could not execute query in get_maps: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    let encoded = match json::encode(&maps) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Could not encode results of get_maps query as json: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.send(format!("{{\"results\": {}, \"size\": {}}}", encoded, maps.len()));
+}
+
+fn map_stats(conn: &PgConnection, game_type: Option<&str>) -> QueryResult<Vec<MapStats>> {
+    let condition = match game_type {
+        Some(g) => format!("status = '{}' AND game_type = {}", moderation::APPROVED, quote_literal(g)),
+        None => format!("status = '{}'", moderation::APPROVED),
+    };
+    sql::<(Text, BigInt, BigInt, BigInt)>(&format!(
+        "SELECT map, sum(playing)::bigint, sum(players)::bigint, sum(rotating)::bigint
+         FROM (SELECT current_map AS map, 1 AS playing, current_users AS players, 0 AS rotating
+               FROM game_servers
+               WHERE current_map IS NOT NULL AND {0}
+               UNION ALL
+               SELECT map, 0, 0, 1
+               FROM (SELECT DISTINCT id, unnest(map_rotation) AS map
+                     FROM game_servers
+                     WHERE {0}) AS rotations) AS maps
+         GROUP BY map
+         ORDER BY 3 DESC, 2 DESC, 4 DESC, map", condition)).load(conn)
+}
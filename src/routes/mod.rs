@@ -1,38 +1,196 @@
 //! Routes used by the REST API
 
-use std::collections::HashSet;
+use std::cmp;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::str;
 
 use diesel::prelude::*;
 use diesel::pg::PgConnection;
+use diesel::result::{Error as DieselError, OptionalExtension};
+use rustful::{Context, Response, StatusCode};
 
-use models::Region;
+use history::Resolution;
+use models::{GameServer, Region, RegionAlias, User};
 
+pub mod blocklist;
+pub mod map;
+pub mod moderation;
 pub mod server;
 pub mod region;
+pub mod report;
+pub mod stats;
+pub mod user;
 
 pub enum AllowedRegion<T> {
     Failure(T),
-    Success,
+    /// The canonical names of the checked regions, in the order they were given.
+    Success(Vec<String>),
     Panic,
 }
 
+/// Checks that every possible region names an existing region, either directly or through
+/// one of its aliases. Names are matched case-insensitively.
 pub fn regions_allowed<'a, 'b, I>(conn: &'a PgConnection, possible_regions: I)
                     -> AllowedRegion<HashSet<&'b str>> where I: Iterator<Item=&'b str> {
-    use ::schema::regions::dsl::*;
-
-    let all_regions: HashSet<String> = match regions.load::<Region>(conn) {
-        Ok(c) => c.into_iter().map(|v| v.name).collect(),
+    let lookup = match region_lookup(conn) {
+        Ok(l) => l,
         Err(e) => {
             error!("Could not execute query in regions_allowed: {:?}", e);
             return AllowedRegion::Panic;
         }
     };
 
-    let failed: HashSet<&'b str> = possible_regions.filter(|r| !all_regions.contains(*r)).collect();
+    let mut canonical = vec![];
+    let mut failed: HashSet<&'b str> = HashSet::new();
+    for r in possible_regions {
+        match lookup.get(&r.to_lowercase()) {
+            Some(name) => canonical.push(name.clone()),
+            None => { failed.insert(r); },
+        }
+    }
     if failed.len() == 0 {
-        AllowedRegion::Success
+        AllowedRegion::Success(canonical)
     } else {
         AllowedRegion::Failure(failed)
     }
 }
 
+/// Maps every lowercased region name and alias to the canonical region name.
+/// Region names take precedence over aliases.
+fn region_lookup(conn: &PgConnection) -> Result<HashMap<String, String>, DieselError> {
+    use ::schema::regions::dsl::regions;
+    use ::schema::region_aliases::dsl::region_aliases;
+
+    let mut lookup = HashMap::new();
+    for r in try!(regions.load::<Region>(conn)) {
+        lookup.insert(r.name.to_lowercase(), r.name);
+    }
+    for a in try!(region_aliases.load::<RegionAlias>(conn)) {
+        lookup.entry(a.alias.to_lowercase()).or_insert(a.region);
+    }
+    Ok(lookup)
+}
+
+/// Checks the `X-Admin-Token` header of a request against the `ADMIN_TOKEN` env variable.
+/// If `ADMIN_TOKEN` is not set, no request is considered to come from an admin.
+pub fn is_admin(context: &Context) -> bool {
+    let admin_token = match env::var("ADMIN_TOKEN") {
+        Ok(t) => t,
+        Err(_) => {
+            warn!("ADMIN_TOKEN is not set, admin routes are disabled.");
+            return false;
+        }
+    };
+    match context.headers.get_raw("X-Admin-Token") {
+        Some(values) if values.len() == 1 => values[0] == admin_token.as_bytes(),
+        _ => false,
+    }
+}
+
+/// The user whose token is in the `X-User-Token` header of a request, if any.
+pub fn current_user(context: &Context, conn: &PgConnection) -> QueryResult<Option<User>> {
+    use ::schema::users::dsl::*;
+
+    let given = match context.headers.get_raw("X-User-Token") {
+        Some(values) if values.len() == 1 => match str::from_utf8(&values[0]) {
+            Ok(t) => t.to_owned(),
+            Err(_) => return Ok(None),
+        },
+        _ => return Ok(None),
+    };
+    users.select((id, name, token)).filter(token.eq(given)).first::<User>(conn).optional()
+}
+
+/// The signed in user of a request. Sets the response status and returns `None` when there
+/// is no such user.
+pub fn require_user(context: &Context, conn: &PgConnection, response: &mut Response) -> Option<User> {
+    match current_user(context, conn) {
+        Ok(Some(u)) => Some(u),
+        Ok(None) => {
+            response.set_status(StatusCode::Unauthorized);
+            None
+        },
+        Err(e) => {
+            error!("Could not look up the user of a request: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            None
+        }
+    }
+}
+
+/// The server in the `id` variable of a request, when the signed in user owns it or the
+/// request comes from an admin. The user is `None` for an admin that is not signed in.
+/// Sets the response status and returns `None` otherwise.
+pub fn require_owned_server(context: &Context, conn: &PgConnection, response: &mut Response)
+                            -> Option<(Option<User>, GameServer)> {
+    use ::schema::game_servers::dsl::*;
+
+    let admin = is_admin(context);
+    let user = if admin {
+        match current_user(context, conn) {
+            Ok(u) => u,
+            Err(e) => {
+                error!("Could not look up the user of a request: {:?}", e);
+                response.set_status(StatusCode::InternalServerError);
+                return None;
+            }
+        }
+    } else {
+        match require_user(context, conn, response) {
+            Some(u) => Some(u),
+            None => return None,
+        }
+    };
+    let server_id: i32 = match context.variables.parse("id") {
+        Ok(i) => i,
+        Err(e) => {
+            error!("The id must be an integer: {:?}", e);
+            response.set_status(StatusCode::BadRequest);
+            return None;
+        }
+    };
+    let server: GameServer = match game_servers.filter(id.eq(server_id)).first(conn) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Server ID does not exist: {:?}", e);
+            response.set_status(StatusCode::BadRequest);
+            return None;
+        }
+    };
+    if !admin && server.owner_id != user.as_ref().map(|u| u.id) {
+        response.set_status(StatusCode::Forbidden);
+        return None;
+    }
+    Some((user, server))
+}
+
+/// Whether the requester may see a server. Servers waiting on moderation, or rejected, are
+/// only shown to their owner and admins.
+pub fn can_view(context: &Context, conn: &PgConnection, server: &GameServer) -> QueryResult<bool> {
+    if server.status == ::moderation::APPROVED || is_admin(context) {
+        return Ok(true);
+    }
+    let viewer = try!(current_user(context, conn));
+    Ok(viewer.map_or(false, |u| Some(u.id) == server.owner_id))
+}
+
+/// Reads the `resolution` (`hour` or `day`) and `buckets` query parameters of history routes.
+/// The number of buckets is capped to how many are kept for the resolution.
+pub fn history_window(context: &Context) -> Result<(Resolution, i32), String> {
+    let resolution = match context.query.get("resolution") {
+        Some(r) => match Resolution::from_str(&r) {
+            Some(res) => res,
+            None => return Err(format!("Unknown resolution `{}`, expected `hour` or `day`.", r)),
+        },
+        None => Resolution::Hour,
+    };
+    let buckets = match context.query.get("buckets") {
+        Some(b) => match b.parse::<i32>() {
+            Ok(n) if n > 0 => cmp::min(n, resolution.retained_buckets()),
+            _ => return Err(format!("buckets must be a positive integer, not `{}`.", b)),
+        },
+        None => resolution.default_buckets(),
+    };
+    Ok((resolution, buckets))
+}
@@ -0,0 +1,228 @@
+//! The queue of listings waiting on an admin, see `moderation`.
+
+use diesel::prelude::*;
+use rustc_serialize::json;
+use rustful::{Context, Response, header, StatusCode};
+
+use ::establish_connection;
+use ::models::GameServer;
+use ::moderation;
+use ::reviews;
+use ::routes::is_admin;
+use ::trust;
+
+/// Lists the servers waiting on moderation, oldest first. Admins only.
+pub fn get_moderation_queue(context: Context, mut response: Response) {
+    use ::schema::game_servers::dsl::*;
+
+    if !is_admin(&context) {
+        response.set_status(StatusCode::Forbidden);
+        return;
+    }
+    let conn = match establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Could not establish connection to DB: {}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+
+    let pending: Vec<GameServer> = match game_servers.filter(status.eq(moderation::PENDING))
+                                                     .order(id.asc()).load(&conn) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Could not execute query in get_moderation_queue: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    let encoded = match json::encode(&pending) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Could not encode the moderation queue as json: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.send(format!("{{\"results\": {}, \"size\": {}}}", encoded, pending.len()));
+}
+
+/// Lists the servers flagged as faking their player counts, least trusted first, with what
+/// gave them away. Admins only.
+pub fn get_suspicious_servers(context: Context, mut response: Response) {
+    if !is_admin(&context) {
+        response.set_status(StatusCode::Forbidden);
+        return;
+    }
+    let conn = match establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Could not establish connection to DB: {}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+
+    let suspicious = match trust::load_signals(&conn, true) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Could not execute query in get_suspicious_servers: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    let encoded = match json::encode(&suspicious) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Could not encode suspicious servers as json: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.send(format!("{{\"results\": {}, \"size\": {}}}", encoded, suspicious.len()));
+}
+
+/// Approves or rejects a server, as `{"decision": "approve"}` or
+/// `{"decision": "reject", "reason": "Not a game server."}`. The owner is notified either way.
+/// Admins only.
+pub fn moderate_server(mut context: Context, mut response: Response) {
+    use ::schema::game_servers::dsl::*;
+
+    if !is_admin(&context) {
+        response.set_status(StatusCode::Forbidden);
+        return;
+    }
+    let conn = match establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Could not establish connection to DB: {}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+
+    let server_id: i32 = match context.variables.parse::<_, i32>("id") {
+        Ok(v) => v,
+        Err(Some(e)) => {
+            error!("Could not parse the ID as an i32: {:?}", e);
+            response.set_status(StatusCode::BadRequest);
+            return;
+        },
+        Err(None) => {
+            error!("Another Error occured during context variable parsing.");
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    let server: GameServer = match game_servers.filter(id.eq(server_id)).first(&conn) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Server ID does not exist: {:?}", e);
+            response.set_status(StatusCode::NotFound);
+            return;
+        }
+    };
+    let body = match context.body.read_json_body() {
+        Ok(b) => b,
+        Err(e) => {
+            error!("Could not read moderate_server json body {:?}", e);
+            response.set_status(StatusCode::BadRequest);
+            return;
+        }
+    };
+    let reason = body.find("reason").and_then(|r| r.as_string())
+                     .map(|r| r.trim()).and_then(|r| if r.is_empty() { None } else { Some(r) });
+    let approve = match body.find("decision").and_then(|d| d.as_string()) {
+        Some("approve") => true,
+        Some("reject") if reason.is_some() => false,
+        Some("reject") => {
+            response.set_status(StatusCode::BadRequest);
+            response.send("\"Rejecting a server needs a reason!\"");
+            return;
+        },
+        _ => {
+            response.set_status(StatusCode::BadRequest);
+            response.send("\"decision must be `approve` or `reject`!\"");
+            return;
+        }
+    };
+
+    if let Err(e) = moderation::decide(&conn, &server, approve, reason) {
+        error!("Could not moderate server {}: {:?}", server_id, e);
+        response.set_status(StatusCode::InternalServerError);
+        return;
+    }
+    let new_status = if approve { moderation::APPROVED } else { moderation::REJECTED };
+    response.send(format!("{{\"server_id\": {}, \"status\": \"{}\"}}", server_id, new_status));
+}
+
+/// Hides a review, as `{"hidden": true}`, or shows it again with `{"hidden": false}`.
+/// Hidden reviews do not count towards the server's rating. Admins only.
+pub fn moderate_review(mut context: Context, mut response: Response) {
+    if !is_admin(&context) {
+        response.set_status(StatusCode::Forbidden);
+        return;
+    }
+    let conn = match establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Could not establish connection to DB: {}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+
+    let review_id: i32 = match context.variables.parse::<_, i32>("id") {
+        Ok(v) => v,
+        Err(Some(e)) => {
+            error!("Could not parse the ID as an i32: {:?}", e);
+            response.set_status(StatusCode::BadRequest);
+            return;
+        },
+        Err(None) => {
+            error!("Another Error occured during context variable parsing.");
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    let review = match reviews::find(&conn, review_id) {
+        Ok(Some(r)) => r,
+        Ok(None) => {
+            response.set_status(StatusCode::NotFound);
+            return;
+        },
+        Err(e) => {
+            error!("Could not load review {} in moderate_review: {:?}", review_id, e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    let body = match context.body.read_json_body() {
+        Ok(b) => b,
+        Err(e) => {
+            error!("Could not read moderate_review json body {:?}", e);
+            response.set_status(StatusCode::BadRequest);
+            return;
+        }
+    };
+    let hidden = match body.find("hidden").and_then(|h| h.as_boolean()) {
+        Some(h) => h,
+        None => {
+            response.set_status(StatusCode::BadRequest);
+            response.send("\"hidden must be `true` or `false`!\"");
+            return;
+        }
+    };
+
+    if let Err(e) = reviews::set_hidden(&conn, &review, hidden) {
+        error!("Could not moderate review {}: {:?}", review_id, e);
+        response.set_status(StatusCode::InternalServerError);
+        return;
+    }
+    response.send(format!("{{\"review_id\": {}, \"hidden\": {}}}", review_id, hidden));
+}
@@ -0,0 +1,181 @@
+
+use diesel;
+use diesel::prelude::*;
+use rustful::{Context, Response, header, StatusCode};
+use rustc_serialize::json;
+
+use ::establish_connection;
+use ::models::{NewRegionAlias, RegionAlias};
+use ::routes::{is_admin, regions_allowed, AllowedRegion};
+
+pub fn get_all_region_aliases(context: Context, mut response: Response) {
+    use ::schema::region_aliases::dsl::*;
+
+    if !is_admin(&context) {
+        response.set_status(StatusCode::Forbidden);
+        return;
+    }
+    let conn = match establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Could not establish connection to DB: {}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+
+    response.headers_mut().set(header::ContentType::json());
+
+    let all: Vec<RegionAlias> = match region_aliases.order(alias.asc()).load(&conn) {
+        Ok(r) => r,
+        Err(e) => {
+            error!("Could not execute query in get_all_region_aliases: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+
+    let encoded = match json::encode(&all) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Could not encode results of get_all_region_aliases query as json: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+
+    response.send(format!("{{\"results\": {}, \"size\": {}}}", encoded, all.len()));
+}
+
+pub fn add_region_alias(mut context: Context, mut response: Response) {
+    use ::schema::region_aliases;
+
+    if !is_admin(&context) {
+        response.set_status(StatusCode::Forbidden);
+        return;
+    }
+    let conn = match establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Could not establish connection to DB: {}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+
+    let body = match context.body.read_json_body() {
+        Ok(b) => b,
+        Err(e) => {
+            error!("Could not decode JSON body in add_region_alias: {}", e);
+            response.set_status(StatusCode::BadRequest);
+            return;
+        }
+    };
+    let alias_name: String = match body.find("alias").and_then(|v| v.as_string()) {
+        Some(s) => s.trim().to_lowercase(),
+        None => {
+            error!("add_region_alias must have a string `alias` parameter.");
+            response.set_status(StatusCode::BadRequest);
+            return;
+        }
+    };
+    let target_region: &str = match body.find("region").and_then(|v| v.as_string()) {
+        Some(s) => s,
+        None => {
+            error!("add_region_alias must have a string `region` parameter.");
+            response.set_status(StatusCode::BadRequest);
+            return;
+        }
+    };
+    if alias_name.is_empty() {
+        response.set_status(StatusCode::BadRequest);
+        response.send("\"An alias can not be empty!\"");
+        return;
+    }
+
+    // An alias that already resolves to something would either shadow a region or be a duplicate.
+    match regions_allowed(&conn, Some(alias_name.as_str()).into_iter()) {
+        AllowedRegion::Success(existing) => {
+            response.set_status(StatusCode::Conflict);
+            response.send(format!("\"`{}` already refers to region `{}`!\"", alias_name, existing[0]));
+            return;
+        },
+        AllowedRegion::Failure(_) => {},
+        AllowedRegion::Panic => {
+            error!("Failed in add_region_alias/regions_allowed, can not complete request.");
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    }
+    let canonical = match regions_allowed(&conn, Some(target_region).into_iter()) {
+        AllowedRegion::Success(mut canonical) => canonical.remove(0),
+        AllowedRegion::Failure(failures) => {
+            response.set_status(StatusCode::BadRequest);
+            response.send(format!("\"Regions `{:?}` do not exist in the Database!\"", failures));
+            return;
+        },
+        AllowedRegion::Panic => {
+            error!("Failed in add_region_alias/regions_allowed, can not complete request.");
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+
+    let new_alias = NewRegionAlias {
+        alias: alias_name,
+        region: canonical,
+    };
+    match diesel::insert(&new_alias).into(region_aliases::table).execute(&conn) {
+        Ok(_) => {},
+        Err(e) => {
+            error!("Failed to insert new alias into region_aliases: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    }
+    response.send(format!("\"Alias `{}` for region `{}` added to DB!\"",
+                          &new_alias.alias, &new_alias.region));
+}
+
+pub fn delete_region_alias(context: Context, mut response: Response) {
+    use ::schema::region_aliases::dsl::*;
+
+    if !is_admin(&context) {
+        response.set_status(StatusCode::Forbidden);
+        return;
+    }
+    let conn = match establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Could not establish connection to DB: {}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    let alias_id: i32 = match context.variables.parse("id") {
+        Ok(i) => i,
+        Err(Some(e)) => {
+            error!("The id must be an integer: {:?}", e);
+            response.set_status(StatusCode::BadRequest);
+            return;
+        },
+        Err(None) => {
+            error!("No id provided!");
+            response.set_status(StatusCode::BadRequest);
+            return;
+        }
+    };
+    match diesel::delete(region_aliases.filter(id.eq(alias_id))).execute(&conn) {
+        Ok(v) => {
+            if v != 1 {
+                error!("Region alias does not exist, nothing deleted.");
+                response.set_status(StatusCode::BadRequest);
+            }
+        },
+        Err(e) => {
+            error!("Encountered an error deleting region alias id {}: {:?}", alias_id, e);
+            response.set_status(StatusCode::InternalServerError);
+        }
+    }
+}
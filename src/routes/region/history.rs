@@ -0,0 +1,86 @@
+
+use diesel::prelude::*;
+use rustc_serialize::json;
+use rustful::{Context, Response, header, StatusCode};
+
+use ::establish_connection;
+use ::history;
+use ::models::Region;
+use ::routes::{history_window, regions_allowed, AllowedRegion};
+
+/// Player history of a region, which may be given by its id, name or an alias.
+pub fn region_history(context: Context, mut response: Response) {
+    use ::schema::regions::dsl::*;
+
+    let conn = match establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Could not establish connection to DB: {}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+
+    let (resolution, buckets) = match history_window(&context) {
+        Ok(w) => w,
+        Err(msg) => {
+            response.set_status(StatusCode::BadRequest);
+            response.send(format!("{:?}", msg));
+            return;
+        }
+    };
+    let given: String = match context.variables.get("id") {
+        Some(v) => v.into_owned(),
+        None => {
+            error!("No region id provided!");
+            response.set_status(StatusCode::BadRequest);
+            return;
+        }
+    };
+    let found = match given.parse::<i32>() {
+        Ok(region_id) => regions.filter(id.eq(region_id)).first::<Region>(&conn),
+        Err(_) => match regions_allowed(&conn, Some(given.as_str()).into_iter()) {
+            AllowedRegion::Success(canonical) => {
+                regions.filter(name.eq(&canonical[0])).first::<Region>(&conn)
+            },
+            AllowedRegion::Failure(failures) => {
+                response.set_status(StatusCode::BadRequest);
+                response.send(format!("\"Regions `{:?}` do not exist in the Database!\"", failures));
+                return;
+            },
+            AllowedRegion::Panic => {
+                error!("Failed in region_history/regions_allowed, can not complete request.");
+                response.set_status(StatusCode::InternalServerError);
+                return;
+            }
+        },
+    };
+    let region: Region = match found {
+        Ok(r) => r,
+        Err(e) => {
+            error!("Region does not exist: {:?}", e);
+            response.set_status(StatusCode::BadRequest);
+            return;
+        }
+    };
+
+    let results = match history::region_history(&conn, region.id, resolution, buckets) {
+        Ok(r) => r,
+        Err(e) => {
+            error!("Could not execute query in region_history: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    let encoded = match json::encode(&results) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Could not encode results of region_history query as json: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.send(format!("{{\"region\": {}, \"resolution\": \"{}\", \"buckets\": {}}}",
+                          json::as_json(&region.name), resolution.as_str(), encoded));
+}
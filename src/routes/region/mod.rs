@@ -1,4 +1,10 @@
 
+mod alias;
+mod history;
+
+pub use self::alias::{get_all_region_aliases, add_region_alias, delete_region_alias};
+pub use self::history::region_history;
+
 use diesel;
 use diesel::prelude::*;
 use rustful::{Context, Response, header, StatusCode};
@@ -78,8 +84,24 @@ pub fn add_region(mut context: Context, mut response: Response) {
             return;
         }
     };
+    let latitude = body.find("latitude").and_then(|v| v.as_f64());
+    let longitude = body.find("longitude").and_then(|v| v.as_f64());
+    if latitude.is_some() != longitude.is_some() {
+        error!("add_region needs both latitude and longitude, or neither.");
+        response.set_status(StatusCode::BadRequest);
+        return;
+    }
+    if latitude.map_or(false, |l| !(l >= -90.0 && l <= 90.0))
+       || longitude.map_or(false, |l| !(l >= -180.0 && l <= 180.0)) {
+        response.set_status(StatusCode::BadRequest);
+        response.send("\"latitude must be between -90 and 90, and longitude between -180 and \
+                       180!\"");
+        return;
+    }
     let new_region = NewRegion {
         name: region_name,
+        latitude: latitude,
+        longitude: longitude,
     };
     match diesel::insert(&new_region).into(regions::table).execute(&conn) {
         Ok(_) => {},
@@ -0,0 +1,184 @@
+//! Triage of abuse reports, see `reports`. Admins only.
+
+use diesel::prelude::*;
+use rustc_serialize::json;
+use rustful::{Context, Response, header, StatusCode};
+
+use ::establish_connection;
+use ::models::{GameServer, Report};
+use ::reports::{self, OPEN, DISMISSED, UPHELD};
+use ::routes::is_admin;
+
+/// Lists reports, oldest first. `?status=` picks `open` (the default), `dismissed` or
+/// `upheld` reports, and `?server_id=` only lists the reports of one server.
+pub fn get_reports(context: Context, mut response: Response) {
+    if !is_admin(&context) {
+        response.set_status(StatusCode::Forbidden);
+        return;
+    }
+    let conn = match establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Could not establish connection to DB: {}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+
+    let report_status = match context.query.get("status") {
+        Some(ref s) if [OPEN, DISMISSED, UPHELD].contains(&&s[..]) => s.to_string(),
+        Some(s) => {
+            response.set_status(StatusCode::BadRequest);
+            response.send(format!("\"Unknown status `{}`, expected `open`, `dismissed` or `upheld`.\"", s));
+            return;
+        },
+        None => OPEN.into(),
+    };
+    let server = match context.query.get("server_id").map(|s| s.parse::<i32>()) {
+        Some(Ok(s)) => Some(s),
+        Some(Err(e)) => {
+            error!("server_id must be an integer: {:?}", e);
+            response.set_status(StatusCode::BadRequest);
+            return;
+        },
+        None => None,
+    };
+
+    let found = match reports::load(&conn, &report_status, server) {
+        Ok(r) => r,
+        Err(e) => {
+            error!("Could not execute query in get_reports: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    let encoded = match json::encode(&found) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Could not encode reports as json: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.send(format!("{{\"results\": {}, \"size\": {}}}", encoded, found.len()));
+}
+
+/// Lists how many reports every reported server has, those with the most open reports first.
+pub fn get_report_counts(context: Context, mut response: Response) {
+    if !is_admin(&context) {
+        response.set_status(StatusCode::Forbidden);
+        return;
+    }
+    let conn = match establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Could not establish connection to DB: {}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+
+    let found = match reports::counts(&conn) {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Could not execute query in get_report_counts: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    let encoded = match json::encode(&found) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Could not encode report counts as json: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.send(format!("{{\"results\": {}, \"size\": {}}}", encoded, found.len()));
+}
+
+/// Resolves an open report, as `{"resolution": "dismiss"}` or `{"resolution": "uphold"}`.
+/// Upholding a report rejects the server it is about.
+pub fn resolve_report(mut context: Context, mut response: Response) {
+    use ::schema::game_servers::dsl::{game_servers, id};
+
+    if !is_admin(&context) {
+        response.set_status(StatusCode::Forbidden);
+        return;
+    }
+    let conn = match establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Could not establish connection to DB: {}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+
+    let report_id: i32 = match context.variables.parse::<_, i32>("id") {
+        Ok(v) => v,
+        Err(Some(e)) => {
+            error!("Could not parse the ID as an i32: {:?}", e);
+            response.set_status(StatusCode::BadRequest);
+            return;
+        },
+        Err(None) => {
+            error!("Another Error occured during context variable parsing.");
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    let report: Report = match reports::find(&conn, report_id) {
+        Ok(Some(ref r)) if r.status != OPEN => {
+            response.set_status(StatusCode::Conflict);
+            response.send(format!("\"Report {} was already {}!\"", report_id, r.status));
+            return;
+        },
+        Ok(Some(r)) => r,
+        Ok(None) => {
+            response.set_status(StatusCode::NotFound);
+            return;
+        },
+        Err(e) => {
+            error!("Could not load report {} in resolve_report: {:?}", report_id, e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    let server: GameServer = match game_servers.filter(id.eq(report.server_id)).first(&conn) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Could not load the server of report {}: {:?}", report_id, e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    let body = match context.body.read_json_body() {
+        Ok(b) => b,
+        Err(e) => {
+            error!("Could not read resolve_report json body {:?}", e);
+            response.set_status(StatusCode::BadRequest);
+            return;
+        }
+    };
+    let uphold = match body.find("resolution").and_then(|r| r.as_string()) {
+        Some("uphold") => true,
+        Some("dismiss") => false,
+        _ => {
+            response.set_status(StatusCode::BadRequest);
+            response.send("\"resolution must be `dismiss` or `uphold`!\"");
+            return;
+        }
+    };
+
+    if let Err(e) = reports::resolve(&conn, &report, &server, uphold) {
+        error!("Could not resolve report {}: {:?}", report_id, e);
+        response.set_status(StatusCode::InternalServerError);
+        return;
+    }
+    let new_status = if uphold { UPHELD } else { DISMISSED };
+    response.send(format!("{{\"report_id\": {}, \"status\": \"{}\"}}", report_id, new_status));
+}
@@ -1,10 +1,21 @@
 
 use diesel;
 use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use diesel::result::{Error as DieselError, OptionalExtension};
+use rustc_serialize::Decodable;
+use rustc_serialize::json;
 use rustful::{Context, Response, header, StatusCode};
 
-use ::models::NewGameServer;
-use ::routes::{regions_allowed, AllowedRegion};
+use ::blocklist;
+use ::geoip;
+use ::hostnames::resolve_endpoint;
+use ::models::{NewGameServer, Region};
+use ::moderation;
+use ::names;
+use ::routes::{regions_allowed, require_user, AllowedRegion};
+use ::server_info;
+use ::verification;
 
 pub fn add_server(mut context: Context, mut response: Response) {
     use schema::game_servers;
@@ -18,7 +29,20 @@ pub fn add_server(mut context: Context, mut response: Response) {
         }
     };
     response.headers_mut().set(header::ContentType::json());
-    let parsed_server: NewGameServer = match context.body.decode_json_body() {
+    // Every listing has an owner, who alone can update or delete it.
+    let user = match require_user(&context, &conn, &mut response) {
+        Some(u) => u,
+        None => return,
+    };
+    let body = match context.body.read_json_body() {
+        Ok(b) => b,
+        Err(e) => {
+            error!("Could not read add_server json body {:?}", e);
+            response.set_status(StatusCode::BadRequest);
+            return;
+        }
+    };
+    let mut parsed_server = match NewGameServer::decode(&mut json::Decoder::new(body.clone())) {
         Ok(s) => s,
         Err(e) => {
             error!("Could not decode request JSON into a GameServer object: {:?}", e);
@@ -26,8 +50,77 @@ pub fn add_server(mut context: Context, mut response: Response) {
             return;
         }
     };
-    match regions_allowed(&conn, Some(parsed_server.region.as_str()).into_iter()) {
-        AllowedRegion::Success => {},
+    let given_port: Option<i32> = match body.find("port") {
+        None => None,
+        Some(p) => match p.as_i64() {
+            Some(p) if p > 0 && p <= 65535 => Some(p as i32),
+            _ => {
+                response.set_status(StatusCode::BadRequest);
+                response.send("\"port must be a port number!\"");
+                return;
+            }
+        }
+    };
+    let (resolved, given_hostname) = match resolve_endpoint(&parsed_server.address, given_port) {
+        Ok(a) => a,
+        Err(e) => {
+            response.set_status(StatusCode::BadRequest);
+            response.send(format!("{{\"error\": {}}}", json::as_json(&e)));
+            return;
+        }
+    };
+    parsed_server.address = resolved.ip().to_string();
+    parsed_server.port = resolved.port() as i32;
+    parsed_server.hostname = given_hostname;
+    parsed_server.name = match names::sanitize(&parsed_server.name) {
+        Ok(n) => n,
+        Err(e) => {
+            response.set_status(StatusCode::BadRequest);
+            response.send(format!("{{\"error\": {}, \"rule\": \"{}\"}}",
+                                  json::as_json(&e.to_string()), e.rule()));
+            return;
+        }
+    };
+    let endpoint = format!("{}:{}", parsed_server.address, parsed_server.port);
+    let blocked = match parsed_server.address.parse() {
+        Ok(ip) => blocklist::blocking_listing(&conn, ip, &endpoint),
+        Err(_) => Ok(None),
+    };
+    match blocked {
+        Ok(None) => {},
+        Ok(Some(network)) => {
+            response.set_status(StatusCode::Forbidden);
+            response.send(format!("{{\"error\": \"Servers in this network can not be listed.\", \
+                                   \"network\": \"{}\"}}", network));
+            return;
+        },
+        Err(e) => {
+            error!("Could not check the blocklist in add_server: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    }
+    parsed_server.owner_id = Some(user.id);
+    let mut inferred = false;
+    if parsed_server.region.is_empty() {
+        parsed_server.region = match infer_region(&conn, &parsed_server.address) {
+            Ok(Some(r)) => r,
+            Ok(None) => {
+                response.set_status(StatusCode::BadRequest);
+                response.send(format!("\"No region given, and none could be inferred from `{}`!\"",
+                                      parsed_server.address));
+                return;
+            },
+            Err(e) => {
+                error!("Could not load regions to infer one in add_server: {:?}", e);
+                response.set_status(StatusCode::InternalServerError);
+                return;
+            }
+        };
+        inferred = true;
+    }
+    let canonical_region = match regions_allowed(&conn, Some(parsed_server.region.as_str()).into_iter()) {
+        AllowedRegion::Success(mut canonical) => canonical.remove(0),
         AllowedRegion::Failure(failures) => {
             response.set_status(StatusCode::BadRequest);
             response.send(format!("\"Regions `{:?}` do not exist in the Database!\"", failures));
@@ -39,13 +132,108 @@ pub fn add_server(mut context: Context, mut response: Response) {
             return;
         }
     };
+    parsed_server.region = canonical_region;
+
+    let listed = game_servers::table.select(game_servers::id)
+                                    .filter(game_servers::address.eq(&parsed_server.address))
+                                    .filter(game_servers::port.eq(parsed_server.port))
+                                    .first::<i32>(&conn).optional();
+    match listed {
+        Ok(None) => {},
+        Ok(Some(existing)) => {
+            response.set_status(StatusCode::Conflict);
+            response.send(format!("{{\"error\": \"This server is already listed.\", \"id\": {0}, \
+                                   \"url\": \"/server/{0}\"}}", existing));
+            return;
+        },
+        Err(e) => {
+            error!("Could not check if a server is already listed in add_server: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    }
+    parsed_server.status = match moderation::initial_status(&conn, parsed_server.owner_id) {
+        Ok(s) => s.into(),
+        Err(e) => {
+            error!("Could not check if the owner of a new server is trusted: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    parsed_server.verification_token = match verification::new_token() {
+        Ok(t) => t,
+        Err(e) => {
+            error!("Could not make a verification token in add_server: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    parsed_server.heartbeat_secret = match verification::new_heartbeat_secret() {
+        Ok(t) => t,
+        Err(e) => {
+            error!("Could not make a heartbeat secret in add_server: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
     match diesel::insert(&parsed_server).into(game_servers::table).execute(&conn) {
         Ok(_) => {},
+        // Listed by someone else since it was checked above.
+        Err(ref e) if already_listed(e) => {
+            response.set_status(StatusCode::Conflict);
+            response.send("{\"error\": \"This server is already listed.\"}");
+            return;
+        },
         Err(e) => {
             error!("Failed to insert server into game_servers: {:?}", e);
             response.set_status(StatusCode::InternalServerError);
             return;
         }
     };
-    response.send(format!("\"server `{}` added!\"", &parsed_server.name));
+    if parsed_server.version.is_some() {
+        if let Err(e) = server_info::flag_outdated(&conn, Some(&parsed_server.game_type)) {
+            error!("Unable to flag outdated servers in add_server: {:?}", e);
+        }
+    }
+    let server_id: i32 = match game_servers::table.select(game_servers::id)
+                                                  .filter(game_servers::address.eq(&parsed_server.address))
+                                                  .filter(game_servers::port.eq(parsed_server.port))
+                                                  .first(&conn) {
+        Ok(i) => i,
+        Err(e) => {
+            error!("Could not load the id of a new server: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    let message = if inferred {
+        format!("server `{}` added to inferred region `{}`!", &parsed_server.name, &parsed_server.region)
+    } else {
+        format!("server `{}` added!", &parsed_server.name)
+    };
+    // The token and secret are only handed out here and to the owner, see `verification`.
+    response.send(format!("{{\"message\": {}, \"id\": {}, \"status\": \"{}\", \
+                           \"verification_token\": \"{}\", \"heartbeat_secret\": \"{}\"}}",
+                          json::as_json(&message), server_id, parsed_server.status,
+                          parsed_server.verification_token, parsed_server.heartbeat_secret));
+}
+
+/// Whether an insert failed on the unique address and port of `game_servers`.
+fn already_listed(e: &DieselError) -> bool {
+    match *e {
+        DieselError::DatabaseError(ref msg) => msg.contains("game_servers_address_port"),
+        _ => false,
+    }
+}
+
+/// Suggests the region nearest to a server, based on the location of its address.
+fn infer_region(conn: &PgConnection, address: &str) -> Result<Option<String>, DieselError> {
+    use ::schema::regions::dsl::regions;
+
+    let location = match geoip::parse_host(address).and_then(geoip::locate) {
+        Some(l) => l,
+        None => return Ok(None),
+    };
+    let all = try!(regions.load::<Region>(conn));
+    Ok(geoip::regions_by_distance(location, all).into_iter().next().map(|(r, _)| r.name))
 }
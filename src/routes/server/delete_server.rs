@@ -3,6 +3,9 @@ use diesel::prelude::*;
 
 use rustful::{Context, Response, StatusCode};
 
+use ::routes::require_owned_server;
+
+/// Deletes a server. Owners and admins only.
 pub fn delete_server(context: Context, mut response: Response) {
     use ::schema::game_servers::dsl::*;
 
@@ -14,22 +17,11 @@ pub fn delete_server(context: Context, mut response: Response) {
             return;
         }
     };
-    let server_id: i32 = match context.variables.parse("id") {
-        Ok(i) => i,
-        Err(err_type) => match err_type {
-            Some(a) => {
-                error!("The id must be an integer: {:?}", a);
-                response.set_status(StatusCode::BadRequest);
-                return;
-            },
-            None => {
-                error!("No id provided!");
-                response.set_status(StatusCode::BadRequest);
-                return;
-            }
-        }
+    let (_, server) = match require_owned_server(&context, &conn, &mut response) {
+        Some(s) => s,
+        None => return,
     };
-    match diesel::delete(game_servers.filter(id.eq(server_id))).execute(&conn) {
+    match diesel::delete(game_servers.filter(id.eq(server.id))).execute(&conn) {
         Ok(v) => {
             if v != 1 {
                 error!("Server does not exist, nothing deleted.");
@@ -37,7 +29,7 @@ pub fn delete_server(context: Context, mut response: Response) {
             }
         },
         Err(e) => {
-            error!("Encountered an error deleting server id {}: {:?}", server_id, e);
+            error!("Encountered an error deleting server id {}: {:?}", server.id, e);
             response.set_status(StatusCode::InternalServerError);
         }
     }
@@ -1,13 +1,11 @@
 
-use diesel::prelude::*;
 use rustc_serialize::json;
 use rustful::{Context, Response, header, StatusCode};
 
 use ::models::{GameServer};
+use ::search::{self, SearchFilters};
 
 pub fn get_all_servers(_ctx: Context, mut response: Response) {
-    use ::schema::game_servers::dsl::*;
-
     let conn = match ::establish_connection() {
         Ok(c) => c,
         Err(e) => {
@@ -18,8 +16,8 @@ pub fn get_all_servers(_ctx: Context, mut response: Response) {
     };
     response.headers_mut().set(header::ContentType::json());
 
-    let all: Vec<GameServer> = match game_servers.load(&conn) {
-        Ok(servers) => servers,
+    let all: Vec<GameServer> = match search::load(&conn, &SearchFilters::default()) {
+        Ok(r) => r.servers,
         Err(e) => {
             error!("Could not execute query query in get_all_regions: {:?}", e);
             response.set_status(StatusCode::InternalServerError);
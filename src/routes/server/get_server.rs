@@ -0,0 +1,76 @@
+use std::collections::BTreeMap;
+
+use diesel::prelude::*;
+use rustc_serialize::json;
+use rustful::{Context, Response, header, StatusCode};
+
+use ::models::GameServer;
+use ::routes::can_view;
+use ::rules;
+
+/// A single server, along with its rules.
+pub fn get_server(context: Context, mut response: Response) {
+    use ::schema::game_servers::dsl::*;
+
+    let conn = match ::establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Connecting to the DB in get_server failed: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+
+    let server_id: i32 = match context.variables.parse::<_, i32>("id") {
+        Ok(v) => v,
+        Err(Some(e)) => {
+            error!("Could not parse the ID as an i32: {:?}", e);
+            response.set_status(StatusCode::BadRequest);
+            return;
+        },
+        Err(None) => {
+            error!("Another Error occured during context variable parsing.");
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    let server: GameServer = match game_servers.filter(id.eq(server_id)).first(&conn) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Server ID does not exist: {:?}", e);
+            response.set_status(StatusCode::NotFound);
+            return;
+        }
+    };
+    match can_view(&context, &conn, &server) {
+        Ok(true) => {},
+        Ok(false) => {
+            response.set_status(StatusCode::NotFound);
+            return;
+        },
+        Err(e) => {
+            error!("Could not look up the user of get_server: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    }
+    let server_rules: BTreeMap<String, String> = match rules::load(&conn, server_id) {
+        Ok(r) => r.into_iter().collect(),
+        Err(e) => {
+            error!("Could not load the rules of server {}: {:?}", server_id, e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+
+    let encoded = match (json::encode(&server), json::encode(&server_rules)) {
+        (Ok(s), Ok(r)) => (s, r),
+        (Err(e), _) | (_, Err(e)) => {
+            error!("Could not encode a server as json: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.send(format!("{{\"server\": {}, \"rules\": {}}}", encoded.0, encoded.1));
+}
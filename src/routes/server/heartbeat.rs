@@ -0,0 +1,188 @@
+
+use diesel;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use rustc_serialize::json::{self, Json};
+use rustful::{Context, Response, header, StatusCode};
+
+use ::history;
+use ::in_transaction;
+use ::models::{GameServer, Player};
+use ::players;
+use ::routes::current_user;
+use ::server_info::normalize_map;
+
+/// Reports the players currently on a server. Every heartbeat is kept in the player history.
+/// A heartbeat may also give the `map` being played, and list the players as
+/// `players: [{name, score, duration}]`, which then replaces the server's roster.
+///
+/// Heartbeats carry the server's secret in the `X-Heartbeat-Secret` header, or come from its
+/// owner, with their `X-User-Token`.
+pub fn heartbeat(mut context: Context, mut response: Response) {
+    use ::schema::game_servers::dsl::*;
+
+    let conn = match ::establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Connecting to the DB in heartbeat failed: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+
+    let server_id: i32 = match context.variables.parse::<_, i32>("id") {
+        Ok(v) => v,
+        Err(Some(e)) => {
+            error!("Could not parse the ID as an i32: {:?}", e);
+            response.set_status(StatusCode::BadRequest);
+            return;
+        },
+        Err(None) => {
+            error!("Another Error occured during context variable parsing.");
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    let server: GameServer = match game_servers.filter(id.eq(server_id)).first(&conn) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Server ID does not exist: {:?}", e);
+            response.set_status(StatusCode::BadRequest);
+            return;
+        }
+    };
+    match authorized(&context, &conn, &server) {
+        Ok(true) => {},
+        Ok(false) => {
+            response.set_status(StatusCode::Unauthorized);
+            response.send("\"Heartbeats need the server's X-Heartbeat-Secret, or its owner's \
+                           X-User-Token!\"");
+            return;
+        },
+        Err(e) => {
+            error!("Could not look up the user sending a heartbeat: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    }
+    let body = match context.body.read_json_body() {
+        Ok(b) => b,
+        Err(e) => {
+            error!("Could not read heartbeat json body {:?}", e);
+            response.set_status(StatusCode::BadRequest);
+            return;
+        }
+    };
+
+    let users: i32 = match body.find("current_users").and_then(|v| v.as_i64()) {
+        Some(v) if v >= 0 && v <= i32::max_value() as i64 => v as i32,
+        _ => {
+            response.set_status(StatusCode::BadRequest);
+            response.send("\"current_users must be a non-negative integer!\"");
+            return;
+        }
+    };
+    let premium_users: Option<i32> = match body.find("current_premium_users") {
+        None => server.current_premium_users,
+        Some(v) => match v.as_i64() {
+            Some(v) if v >= 0 && v <= i32::max_value() as i64 => Some(v as i32),
+            _ => {
+                response.set_status(StatusCode::BadRequest);
+                response.send("\"current_premium_users must be a non-negative integer!\"");
+                return;
+            }
+        }
+    };
+    let map: Option<Option<String>> = match body.find("map") {
+        None => None,
+        Some(v) => match v.as_string() {
+            Some(m) => Some(normalize_map(m)),
+            None => {
+                response.set_status(StatusCode::BadRequest);
+                response.send("\"map must be a string!\"");
+                return;
+            }
+        }
+    };
+    let roster: Option<Vec<Player>> = match body.find("players") {
+        None => None,
+        Some(v) => match parse_players(v) {
+            Ok(p) => Some(p),
+            Err(msg) => {
+                response.set_status(StatusCode::BadRequest);
+                response.send(format!("{}", json::as_json(&msg)));
+                return;
+            }
+        }
+    };
+
+    // The history has to agree with the count the server is listed with.
+    let result = in_transaction(&conn, || {
+        try!(diesel::update(game_servers.filter(id.eq(server_id)))
+                    .set((current_users.eq(users), current_premium_users.eq(premium_users)))
+                    .execute(&conn));
+        if let Some(map) = map {
+            try!(diesel::update(game_servers.filter(id.eq(server_id)))
+                        .set(current_map.eq(map)).execute(&conn));
+        }
+        history::record(&conn, server_id, &server.region, users)
+    });
+    if let Err(e) = result {
+        error!("Unable to record the player count of a server in heartbeat: {:?}", e);
+        response.set_status(StatusCode::InternalServerError);
+        return;
+    }
+    if let Some(roster) = roster {
+        if let Err(e) = players::store(&conn, server_id, &roster) {
+            error!("Unable to store the players of a server in heartbeat: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    }
+
+    response.send("\"Heartbeat recorded\"");
+}
+
+/// Whether a heartbeat comes from the server, or from its owner.
+fn authorized(context: &Context, conn: &PgConnection, server: &GameServer) -> QueryResult<bool> {
+    if let Some(values) = context.headers.get_raw("X-Heartbeat-Secret") {
+        if values.len() == 1 && values[0] == server.heartbeat_secret.as_bytes() {
+            return Ok(true);
+        }
+    }
+    match (try!(current_user(context, conn)), server.owner_id) {
+        (Some(user), Some(owner)) => Ok(user.id == owner),
+        _ => Ok(false),
+    }
+}
+
+fn parse_players(value: &Json) -> Result<Vec<Player>, String> {
+    let list = match value.as_array() {
+        Some(l) => l,
+        None => return Err("players must be an array!".into()),
+    };
+    let mut roster = Vec::with_capacity(list.len());
+    for player in list {
+        let name = match player.find("name").and_then(|v| v.as_string()) {
+            Some(n) => n,
+            None => return Err("every player must have a string `name`!".into()),
+        };
+        let score = match player.find("score") {
+            None => 0,
+            Some(v) => match v.as_i64() {
+                Some(v) if v >= i32::min_value() as i64 && v <= i32::max_value() as i64 => v as i32,
+                _ => return Err("a player's score must be an integer!".into()),
+            }
+        };
+        let duration = match player.find("duration") {
+            None => 0.0,
+            Some(v) => match v.as_f64() {
+                Some(v) if v >= 0.0 => v as f32,
+                _ => return Err("a player's duration must be a non-negative number of seconds!".into()),
+            }
+        };
+        roster.push(Player { name: name.into(), score: score, duration: duration });
+    }
+    Ok(roster)
+}
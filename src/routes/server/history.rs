@@ -0,0 +1,83 @@
+
+use diesel::prelude::*;
+use rustc_serialize::json;
+use rustful::{Context, Response, header, StatusCode};
+
+use ::history;
+use ::models::GameServer;
+use ::routes::{can_view, history_window};
+
+pub fn server_history(context: Context, mut response: Response) {
+    use ::schema::game_servers::dsl::*;
+
+    let conn = match ::establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Connecting to the DB in server_history failed: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+
+    let server_id: i32 = match context.variables.parse::<_, i32>("id") {
+        Ok(v) => v,
+        Err(Some(e)) => {
+            error!("Could not parse the ID as an i32: {:?}", e);
+            response.set_status(StatusCode::BadRequest);
+            return;
+        },
+        Err(None) => {
+            error!("Another Error occured during context variable parsing.");
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    let (resolution, buckets) = match history_window(&context) {
+        Ok(w) => w,
+        Err(msg) => {
+            response.set_status(StatusCode::BadRequest);
+            response.send(format!("{:?}", msg));
+            return;
+        }
+    };
+    let server: GameServer = match game_servers.filter(id.eq(server_id)).first(&conn) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Server ID does not exist: {:?}", e);
+            response.set_status(StatusCode::NotFound);
+            return;
+        }
+    };
+    match can_view(&context, &conn, &server) {
+        Ok(true) => {},
+        Ok(false) => {
+            response.set_status(StatusCode::NotFound);
+            return;
+        },
+        Err(e) => {
+            error!("Could not look up the user of server_history: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    }
+
+    let results = match history::server_history(&conn, server_id, resolution, buckets) {
+        Ok(r) => r,
+        Err(e) => {
+            error!("Could not execute query in server_history: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    let encoded = match json::encode(&results) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Could not encode results of server_history query as json: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.send(format!("{{\"server_id\": {}, \"resolution\": \"{}\", \"buckets\": {}}}",
+                          server_id, resolution.as_str(), encoded));
+}
@@ -0,0 +1,63 @@
+use diesel;
+use diesel::prelude::*;
+use rustful::{Context, Response, header, StatusCode};
+
+use ::routes::require_owned_server;
+use ::server_info::normalize_map;
+
+/// Sets the map rotation of a server, as `{"rotation": ["de_dust2", "de_inferno"]}`.
+/// Owners and admins only.
+pub fn set_map_rotation(mut context: Context, mut response: Response) {
+    use ::schema::game_servers::dsl::*;
+
+    let conn = match ::establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Connecting to the DB in set_map_rotation failed: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+    let (_, server) = match require_owned_server(&context, &conn, &mut response) {
+        Some(s) => s,
+        None => return,
+    };
+    let body = match context.body.read_json_body() {
+        Ok(b) => b,
+        Err(e) => {
+            error!("Could not read set_map_rotation json body {:?}", e);
+            response.set_status(StatusCode::BadRequest);
+            return;
+        }
+    };
+    let given = match body.find("rotation").and_then(|r| r.as_array()) {
+        Some(r) => r,
+        None => {
+            response.set_status(StatusCode::BadRequest);
+            response.send("\"rotation must be an array of map names!\"");
+            return;
+        }
+    };
+    let mut rotation: Vec<String> = vec![];
+    for map in given {
+        match map.as_string().and_then(normalize_map) {
+            Some(m) => if !rotation.contains(&m) {
+                rotation.push(m);
+            },
+            None => {
+                response.set_status(StatusCode::BadRequest);
+                response.send("\"rotation must be an array of map names!\"");
+                return;
+            }
+        }
+    }
+
+    let target = game_servers.filter(id.eq(server.id));
+    if let Err(e) = diesel::update(target).set(map_rotation.eq(rotation.clone())).execute(&conn) {
+        error!("Unable to set the map rotation of server {}: {:?}", server.id, e);
+        response.set_status(StatusCode::InternalServerError);
+        return;
+    }
+    response.send(format!("\"Set a rotation of {} maps\"", rotation.len()));
+}
@@ -1,12 +1,32 @@
 
 mod update_server;
 mod get_all_servers;
+mod get_server;
 mod add_server;
 mod search_servers;
 mod delete_server;
+mod heartbeat;
+mod history;
+mod map_rotation;
+mod players;
+mod rcon;
+mod report;
+mod reviews;
+mod rules;
+mod verification;
 
 pub use self::update_server::update_server;
 pub use self::get_all_servers::get_all_servers;
+pub use self::get_server::get_server;
 pub use self::add_server::add_server;
-pub use self::search_servers::search_servers;
+pub use self::search_servers::{search_servers, search_servers_query};
 pub use self::delete_server::delete_server;
+pub use self::heartbeat::heartbeat;
+pub use self::history::server_history;
+pub use self::map_rotation::set_map_rotation;
+pub use self::players::server_players;
+pub use self::rcon::{set_rcon_password, rcon_command};
+pub use self::report::report_server;
+pub use self::reviews::{get_reviews, review_server, delete_review, reply_to_review};
+pub use self::rules::set_server_rules;
+pub use self::verification::{get_verification, check_verification};
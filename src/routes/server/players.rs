@@ -0,0 +1,100 @@
+use diesel::prelude::*;
+use rustc_serialize::json;
+use rustful::{Context, Response, header, StatusCode};
+
+use ::models::GameServer;
+use ::players::{self, Roster};
+use ::routes::can_view;
+
+/// Lists the players on a server. The roster is fetched from the game server when the last
+/// one is too old, and the last one is served, marked `stale`, when that fails.
+pub fn server_players(context: Context, mut response: Response) {
+    use ::schema::game_servers::dsl::*;
+
+    let conn = match ::establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Connecting to the DB in server_players failed: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+
+    let server_id: i32 = match context.variables.parse::<_, i32>("id") {
+        Ok(v) => v,
+        Err(Some(e)) => {
+            error!("Could not parse the ID as an i32: {:?}", e);
+            response.set_status(StatusCode::BadRequest);
+            return;
+        },
+        Err(None) => {
+            error!("Another Error occured during context variable parsing.");
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    let server: GameServer = match game_servers.filter(id.eq(server_id)).first(&conn) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Server ID does not exist: {:?}", e);
+            response.set_status(StatusCode::NotFound);
+            return;
+        }
+    };
+    match can_view(&context, &conn, &server) {
+        Ok(true) => {},
+        Ok(false) => {
+            response.set_status(StatusCode::NotFound);
+            return;
+        },
+        Err(e) => {
+            error!("Could not look up the user of server_players: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    }
+
+    let mut roster = match players::load(&conn, server_id) {
+        Ok(r) => r,
+        Err(e) => {
+            error!("Could not load the roster of server {}: {:?}", server_id, e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    if !roster.as_ref().map_or(false, Roster::is_fresh) {
+        match players::refresh(&conn, &server) {
+            Ok(()) => {
+                roster = match players::load(&conn, server_id) {
+                    Ok(r) => r,
+                    Err(e) => {
+                        error!("Could not load the roster of server {}: {:?}", server_id, e);
+                        response.set_status(StatusCode::InternalServerError);
+                        return;
+                    }
+                };
+            },
+            Err(e) => warn!("Could not fetch the players of server {}: {:?}", server_id, e),
+        }
+    }
+    let roster = match roster {
+        Some(r) => r,
+        None => {
+            response.set_status(StatusCode::BadGateway);
+            response.send("\"The server did not list its players, try again later.\"");
+            return;
+        }
+    };
+
+    let encoded = match json::encode(&roster.players) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Could not encode the players of a server as json: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.send(format!("{{\"server_id\": {}, \"fetched_at\": {}, \"stale\": {}, \"results\": {}, \"size\": {}}}",
+                          server_id, roster.fetched_at, !roster.is_fresh(), encoded, roster.players.len()));
+}
@@ -0,0 +1,263 @@
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use diesel;
+use diesel::prelude::*;
+use diesel::result::OptionalExtension;
+use rustc_serialize::json;
+use rustful::{Context, Response, header, StatusCode};
+
+use ::audit;
+use ::in_transaction;
+use ::models::{GameServer, NewRconCredentials, RconCredentials};
+use ::rcon::{RconClient, RconError};
+use ::routes::require_owned_server;
+use ::secrets;
+
+/// How long to wait on the game server for every read and write.
+const RCON_TIMEOUT_SECS: u64 = 5;
+/// How many refused passwords in a row lock RCON of a server, and for how long after the
+/// last one.
+const MAX_AUTH_FAILURES: u32 = 5;
+const AUTH_LOCKOUT_SECS: u64 = 15 * 60;
+
+lazy_static! {
+    /// When the RCON password of each server was last refused, and how many times in a row.
+    static ref AUTH_FAILURES: Mutex<HashMap<i32, (Instant, u32)>> = Mutex::new(HashMap::new());
+}
+
+/// Associated data of a sealed RCON password, so it only opens for the server it was set for.
+fn password_context(server: &GameServer) -> Vec<u8> {
+    format!("rcon_password:{}", server.id).into_bytes()
+}
+
+/// Sets the RCON password, and optionally port, of a verified server. Owners and admins only.
+pub fn set_rcon_password(mut context: Context, mut response: Response) {
+    use ::schema::rcon_credentials::dsl::*;
+
+    let conn = match ::establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Connecting to the DB in set_rcon_password failed: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+    let (user, server) = match require_owned_server(&context, &conn, &mut response) {
+        Some(s) => s,
+        None => return,
+    };
+    // Until the server shows its token, whoever listed it may not be the one running it.
+    if !server.verified {
+        response.set_status(StatusCode::Forbidden);
+        response.send("\"Verify the server before using RCON!\"");
+        return;
+    }
+    let body = match context.body.read_json_body() {
+        Ok(b) => b,
+        Err(e) => {
+            error!("Could not read set_rcon_password json body {:?}", e);
+            response.set_status(StatusCode::BadRequest);
+            return;
+        }
+    };
+    let password: &str = match body.find("password").and_then(|p| p.as_string()) {
+        Some(p) if !p.is_empty() => p,
+        _ => {
+            response.set_status(StatusCode::BadRequest);
+            response.send("\"password must be a non-empty string!\"");
+            return;
+        }
+    };
+    let rcon_port: Option<i32> = match body.find("port") {
+        None => None,
+        Some(p) => match p.as_i64() {
+            Some(p) if p > 0 && p <= 65535 => Some(p as i32),
+            _ => {
+                response.set_status(StatusCode::BadRequest);
+                response.send("\"port must be a port number!\"");
+                return;
+            }
+        }
+    };
+    let sealed = match secrets::seal(password.as_bytes(), &password_context(&server)) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Could not encrypt an RCON password: {}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+
+    let result = in_transaction(&conn, || {
+        try!(audit::record(&conn, user.as_ref().map(|u| u.id), Some(server.id), "rcon_password",
+                           "RCON password set"));
+        let target = rcon_credentials.filter(server_id.eq(server.id));
+        let updated = try!(diesel::update(target).set((encrypted_password.eq(&sealed),
+                                                       port.eq(rcon_port),
+                                                       address.eq(&server.address))).execute(&conn));
+        if updated == 0 {
+            let credentials = NewRconCredentials {
+                server_id: server.id,
+                encrypted_password: sealed.clone(),
+                port: rcon_port,
+                address: server.address.clone(),
+            };
+            try!(diesel::insert(&credentials).into(rcon_credentials).execute(&conn));
+        }
+        Ok(())
+    });
+    if let Err(e) = result {
+        error!("Unable to store RCON credentials: {:?}", e);
+        response.set_status(StatusCode::InternalServerError);
+        return;
+    }
+    response.send("\"RCON password set\"");
+}
+
+/// Runs an RCON command on a verified server and returns its output. Owners and admins only,
+/// and every command is written to the audit trail before it is sent.
+pub fn rcon_command(mut context: Context, mut response: Response) {
+    use ::schema::rcon_credentials::dsl::*;
+
+    let conn = match ::establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Connecting to the DB in rcon_command failed: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+    let (user, server) = match require_owned_server(&context, &conn, &mut response) {
+        Some(s) => s,
+        None => return,
+    };
+    // Until the server shows its token, whoever listed it may not be the one running it.
+    if !server.verified {
+        response.set_status(StatusCode::Forbidden);
+        response.send("\"Verify the server before using RCON!\"");
+        return;
+    }
+    let body = match context.body.read_json_body() {
+        Ok(b) => b,
+        Err(e) => {
+            error!("Could not read rcon_command json body {:?}", e);
+            response.set_status(StatusCode::BadRequest);
+            return;
+        }
+    };
+    let command: String = match body.find("command").and_then(|c| c.as_string()) {
+        Some(c) if !c.trim().is_empty() => c.trim().into(),
+        _ => {
+            response.set_status(StatusCode::BadRequest);
+            response.send("\"command must be a non-empty string!\"");
+            return;
+        }
+    };
+
+    let credentials: RconCredentials = match rcon_credentials.filter(server_id.eq(server.id))
+                                                             .first(&conn).optional() {
+        Ok(Some(c)) => c,
+        Ok(None) => {
+            response.set_status(StatusCode::BadRequest);
+            response.send("\"No RCON password is set for this server!\"");
+            return;
+        },
+        Err(e) => {
+            error!("Could not load RCON credentials: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    // The password was given to the server at its old address. Whoever runs the new one
+    // must not receive it.
+    if credentials.address != server.address {
+        response.set_status(StatusCode::Conflict);
+        response.send("\"The server moved since its RCON password was set, set it again!\"");
+        return;
+    }
+    let password = match secrets::open(&credentials.encrypted_password, &password_context(&server)) {
+        Ok(p) => String::from_utf8_lossy(&p).into_owned(),
+        Err(e) => {
+            error!("Could not decrypt the RCON password of server {}: {}", server.id, e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    let rcon_target = match rcon_address(&server, credentials.port) {
+        Some(a) => a,
+        None => {
+            response.set_status(StatusCode::BadRequest);
+            response.send(format!("\"Can not connect to RCON at `{}`!\"", server.endpoint()));
+            return;
+        }
+    };
+
+    let user_id = user.as_ref().map(|u| u.id);
+    if locked_out(server.id) {
+        response.set_status(StatusCode::TooManyRequests);
+        response.send("\"The RCON password was refused too often, try again later!\"");
+        return;
+    }
+    if let Err(e) = audit::record(&conn, user_id, Some(server.id), "rcon", &command) {
+        error!("Could not write an RCON command to the audit trail, not running it: {:?}", e);
+        response.set_status(StatusCode::InternalServerError);
+        return;
+    }
+    let timeout = Duration::from_secs(RCON_TIMEOUT_SECS);
+    let output = RconClient::connect(rcon_target, &password, timeout).and_then(|mut c| c.exec(&command));
+    record_auth(server.id, &output);
+    match output {
+        Ok(out) => {
+            response.send(format!("{{\"output\": {}}}", json::as_json(&out)));
+        },
+        Err(e) => {
+            error!("RCON command on server {} failed: {}", server.id, e);
+            response.set_status(StatusCode::BadGateway);
+            response.send(format!("{}", json::as_json(&e.to_string())));
+        }
+    }
+}
+
+fn rcon_address(server: &GameServer, rcon_port: Option<i32>) -> Option<SocketAddr> {
+    server.socket_address().map(|game_address| match rcon_port {
+        Some(p) => SocketAddr::new(game_address.ip(), p as u16),
+        None => game_address,
+    })
+}
+
+/// Whether the RCON password of a server was refused too often lately to try again.
+fn locked_out(server: i32) -> bool {
+    let failures = AUTH_FAILURES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    match failures.get(&server) {
+        Some(&(failed_at, count)) => {
+            count >= MAX_AUTH_FAILURES &&
+                failed_at.elapsed() < Duration::from_secs(AUTH_LOCKOUT_SECS)
+        },
+        None => false,
+    }
+}
+
+/// Counts a refused password against a server, or forgets earlier ones once it is accepted.
+fn record_auth<T>(server: i32, output: &Result<T, RconError>) {
+    let mut failures = AUTH_FAILURES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    match *output {
+        Err(RconError::AuthFailed) => {
+            let lockout = Duration::from_secs(AUTH_LOCKOUT_SECS);
+            let count = match failures.get(&server) {
+                // A lockout that ran out gives a fresh set of tries.
+                Some(&(failed_at, c)) if failed_at.elapsed() < lockout => c,
+                _ => 0,
+            };
+            failures.insert(server, (Instant::now(), count + 1));
+        },
+        // Other errors say nothing about the password.
+        Err(_) => {},
+        Ok(_) => { failures.remove(&server); },
+    }
+}
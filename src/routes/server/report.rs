@@ -0,0 +1,107 @@
+use diesel::prelude::*;
+use rustful::{Context, Response, header, StatusCode};
+
+use ::models::{GameServer, NewReport};
+use ::reports::{self, CATEGORIES, MAX_DETAIL_LENGTH, REPORTS_PER_HOUR};
+use ::routes::require_user;
+
+/// Reports a server, as `{"category": "fake_players", "detail": "Shows 64 players, has 2."}`.
+/// Signed in users only.
+pub fn report_server(mut context: Context, mut response: Response) {
+    use ::schema::game_servers::dsl::*;
+
+    let conn = match ::establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Connecting to the DB in report_server failed: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+    let user = match require_user(&context, &conn, &mut response) {
+        Some(u) => u,
+        None => return,
+    };
+
+    let server_id: i32 = match context.variables.parse::<_, i32>("id") {
+        Ok(v) => v,
+        Err(Some(e)) => {
+            error!("Could not parse the ID as an i32: {:?}", e);
+            response.set_status(StatusCode::BadRequest);
+            return;
+        },
+        Err(None) => {
+            error!("Another Error occured during context variable parsing.");
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    let server: GameServer = match game_servers.filter(id.eq(server_id)).first(&conn) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Server ID does not exist: {:?}", e);
+            response.set_status(StatusCode::NotFound);
+            return;
+        }
+    };
+    let body = match context.body.read_json_body() {
+        Ok(b) => b,
+        Err(e) => {
+            error!("Could not read report_server json body {:?}", e);
+            response.set_status(StatusCode::BadRequest);
+            return;
+        }
+    };
+    let category = match body.find("category").and_then(|c| c.as_string()) {
+        Some(c) if CATEGORIES.contains(&c) => c.to_string(),
+        _ => {
+            response.set_status(StatusCode::BadRequest);
+            response.send(format!("\"category must be one of {:?}!\"", CATEGORIES));
+            return;
+        }
+    };
+    let detail: String = body.find("detail").and_then(|d| d.as_string()).unwrap_or("")
+                             .trim().chars().take(MAX_DETAIL_LENGTH).collect();
+
+    match reports::has_open_report(&conn, server_id, user.id) {
+        Ok(false) => {},
+        Ok(true) => {
+            response.set_status(StatusCode::Conflict);
+            response.send("\"You already reported this server, it will be reviewed soon.\"");
+            return;
+        },
+        Err(e) => {
+            error!("Could not check for earlier reports in report_server: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    }
+    match reports::recent_reports(&conn, user.id) {
+        Ok(n) if n < REPORTS_PER_HOUR => {},
+        Ok(_) => {
+            response.set_status(StatusCode::TooManyRequests);
+            response.send(format!("\"You can only make {} reports an hour!\"", REPORTS_PER_HOUR));
+            return;
+        },
+        Err(e) => {
+            error!("Could not count recent reports in report_server: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    }
+
+    let report = NewReport {
+        server_id: server_id,
+        reporter_id: Some(user.id),
+        category: category,
+        detail: detail,
+    };
+    match reports::submit(&conn, &server, &report) {
+        Ok(_) => response.send("\"Thank you, the server will be reviewed.\""),
+        Err(e) => {
+            error!("Could not file a report on server {}: {:?}", server_id, e);
+            response.set_status(StatusCode::InternalServerError);
+        }
+    }
+}
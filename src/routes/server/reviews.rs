@@ -0,0 +1,299 @@
+use diesel::prelude::*;
+use rustc_serialize::json::{self, Json};
+use rustful::{Context, Response, header, StatusCode};
+
+use ::models::{GameServer, Review};
+use ::moderation;
+use ::names;
+use ::notifications;
+use ::reviews::{self, MIN_RATING, MAX_RATING, MAX_REVIEW_LENGTH};
+use ::routes::{is_admin, require_owned_server, require_user};
+
+/// Lists the reviews of a server, newest first, with its average rating. Admins also see
+/// hidden reviews.
+pub fn get_reviews(context: Context, mut response: Response) {
+    use ::schema::game_servers::dsl::*;
+
+    let conn = match ::establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Connecting to the DB in get_reviews failed: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+
+    let server_id: i32 = match context.variables.parse::<_, i32>("id") {
+        Ok(v) => v,
+        Err(Some(e)) => {
+            error!("Could not parse the ID as an i32: {:?}", e);
+            response.set_status(StatusCode::BadRequest);
+            return;
+        },
+        Err(None) => {
+            error!("Another Error occured during context variable parsing.");
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    let admin = is_admin(&context);
+    let server: GameServer = match game_servers.filter(id.eq(server_id)).first(&conn) {
+        Ok(ref s) if s.status != moderation::APPROVED && !admin => {
+            response.set_status(StatusCode::NotFound);
+            return;
+        },
+        Ok(s) => s,
+        Err(e) => {
+            error!("Server ID does not exist: {:?}", e);
+            response.set_status(StatusCode::NotFound);
+            return;
+        }
+    };
+    let found = match reviews::load(&conn, server_id, admin) {
+        Ok(r) => r,
+        Err(e) => {
+            error!("Could not load the reviews of server {}: {:?}", server_id, e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    let encoded = match json::encode(&found) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Could not encode reviews as json: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.send(format!("{{\"results\": {}, \"size\": {}, \"rating_average\": {}, \
+                           \"rating_count\": {}}}",
+                          encoded, found.len(), json::as_json(&server.rating_average),
+                          server.rating_count));
+}
+
+/// Rates a server, as `{"rating": 4, "review": "Friendly admins."}`, or edits the rating
+/// the user gave it before. Signed in users only, and not on their own servers.
+pub fn review_server(mut context: Context, mut response: Response) {
+    use ::schema::game_servers::dsl::*;
+
+    let conn = match ::establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Connecting to the DB in review_server failed: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+    let user = match require_user(&context, &conn, &mut response) {
+        Some(u) => u,
+        None => return,
+    };
+
+    let server_id: i32 = match context.variables.parse::<_, i32>("id") {
+        Ok(v) => v,
+        Err(Some(e)) => {
+            error!("Could not parse the ID as an i32: {:?}", e);
+            response.set_status(StatusCode::BadRequest);
+            return;
+        },
+        Err(None) => {
+            error!("Another Error occured during context variable parsing.");
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    let server: GameServer = match game_servers.filter(id.eq(server_id))
+                                               .filter(status.eq(moderation::APPROVED))
+                                               .first(&conn) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Server ID does not exist: {:?}", e);
+            response.set_status(StatusCode::NotFound);
+            return;
+        }
+    };
+    if server.owner_id == Some(user.id) {
+        response.set_status(StatusCode::Forbidden);
+        response.send("\"You can not review your own server!\"");
+        return;
+    }
+    let body = match context.body.read_json_body() {
+        Ok(b) => b,
+        Err(e) => {
+            error!("Could not read review_server json body {:?}", e);
+            response.set_status(StatusCode::BadRequest);
+            return;
+        }
+    };
+    let rating = match body.find("rating").and_then(|r| r.as_i64()) {
+        Some(r) if r >= MIN_RATING as i64 && r <= MAX_RATING as i64 => r as i32,
+        _ => {
+            response.set_status(StatusCode::BadRequest);
+            response.send(format!("\"rating must be a whole number from {} to {}!\"", MIN_RATING, MAX_RATING));
+            return;
+        }
+    };
+    let text = match read_text(&body, "review") {
+        Ok(t) => t.unwrap_or(String::new()),
+        Err(msg) => {
+            response.set_status(StatusCode::BadRequest);
+            response.send(format!("{:?}", msg));
+            return;
+        }
+    };
+
+    match reviews::save(&conn, server_id, user.id, rating, &text) {
+        Ok(true) => response.send("\"Review updated!\""),
+        Ok(false) => {
+            if let Some(owner) = server.owner_id {
+                let message = format!("{} rated your server `{}` {} out of {}.",
+                                      user.name, server.name, rating, MAX_RATING);
+                if let Err(e) = notifications::notify(&conn, owner, Some(server_id), &message) {
+                    error!("Could not notify the owner of server {} of a review: {:?}", server_id, e);
+                }
+            }
+            response.send("\"Review added!\"");
+        },
+        Err(e) => {
+            error!("Could not save a review of server {}: {:?}", server_id, e);
+            response.set_status(StatusCode::InternalServerError);
+        }
+    }
+}
+
+/// Deletes the signed in user's review of a server.
+pub fn delete_review(context: Context, mut response: Response) {
+    let conn = match ::establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Connecting to the DB in delete_review failed: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+    let user = match require_user(&context, &conn, &mut response) {
+        Some(u) => u,
+        None => return,
+    };
+
+    let server_id: i32 = match context.variables.parse::<_, i32>("id") {
+        Ok(v) => v,
+        Err(Some(e)) => {
+            error!("Could not parse the ID as an i32: {:?}", e);
+            response.set_status(StatusCode::BadRequest);
+            return;
+        },
+        Err(None) => {
+            error!("Another Error occured during context variable parsing.");
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    match reviews::delete(&conn, server_id, user.id) {
+        Ok(true) => response.send("\"Review deleted!\""),
+        Ok(false) => response.set_status(StatusCode::NotFound),
+        Err(e) => {
+            error!("Could not delete a review of server {}: {:?}", server_id, e);
+            response.set_status(StatusCode::InternalServerError);
+        }
+    }
+}
+
+/// Answers a review of a server, as `{"reply": "Thanks, see you on the server!"}`. An empty
+/// reply takes the answer back. Owners and admins only.
+pub fn reply_to_review(mut context: Context, mut response: Response) {
+    let conn = match ::establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Connecting to the DB in reply_to_review failed: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+    let (_, server) = match require_owned_server(&context, &conn, &mut response) {
+        Some(s) => s,
+        None => return,
+    };
+
+    let review_id: i32 = match context.variables.parse::<_, i32>("review_id") {
+        Ok(v) => v,
+        Err(Some(e)) => {
+            error!("Could not parse the review ID as an i32: {:?}", e);
+            response.set_status(StatusCode::BadRequest);
+            return;
+        },
+        Err(None) => {
+            error!("Another Error occured during context variable parsing.");
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    let review: Review = match reviews::find(&conn, review_id) {
+        Ok(Some(r)) if r.server_id == server.id => r,
+        Ok(_) => {
+            response.set_status(StatusCode::NotFound);
+            return;
+        },
+        Err(e) => {
+            error!("Could not load review {} in reply_to_review: {:?}", review_id, e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    let body = match context.body.read_json_body() {
+        Ok(b) => b,
+        Err(e) => {
+            error!("Could not read reply_to_review json body {:?}", e);
+            response.set_status(StatusCode::BadRequest);
+            return;
+        }
+    };
+    let text = match read_text(&body, "reply") {
+        Ok(t) => t,
+        Err(msg) => {
+            response.set_status(StatusCode::BadRequest);
+            response.send(format!("{:?}", msg));
+            return;
+        }
+    };
+
+    if let Err(e) = reviews::reply(&conn, &review, text.as_ref().map(|t| t.as_str())) {
+        error!("Could not reply to review {}: {:?}", review_id, e);
+        response.set_status(StatusCode::InternalServerError);
+        return;
+    }
+    if text.is_some() {
+        let message = format!("The owner of `{}` answered your review.", server.name);
+        if let Err(e) = notifications::notify(&conn, review.user_id, Some(server.id), &message) {
+            error!("Could not notify the author of review {} of a reply: {:?}", review_id, e);
+        }
+        response.send("\"Reply saved!\"");
+    } else {
+        response.send("\"Reply removed!\"");
+    }
+}
+
+/// Reads an optional piece of text written by a user. Empty text is `None`.
+fn read_text(body: &Json, key: &str) -> Result<Option<String>, String> {
+    let text = match body.find(key) {
+        None => return Ok(None),
+        Some(t) => match t.as_string() {
+            Some(s) => s.trim(),
+            None => return Err(format!("{} must be a string!", key)),
+        },
+    };
+    if text.is_empty() {
+        return Ok(None);
+    }
+    if text.chars().count() > MAX_REVIEW_LENGTH {
+        return Err(format!("{} can be at most {} characters long!", key, MAX_REVIEW_LENGTH));
+    }
+    if names::has_blocked_word(text) {
+        return Err(format!("{} contains a word that is not allowed!", key));
+    }
+    Ok(Some(text.to_string()))
+}
@@ -0,0 +1,72 @@
+use rustful::{Context, Response, header, StatusCode};
+
+use ::routes::require_owned_server;
+use ::rules;
+
+/// Sets rules of a server, as `{"rules": {"tickrate": "128", "sv_pure": null}}`. A null
+/// value removes a rule. Rules set here are kept over what the server itself reports.
+/// Owners and admins only.
+pub fn set_server_rules(mut context: Context, mut response: Response) {
+    let conn = match ::establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Connecting to the DB in set_server_rules failed: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+    let (_, server) = match require_owned_server(&context, &conn, &mut response) {
+        Some(s) => s,
+        None => return,
+    };
+    let body = match context.body.read_json_body() {
+        Ok(b) => b,
+        Err(e) => {
+            error!("Could not read set_server_rules json body {:?}", e);
+            response.set_status(StatusCode::BadRequest);
+            return;
+        }
+    };
+    let given = match body.find("rules").and_then(|r| r.as_object()) {
+        Some(r) => r,
+        None => {
+            response.set_status(StatusCode::BadRequest);
+            response.send("\"rules must be an object of rule names to values!\"");
+            return;
+        }
+    };
+
+    let mut changes: Vec<(String, Option<String>)> = vec![];
+    for (name, value) in given {
+        if name.trim().is_empty() {
+            response.set_status(StatusCode::BadRequest);
+            response.send("\"Rule names can not be empty!\"");
+            return;
+        }
+        let value = if value.is_null() {
+            None
+        } else {
+            match value.as_string() {
+                Some(v) => Some(v.to_string()),
+                None => match value.as_i64().map(|n| n.to_string())
+                                   .or(value.as_f64().map(|n| n.to_string())) {
+                    Some(v) => Some(v),
+                    None => {
+                        response.set_status(StatusCode::BadRequest);
+                        response.send(format!("\"The value of rule `{}` must be a string, number or null!\"", name));
+                        return;
+                    }
+                },
+            }
+        };
+        changes.push((name.trim().to_string(), value));
+    }
+
+    if let Err(e) = rules::set_by_owner(&conn, server.id, &changes) {
+        error!("Unable to set the rules of server {}: {:?}", server.id, e);
+        response.set_status(StatusCode::InternalServerError);
+        return;
+    }
+    response.send(format!("\"Set {} rules\"", changes.len()));
+}
@@ -1,13 +1,66 @@
 
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+
 use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use diesel::result::Error as DieselError;
 use rustc_serialize::json;
 use rustful::{Context, Response, header, StatusCode};
 
+use ::geoip;
 use ::routes::{AllowedRegion, regions_allowed};
-use ::models::{GameServer};
+use ::search::{self, dsl, SearchFilters, SearchResults};
+use ::trust;
+use ::models::{GameServer, Region};
+
+/// How many of the regions nearest to the client are reported as inferred.
+const INFERRED_REGION_COUNT: usize = 3;
+
+/// How many servers in the search results have a certain value.
+#[derive(Debug, Clone, RustcEncodable)]
+pub struct FacetCount {
+    pub value: String,
+    pub count: usize,
+}
+
+/// Counts of the regions, game types, tags and maps in a set of search results, most common first.
+#[derive(Debug, Clone, RustcEncodable)]
+pub struct Facets {
+    pub regions: Vec<FacetCount>,
+    pub game_types: Vec<FacetCount>,
+    pub tags: Vec<FacetCount>,
+    pub maps: Vec<FacetCount>,
+}
+
+impl Facets {
+    pub fn of(servers: &[GameServer]) -> Facets {
+        Facets {
+            regions: count_values(servers.iter().map(|s| s.region.as_str())),
+            game_types: count_values(servers.iter().map(|s| s.game_type.as_str())),
+            tags: count_values(servers.iter().flat_map(|s| s.tags.iter().map(|t| t.as_str()))),
+            maps: count_values(servers.iter().filter_map(|s| s.current_map.as_ref().map(|m| m.as_str()))),
+        }
+    }
+}
+
+fn count_values<'a, I>(values: I) -> Vec<FacetCount> where I: Iterator<Item=&'a str> {
+    let mut counts: HashMap<&'a str, usize> = HashMap::new();
+    for v in values {
+        *counts.entry(v).or_insert(0) += 1;
+    }
+    let mut facets: Vec<FacetCount> = counts.into_iter().map(|(v, c)| {
+        FacetCount { value: v.into(), count: c }
+    }).collect();
+    facets.sort_by(|a, b| match b.count.cmp(&a.count) {
+        Ordering::Equal => a.value.cmp(&b.value),
+        other => other,
+    });
+    facets
+}
 
 pub fn search_servers(mut context: Context, mut response: Response) {
-    use ::schema::game_servers::dsl::*;
     let conn = match ::establish_connection() {
         Ok(c) => c,
         Err(e) => {
@@ -26,17 +79,53 @@ pub fn search_servers(mut context: Context, mut response: Response) {
             return;
         }
     };
+    let filters = match SearchFilters::from_json(&body) {
+        Ok(f) => f,
+        Err(msg) => {
+            response.set_status(StatusCode::BadRequest);
+            response.send(format!("{:?}", msg));
+            return;
+        }
+    };
 
-    let search_region: Option<&str> = body.find("region")
-                                             .and_then(|r| r.as_string())
-                                             .and_then(|s| Some(s));
+    send_search_results(&conn, filters, context.address.ip(), response);
+}
+
+/// Searches with the query language in the `q` parameter, see `search::dsl`.
+/// A missing or empty query finds every server.
+pub fn search_servers_query(context: Context, mut response: Response) {
+    let conn = match ::establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Connecting to the DB in search_servers_query failed! {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+
+    let query = context.query.get("q").map(|q| q.into_owned()).unwrap_or(String::new());
+    let filters = match dsl::parse(&query) {
+        Ok(f) => f,
+        Err(e) => {
+            response.set_status(StatusCode::BadRequest);
+            response.send(format!("{{\"error\": {}, \"position\": {}}}",
+                                  json::as_json(&e.to_string()), e.position));
+            return;
+        }
+    };
 
-    let search_game_type: Option<String> = body.find("game_type")
-                                         .and_then(|r| r.as_string())
-                                         .and_then(|s| Some(s.into()));
+    send_search_results(&conn, filters, context.address.ip(), response);
+}
 
-    match regions_allowed(&conn, search_region.into_iter()) {
-        AllowedRegion::Success => {},
+/// Runs a search and sends its results, facets and inferred regions.
+fn send_search_results(conn: &PgConnection, mut filters: SearchFilters, client: IpAddr,
+                       mut response: Response) {
+    let region_count = filters.regions.len();
+    let mut canonical = match regions_allowed(conn, filters.regions.iter()
+                                                          .chain(filters.exclude_regions.iter())
+                                                          .map(|r| r.as_str())) {
+        AllowedRegion::Success(canonical) => canonical,
         AllowedRegion::Failure(failures) => {
             response.set_status(StatusCode::BadRequest);
             response.send(format!("\"Regions `{:?}` do not exist in the Database!\"", failures));
@@ -48,16 +137,24 @@ pub fn search_servers(mut context: Context, mut response: Response) {
             return;
         }
     };
+    filters.exclude_regions = canonical.split_off(region_count);
+    filters.regions = canonical;
 
-    let mut query = game_servers.into_boxed();
-    if let Some(r) = search_region {
-        query = query.filter(region.eq(r));
-    }
-    if let Some(g) = search_game_type {
-        query = query.filter(game_type.eq(g));
-    }
+    // Without a region to search in, we guess which regions are nearest to the client.
+    let nearest: Vec<String> = if !filters.has_region() {
+        match nearest_regions(conn, client) {
+            Ok(n) => n,
+            Err(e) => {
+                error!("Could not load regions to infer the nearest ones: {:?}", e);
+                response.set_status(StatusCode::InternalServerError);
+                return;
+            }
+        }
+    } else {
+        vec![]
+    };
 
-    let results = match query.load::<GameServer>(&conn) {
+    let SearchResults { servers: mut results, scores } = match search::load(conn, &filters) {
         Ok(v) => v,
         Err(e) => {
             error!("Server search filter failed: {:?}", e);
@@ -66,6 +163,48 @@ pub fn search_servers(mut context: Context, mut response: Response) {
         }
     };
 
+    // Relevance to the searched text beats distance.
+    if !nearest.is_empty() && filters.text.is_none() {
+        let rank: HashMap<&str, usize> = nearest.iter().enumerate()
+                                                .map(|(i, r)| (r.as_str(), i)).collect();
+        results.sort_by_key(|s| rank.get(s.region.as_str()).cloned().unwrap_or(nearest.len()));
+    }
+    // Sorting by region mixed the servers `search::load` listed last back in.
+    if trust::demote_suspicious() {
+        results.sort_by_key(|s| s.suspicious);
+    }
+
+    let inferred = nearest.iter().take(INFERRED_REGION_COUNT).collect::<Vec<_>>();
+    let json_inferred = match json::encode(&inferred) {
+        Ok(r) => r,
+        Err(e) => {
+            error!("Could not encode inferred regions as JSON: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+
+    let json_facets = match json::encode(&Facets::of(&results)) {
+        Ok(r) => r,
+        Err(e) => {
+            error!("Could not encode search facets as JSON: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+
+    // Relevance is scored before the other filters apply, so it covers servers they left out.
+    let scores_by_id: BTreeMap<String, f32> =
+        results.iter().filter_map(|s| scores.get(&s.id).map(|v| (s.id.to_string(), *v))).collect();
+    let json_scores = match json::encode(&scores_by_id) {
+        Ok(r) => r,
+        Err(e) => {
+            error!("Could not encode search scores as JSON: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+
     let json_response = match json::encode(&results) {
         Ok(r) => r,
         Err(e) => {
@@ -74,5 +213,20 @@ pub fn search_servers(mut context: Context, mut response: Response) {
             return;
         }
     };
-    response.send(format!("{{\"results\": {}, \"size\": {}}}", json_response, results.len()))
+    response.send(format!("{{\"results\": {}, \"size\": {}, \"inferred_regions\": {}, \"facets\": {}, \
+                           \"scores\": {}}}",
+                          json_response, results.len(), json_inferred, json_facets, json_scores))
+}
+
+/// Names of all located regions, nearest to the client first.
+/// Empty when the client's location can not be determined.
+fn nearest_regions(conn: &PgConnection, client: IpAddr) -> Result<Vec<String>, DieselError> {
+    use ::schema::regions::dsl::regions;
+
+    let location = match geoip::locate(client) {
+        Some(l) => l,
+        None => return Ok(vec![]),
+    };
+    let all = try!(regions.load::<Region>(conn));
+    Ok(geoip::regions_by_distance(location, all).into_iter().map(|(r, _)| r.name).collect())
 }
@@ -1,8 +1,18 @@
 
+use diesel;
 use diesel::prelude::*;
+use diesel::result::OptionalExtension;
+use rustc_serialize::json;
 use rustful::{Context, Response, header, StatusCode};
 
-use ::models::{UpdatedGameServer, GameServer};
+use ::blocklist;
+use ::hostnames::resolve_endpoint;
+use ::in_transaction;
+use ::models::{UpdatedGameServer, GameServer, OPERATING_SYSTEMS};
+use ::moderation;
+use ::names;
+use ::routes::{is_admin, regions_allowed, require_owned_server, AllowedRegion};
+use ::server_info;
 
 pub fn update_server(mut context: Context, mut response: Response) {
     use ::schema::game_servers::dsl::*;
@@ -17,27 +27,11 @@ pub fn update_server(mut context: Context, mut response: Response) {
     };
     response.headers_mut().set(header::ContentType::json());
 
-    let server_id: i32 = match context.variables.parse::<_, i32>("id") {
-        Ok(v) => v,
-        Err(Some(e)) => {
-            error!("Could not parse the ID as an i32: {:?}", e);
-            response.set_status(StatusCode::BadRequest);
-            return;
-        },
-        Err(None) => {
-            error!("Another Error occured during context variable parsing.");
-            response.set_status(StatusCode::InternalServerError);
-            return;
-        }
-    };
-    let mut server: GameServer = match game_servers.filter(id.eq(server_id)).first(&conn) {
-        Ok(s) => s,
-        Err(e) => {
-            error!("Server ID does not exist: {:?}", e);
-            response.set_status(StatusCode::BadRequest);
-            return;
-        }
+    let (_, server) = match require_owned_server(&context, &conn, &mut response) {
+        Some(s) => s,
+        None => return,
     };
+    let server_id = server.id;
     let body = match context.body.read_json_body() {
         Ok(b) => b,
         Err(e) => {
@@ -47,22 +41,104 @@ pub fn update_server(mut context: Context, mut response: Response) {
         }
     };
 
-    // FIXME: Need to verify that the id is valid.
     let mut updated_server = UpdatedGameServer::default();
-    // FIXME: None of this accounts for parsing errors. DEAL WITH THEM
-    updated_server.name =  body.find("name")
-                               .and_then(|s| s.as_string())
-                               .and_then(|s| Some(s.into()));
-    // FIXME: check this against regions_allowed
-    updated_server.region =  body.find("region")
-                                 .and_then(|s| s.as_string())
-                                 .and_then(|s| Some(s.into()));
+    if let Some(n) = body.find("name").and_then(|s| s.as_string()) {
+        updated_server.name = match names::sanitize(n) {
+            Ok(n) => Some(n),
+            Err(e) => {
+                response.set_status(StatusCode::BadRequest);
+                response.send(format!("{{\"error\": {}, \"rule\": \"{}\"}}",
+                                      json::as_json(&e.to_string()), e.rule()));
+                return;
+            }
+        };
+    }
+    if let Some(r) = body.find("region").and_then(|s| s.as_string()) {
+        updated_server.region = match regions_allowed(&conn, Some(r).into_iter()) {
+            AllowedRegion::Success(mut canonical) => Some(canonical.remove(0)),
+            AllowedRegion::Failure(failures) => {
+                response.set_status(StatusCode::BadRequest);
+                response.send(format!("\"Regions `{:?}` do not exist in the Database!\"", failures));
+                return;
+            },
+            AllowedRegion::Panic => {
+                error!("Failed in update_server/regions_allowed, can not complete request.");
+                response.set_status(StatusCode::InternalServerError);
+                return;
+            }
+        };
+    }
     updated_server.game_type = body.find("game_type")
                                    .and_then(|s| s.as_string())
                                    .and_then(|s| Some(s.into()));
-    updated_server.ip = body.find("ip")
-                            .and_then(|s| s.as_string())
-                            .and_then(|s| Some(s.into()));
+    let given_address = body.find("address").or_else(|| body.find("ip")).and_then(|s| s.as_string());
+    let given_port: Option<i32> = match body.find("port") {
+        None => None,
+        Some(p) => match p.as_i64() {
+            Some(p) if p > 0 && p <= 65535 => Some(p as i32),
+            _ => {
+                response.set_status(StatusCode::BadRequest);
+                response.send("\"port must be a port number!\"");
+                return;
+            }
+        }
+    };
+    if given_address.is_some() || given_port.is_some() {
+        // A new port alone keeps the server at its host name, if it was listed by one.
+        let current = server.hostname.clone().unwrap_or(server.address.clone());
+        let (endpoint, given_hostname) = match resolve_endpoint(given_address.unwrap_or(&current),
+                                                                given_port) {
+            Ok(a) => a,
+            Err(e) => {
+                response.set_status(StatusCode::BadRequest);
+                response.send(format!("{}", json::as_json(&e)));
+                return;
+            }
+        };
+        match blocklist::blocking_listing(&conn, endpoint.ip(), &endpoint.to_string()) {
+            Ok(None) => {},
+            Ok(Some(network)) => {
+                response.set_status(StatusCode::Forbidden);
+                response.send(format!("{{\"error\": \"Servers in this network can not be listed.\", \
+                                       \"network\": \"{}\"}}", network));
+                return;
+            },
+            Err(e) => {
+                error!("Could not check the blocklist in update_server: {:?}", e);
+                response.set_status(StatusCode::InternalServerError);
+                return;
+            }
+        }
+        let listed = game_servers.select(id)
+                                 .filter(address.eq(endpoint.ip().to_string()))
+                                 .filter(port.eq(endpoint.port() as i32))
+                                 .filter(id.ne(server_id))
+                                 .first::<i32>(&conn).optional();
+        match listed {
+            Ok(None) => {},
+            Ok(Some(existing)) => {
+                response.set_status(StatusCode::Conflict);
+                response.send(format!("{{\"error\": \"Another server is listed at `{1}`.\", \"id\": {0}, \
+                                       \"url\": \"/server/{0}\"}}", existing, endpoint));
+                return;
+            },
+            Err(e) => {
+                error!("Could not check if an address is already listed in update_server: {:?}", e);
+                response.set_status(StatusCode::InternalServerError);
+                return;
+            }
+        }
+        updated_server.address = Some(endpoint.ip().to_string());
+        updated_server.port = Some(endpoint.port() as i32);
+        updated_server.hostname = Some(given_hostname);
+        // Whoever runs the server at the new address has to show the token again.
+        if server.socket_address() != Some(endpoint) {
+            updated_server.verified = Some(false);
+        }
+    }
+    updated_server.motd = body.find("motd")
+                              .and_then(|s| s.as_string())
+                              .and_then(|s| Some(s.into()));
     // FIXME: Casts == bad.
     updated_server.max_users = body.find("max_users")
                                     .and_then(|s| s.as_i64()
@@ -70,6 +146,25 @@ pub fn update_server(mut context: Context, mut response: Response) {
     updated_server.max_premium_users = body.find("max_premium_users")
                                            .and_then(|s| s.as_i64()
                                            .and_then(|v| Some(v as i32)));
+    updated_server.password_protected = body.find("password_protected").and_then(|s| s.as_boolean());
+    updated_server.vac_secured = body.find("vac_secured").and_then(|s| s.as_boolean());
+    updated_server.dedicated = body.find("dedicated").and_then(|s| s.as_boolean());
+    updated_server.bots = body.find("bots")
+                              .and_then(|s| s.as_i64()
+                              .and_then(|v| Some(v as i32)));
+    updated_server.version = body.find("version")
+                                 .and_then(|s| s.as_string())
+                                 .and_then(|s| Some(s.into()));
+    if let Some(o) = body.find("os").and_then(|s| s.as_string()) {
+        let o = o.to_lowercase();
+        if !OPERATING_SYSTEMS.contains(&o.as_str()) {
+            response.set_status(StatusCode::BadRequest);
+            response.send(format!("\"os must be one of {:?}!\"", OPERATING_SYSTEMS));
+            return;
+        }
+        updated_server.os = Some(o);
+    }
+
     match body.find("tags") {
         Some(t) => {
             match t.as_array() {
@@ -99,16 +194,76 @@ pub fn update_server(mut context: Context, mut response: Response) {
         None => {}
     }
 
-    server.update(updated_server);
+    // The game type a server leaves may have lost its newest version with it.
+    let previous_game_type = server.game_type.clone();
+    let version_changed = updated_server.version.is_some() || updated_server.game_type.is_some();
+    let moved = updated_server.address.as_ref().map_or(false, |a| *a != server.address);
+    let renamed = updated_server.name.as_ref().map_or(false, |n| *n != server.name);
+    let readdressed = moved || updated_server.port.map_or(false, |p| p != server.port);
+    // Admins approved the name and address the server had. Unless an admin made the change
+    // or the owner is trusted, a new one is reviewed again, like a new listing.
+    let review = match server.owner_id {
+        Some(owner) if (renamed || readdressed) && !is_admin(&context) => {
+            match moderation::is_trusted(&conn, owner) {
+                Ok(trusted) => !trusted,
+                Err(e) => {
+                    error!("Unable to check if user {} is trusted in update_server: {:?}",
+                           owner, e);
+                    response.set_status(StatusCode::InternalServerError);
+                    return;
+                }
+            }
+        },
+        _ => false,
+    };
 
-    // FIXME: cant update tags because thats a lot of work.
-    match server.save_changes::<GameServer>(&conn) {
-        Ok(_n) => {},
+    // The row is updated before its status is read, so that the status is the current one,
+    // and the server is held before anyone can see the new name.
+    let updated = in_transaction(&conn, || {
+        if !updated_server.is_empty() {
+            try!(diesel::update(game_servers.filter(id.eq(server_id)))
+                        .set(&updated_server)
+                        .execute(&conn));
+        }
+        let server = try!(game_servers.filter(id.eq(server_id)).first::<GameServer>(&conn));
+        if review && server.status == moderation::APPROVED {
+            let reason = if renamed { "it was renamed" } else { "it moved to a new address" };
+            try!(moderation::hold_for_review(&conn, &server, reason));
+            return Ok((server, true));
+        }
+        Ok((server, false))
+    });
+    let (server, held) = match updated {
+        Ok(u) => u,
         Err(e) => {
-            error!("Unable to update game_servers in update_server: {:?}", e);
+            error!("Unable to update server {} in update_server: {:?}", server_id, e);
             response.set_status(StatusCode::InternalServerError);
             return;
         }
+    };
+    // An RCON password belongs to the machine it was set for, not to the listing.
+    if moved {
+        use ::schema::rcon_credentials;
+        let credentials = rcon_credentials::table.filter(rcon_credentials::server_id.eq(server_id));
+        if let Err(e) = diesel::delete(credentials).execute(&conn) {
+            error!("Unable to forget the RCON password of moved server {}: {:?}", server_id, e);
+        }
+    }
+    if version_changed {
+        let mut game_types = vec![&server.game_type];
+        if previous_game_type != server.game_type {
+            game_types.push(&previous_game_type);
+        }
+        for g in game_types {
+            if let Err(e) = server_info::flag_outdated(&conn, Some(g)) {
+                error!("Unable to flag outdated {} servers in update_server: {:?}", g, e);
+            }
+        }
+    }
+    if held {
+        response.send("\"Update of server was successful, it is hidden until an admin \
+                       reviews it again\"");
+        return;
     }
 
     response.send("\"Update of server was successful\"");
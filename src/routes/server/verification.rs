@@ -0,0 +1,64 @@
+use rustful::{Context, Response, header, StatusCode};
+
+use ::routes::require_owned_server;
+use ::verification::{self, CheckError};
+
+/// Shows whether a server is verified, the token it has to show to become verified, and
+/// the secret it sends heartbeats with. Owners and admins only.
+pub fn get_verification(context: Context, mut response: Response) {
+    let conn = match ::establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Connecting to the DB in get_verification failed: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+    let (_, server) = match require_owned_server(&context, &conn, &mut response) {
+        Some(s) => s,
+        None => return,
+    };
+    response.send(format!("{{\"server_id\": {}, \"verified\": {}, \"verification_token\": \"{}\", \
+                           \"heartbeat_secret\": \"{}\", \
+                           \"instructions\": \"Put the token in any rule (such as sv_tags) or in \
+                           the name of the server. Send the secret in the X-Heartbeat-Secret \
+                           header of heartbeats.\"}}",
+                          server.id, server.verified, server.verification_token,
+                          server.heartbeat_secret));
+}
+
+/// Queries a server right away to see if it shows its token. Owners and admins only.
+pub fn check_verification(context: Context, mut response: Response) {
+    let conn = match ::establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Connecting to the DB in check_verification failed: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+    let (_, server) = match require_owned_server(&context, &conn, &mut response) {
+        Some(s) => s,
+        None => return,
+    };
+    match verification::check(&conn, &server) {
+        Ok(verified) => {
+            response.send(format!("{{\"server_id\": {}, \"verified\": {}}}", server.id, verified));
+        },
+        Err(CheckError::NoAddress) => {
+            response.set_status(StatusCode::BadRequest);
+            response.send(format!("\"Can not query the server at `{}`!\"", server.endpoint()));
+        },
+        Err(CheckError::Query(e)) => {
+            warn!("Could not query server {} to verify it: {}", server.id, e);
+            response.set_status(StatusCode::BadGateway);
+            response.send("\"The server did not answer, try again later.\"");
+        },
+        Err(CheckError::Db(e)) => {
+            error!("Could not mark server {} verified: {:?}", server.id, e);
+            response.set_status(StatusCode::InternalServerError);
+        }
+    }
+}
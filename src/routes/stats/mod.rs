@@ -0,0 +1,128 @@
+//! Aggregate statistics over every listed server, for landing pages and the like.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use diesel::expression::dsl::sql;
+use diesel::types::{BigInt, Text};
+use rustc_serialize::json;
+use rustful::{Context, Response, header, StatusCode};
+
+use ::establish_connection;
+use ::models::GameServer;
+use ::moderation;
+
+/// How long computed stats are served before they are computed again.
+const CACHE_SECS: u64 = 30;
+/// How many servers are listed as busiest.
+const BUSIEST_COUNT: i64 = 10;
+
+lazy_static! {
+    /// The last encoded stats, and when they were computed.
+    static ref CACHE: Mutex<Option<(Instant, String)>> = Mutex::new(None);
+}
+
+/// Servers, players and player slots of a group of servers.
+#[derive(Debug, Clone, RustcEncodable, Queryable)]
+pub struct GroupStats {
+    pub name: String,
+    pub servers: i64,
+    pub players: i64,
+    pub capacity: i64,
+}
+
+#[derive(Debug, Clone, RustcEncodable)]
+pub struct Stats {
+    pub servers: i64,
+    pub players: i64,
+    pub capacity: i64,
+    /// Players divided by capacity, 0 when there is no capacity at all.
+    pub fill_rate: f64,
+    pub regions: Vec<GroupStats>,
+    pub game_types: Vec<GroupStats>,
+    pub tags: Vec<GroupStats>,
+    pub busiest: Vec<GameServer>,
+}
+
+pub fn get_stats(_context: Context, mut response: Response) {
+    response.headers_mut().set(header::ContentType::json());
+
+    let mut cache = CACHE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some((computed_at, ref encoded)) = *cache {
+        if computed_at.elapsed() < Duration::from_secs(CACHE_SECS) {
+            response.send(encoded.clone());
+            return;
+        }
+    }
+
+    let conn = match establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Could not establish connection to DB: {}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    let stats = match compute_stats(&conn) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Could not execute queries in get_stats: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    let encoded = match json::encode(&stats) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Could not encode results of get_stats as json: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    *cache = Some((Instant::now(), encoded.clone()));
+    response.send(encoded);
+}
+
+fn compute_stats(conn: &PgConnection) -> QueryResult<Stats> {
+    use ::schema::game_servers::dsl::*;
+
+    let totals: GroupStats = try!(sql::<(Text, BigInt, BigInt, BigInt)>(&format!(
+        "SELECT 'all'::varchar, count(*), coalesce(sum(current_users), 0)::bigint,
+                coalesce(sum(max_users), 0)::bigint
+         FROM game_servers
+         WHERE status = '{}'", moderation::APPROVED)).get_result(conn));
+    let by_region = try!(grouped_by(conn, "region"));
+    let by_game_type = try!(grouped_by(conn, "game_type"));
+    let by_tag = try!(grouped_by(conn, "unnest(tags)"));
+    let busiest = try!(game_servers.filter(status.eq(moderation::APPROVED))
+                                   .order(current_users.desc()).limit(BUSIEST_COUNT)
+                                   .load::<GameServer>(conn));
+
+    Ok(Stats {
+        servers: totals.servers,
+        players: totals.players,
+        capacity: totals.capacity,
+        fill_rate: if totals.capacity > 0 {
+            totals.players as f64 / totals.capacity as f64
+        } else {
+            0.0
+        },
+        regions: by_region,
+        game_types: by_game_type,
+        tags: by_tag,
+        busiest: busiest,
+    })
+}
+
+/// Totals of servers grouped by a column (or expression) of `game_servers`, busiest first.
+fn grouped_by(conn: &PgConnection, group: &'static str) -> QueryResult<Vec<GroupStats>> {
+    sql::<(Text, BigInt, BigInt, BigInt)>(&format!(
+        "SELECT grp, count(*), sum(current_users)::bigint, sum(max_users)::bigint
+         FROM (SELECT {} AS grp, current_users, max_users
+               FROM game_servers
+               WHERE status = '{}') AS grouped
+         GROUP BY grp
+         ORDER BY sum(current_users) DESC, grp", group, moderation::APPROVED)).load(conn)
+}
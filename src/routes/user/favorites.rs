@@ -0,0 +1,132 @@
+
+use diesel;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use diesel::result::OptionalExtension;
+use rustful::{Context, Response, header, StatusCode};
+
+use ::establish_connection;
+use ::models::{GameServer, NewFavorite};
+use ::routes::require_user;
+use super::{body_server, load_entries, send_entries};
+
+pub fn get_favorites(context: Context, mut response: Response) {
+    let conn = match establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Could not establish connection to DB: {}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+    let user = match require_user(&context, &conn, &mut response) {
+        Some(u) => u,
+        None => return,
+    };
+
+    let entries = match load_entries(&conn, "favorites", "added_at", user.id) {
+        Ok(e) => e,
+        Err(e) => {
+            error!("Could not execute query in get_favorites: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    send_entries(&conn, entries, response);
+}
+
+pub fn add_favorite(mut context: Context, mut response: Response) {
+    let conn = match establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Could not establish connection to DB: {}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+    let user = match require_user(&context, &conn, &mut response) {
+        Some(u) => u,
+        None => return,
+    };
+    let server = match body_server(&mut context, &conn, &mut response) {
+        Some(s) => s,
+        None => return,
+    };
+
+    if let Err(e) = save_favorite(&conn, user.id, &server) {
+        error!("Failed to add a favorite in add_favorite: {:?}", e);
+        response.set_status(StatusCode::InternalServerError);
+        return;
+    }
+    response.send(format!("\"server `{}` added to favorites!\"", server.name));
+}
+
+/// Adds a server to a user's favorites. A favorite of a server at the same address is relinked
+/// instead, as the server may have been listed again since it was added.
+pub fn save_favorite(conn: &PgConnection, favorite_user: i32, server: &GameServer) -> QueryResult<()> {
+    use ::schema::favorites::dsl::*;
+
+    let existing = try!(favorites.select(id).filter(user_id.eq(favorite_user))
+                                 .filter(ip.eq(server.endpoint())).first::<i32>(conn).optional());
+    match existing {
+        Some(favorite_id) => {
+            diesel::update(favorites.filter(id.eq(favorite_id)))
+                   .set((server_id.eq(Some(server.id)), name.eq(&server.name))).execute(conn)
+        },
+        None => {
+            let favorite = NewFavorite {
+                user_id: favorite_user,
+                server_id: Some(server.id),
+                ip: server.endpoint(),
+                name: server.name.clone(),
+            };
+            diesel::insert(&favorite).into(favorites).execute(conn)
+        },
+    }.map(|_| ())
+}
+
+/// Removes a favorite by its own id, which is not necessarily the id of its server.
+pub fn delete_favorite(context: Context, mut response: Response) {
+    use ::schema::favorites::dsl::*;
+
+    let conn = match establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Could not establish connection to DB: {}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    let user = match require_user(&context, &conn, &mut response) {
+        Some(u) => u,
+        None => return,
+    };
+    let favorite_id: i32 = match context.variables.parse("id") {
+        Ok(i) => i,
+        Err(Some(e)) => {
+            error!("The id must be an integer: {:?}", e);
+            response.set_status(StatusCode::BadRequest);
+            return;
+        },
+        Err(None) => {
+            error!("No id provided!");
+            response.set_status(StatusCode::BadRequest);
+            return;
+        }
+    };
+    let target = favorites.filter(id.eq(favorite_id)).filter(user_id.eq(user.id));
+    match diesel::delete(target).execute(&conn) {
+        Ok(v) => {
+            if v != 1 {
+                error!("Favorite does not exist, nothing deleted.");
+                response.set_status(StatusCode::BadRequest);
+            }
+        },
+        Err(e) => {
+            error!("Encountered an error deleting favorite id {}: {:?}", favorite_id, e);
+            response.set_status(StatusCode::InternalServerError);
+        }
+    }
+}
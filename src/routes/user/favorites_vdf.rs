@@ -0,0 +1,184 @@
+
+use std::io::Read;
+
+use std::net::SocketAddr;
+
+use diesel::prelude::*;
+use diesel::expression::dsl::{any, sql};
+use diesel::types::Bool;
+use rustc_serialize::json;
+use rustful::{Context, Response, header, StatusCode};
+
+use ::establish_connection;
+use ::models::{parse_endpoint, GameServer};
+use ::moderation;
+use ::search::endpoints_sql;
+use ::vdf::{self, Value};
+use super::favorites::save_favorite;
+use ::routes::require_user;
+use super::load_entries;
+
+/// The name Steam gives its server browser favorites and history file.
+const VDF_FILE_NAME: &'static str = "serverbrowser_hist.vdf";
+/// The largest file that is imported. Steam's own files are a few kilobytes.
+const MAX_VDF_SIZE: u64 = 1024 * 1024;
+
+/// Adds every server in an uploaded Steam favorites file that is listed in fula, matching
+/// on the address. Servers that are not listed are reported back as unmatched.
+pub fn import_favorites_vdf(mut context: Context, mut response: Response) {
+    use ::schema::game_servers::dsl::*;
+
+    let conn = match establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Could not establish connection to DB: {}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+    let user = match require_user(&context, &conn, &mut response) {
+        Some(u) => u,
+        None => return,
+    };
+
+    let mut body = String::new();
+    // One byte more than allowed is read, to tell a file of exactly the largest size from one
+    // that is too large.
+    if let Err(e) = (&mut context.body).take(MAX_VDF_SIZE + 1).read_to_string(&mut body) {
+        error!("Could not read the body of import_favorites_vdf as text: {:?}", e);
+        response.set_status(StatusCode::BadRequest);
+        return;
+    }
+    if body.len() as u64 > MAX_VDF_SIZE {
+        response.set_status(StatusCode::PayloadTooLarge);
+        response.send(format!("\"Favorites files can be at most {} bytes!\"", MAX_VDF_SIZE));
+        return;
+    }
+    let document = match vdf::parse(&body) {
+        Ok(d) => d,
+        Err(e) => {
+            response.set_status(StatusCode::BadRequest);
+            response.send(format!("{}", json::as_json(&format!("Invalid VDF file, {}", e))));
+            return;
+        }
+    };
+
+    let addresses = favorite_addresses(&document);
+    let endpoints: Vec<SocketAddr> = addresses.iter().filter_map(|a| parse_endpoint(a, None).ok())
+                                              .collect();
+    let found: Vec<GameServer> = match game_servers.filter(sql::<Bool>(&endpoints_sql(&endpoints)))
+                                                   .filter(status.eq(moderation::APPROVED))
+                                                   .load(&conn) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Could not execute query in import_favorites_vdf: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    for server in &found {
+        if let Err(e) = save_favorite(&conn, user.id, server) {
+            error!("Failed to add a favorite in import_favorites_vdf: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    }
+
+    let unmatched: Vec<&String> = addresses.iter().filter(|a| {
+        let endpoint = parse_endpoint(a, None).ok();
+        !found.iter().any(|s| endpoint.is_some() && s.socket_address() == endpoint)
+    }).collect();
+    let encoded = match json::encode(&unmatched) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Could not encode unmatched favorites as json: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.send(format!("{{\"added\": {}, \"unmatched\": {}}}", found.len(), encoded));
+}
+
+/// Sends the user's favorites as a Steam server browser file.
+pub fn export_favorites_vdf(context: Context, mut response: Response) {
+    use ::schema::game_servers::dsl::*;
+
+    let conn = match establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Could not establish connection to DB: {}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    let user = match require_user(&context, &conn, &mut response) {
+        Some(u) => u,
+        None => return,
+    };
+
+    let entries = match load_entries(&conn, "favorites", "added_at", user.id) {
+        Ok(e) => e,
+        Err(e) => {
+            error!("Could not execute query in export_favorites_vdf: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    let ids: Vec<i32> = entries.iter().filter_map(|e| e.server_id).collect();
+    let servers: Vec<GameServer> = match game_servers.filter(id.eq(any(ids)))
+                                                     .filter(status.eq(moderation::APPROVED))
+                                                     .load(&conn) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Could not load favorite servers in export_favorites_vdf: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+
+    let favorites: Vec<(String, Value)> = entries.iter().enumerate().map(|(i, e)| {
+        let mut pairs = vec![
+            ("name".to_string(), Value::Str(e.name.clone())),
+            ("address".to_string(), Value::Str(e.ip.clone())),
+            ("LastPlayed".to_string(), Value::Str("0".into())),
+        ];
+        let app_id = servers.iter().find(|s| Some(s.id) == e.server_id)
+                            .and_then(|s| s.steam_app_id());
+        if let Some(app) = app_id {
+            pairs.push(("appid".to_string(), Value::Str(app.to_string())));
+        }
+        (i.to_string(), Value::Section(pairs))
+    }).collect();
+    let document = vec![
+        ("Filters".to_string(), Value::Section(vec![
+            ("Favorites".to_string(), Value::Section(favorites)),
+        ])),
+    ];
+
+    response.headers_mut().set(header::ContentType::plaintext());
+    response.headers_mut().set_raw("Content-Disposition",
+        vec![format!("attachment; filename=\"{}\"", VDF_FILE_NAME).into_bytes()]);
+    response.send(vdf::write(&document));
+}
+
+/// The addresses in the `Favorites` section of a Steam server browser file, which is usually
+/// inside a `Filters` section.
+fn favorite_addresses(document: &[(String, Value)]) -> Vec<String> {
+    let root = Value::Section(document.to_vec());
+    let section = root.get("Filters").and_then(|f| f.get("Favorites"))
+                      .or_else(|| root.get("Favorites"));
+    let entries = match section.and_then(|s| s.as_section()) {
+        Some(e) => e,
+        None => return vec![],
+    };
+    let mut addresses: Vec<String> = entries.iter()
+                                            .filter_map(|&(_, ref e)| e.get("address"))
+                                            .filter_map(|a| a.as_str())
+                                            .map(|a| a.trim().to_string())
+                                            .filter(|a| !a.is_empty())
+                                            .collect();
+    addresses.sort();
+    addresses.dedup();
+    addresses
+}
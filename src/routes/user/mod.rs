@@ -0,0 +1,222 @@
+//! Users, and their personal lists of servers.
+
+mod favorites;
+mod favorites_vdf;
+mod notifications;
+mod play_history;
+
+pub use self::favorites::{get_favorites, add_favorite, delete_favorite};
+pub use self::favorites_vdf::{import_favorites_vdf, export_favorites_vdf};
+pub use self::notifications::{get_notifications, set_contact};
+pub use self::play_history::{get_play_history, add_play_history, delete_play_history};
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use diesel;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use diesel::expression::dsl::{any, sql};
+use diesel::result::OptionalExtension;
+use diesel::types::{BigInt, Bool, Integer, Nullable, Text};
+use rand::{OsRng, Rng};
+use rustc_serialize::json;
+use rustful::{Context, Response, header, StatusCode};
+
+use ::establish_connection;
+use ::models::{parse_endpoint, GameServer, NewUser, ServerListEntry};
+use ::moderation;
+use ::search::endpoints_sql;
+
+/// Length of the tokens handed out to users.
+const TOKEN_LENGTH: usize = 40;
+
+pub fn register_user(mut context: Context, mut response: Response) {
+    use ::schema::users;
+
+    let conn = match establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Could not establish connection to DB: {}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+
+    let body = match context.body.read_json_body() {
+        Ok(b) => b,
+        Err(e) => {
+            error!("Could not decode JSON body in register_user: {}", e);
+            response.set_status(StatusCode::BadRequest);
+            return;
+        }
+    };
+    let user_name: String = match body.find("name").and_then(|v| v.as_string()) {
+        Some(s) if !s.trim().is_empty() => s.trim().into(),
+        _ => {
+            error!("register_user must have a non-empty string `name` parameter.");
+            response.set_status(StatusCode::BadRequest);
+            return;
+        }
+    };
+    let mut rng = match OsRng::new() {
+        Ok(r) => r,
+        Err(e) => {
+            error!("Could not open the OS random number generator: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+
+    let taken = users::table.select(users::id).filter(users::name.eq(&user_name))
+                            .first::<i32>(&conn).optional();
+    match taken {
+        Ok(None) => {},
+        Ok(Some(_)) => {
+            response.set_status(StatusCode::Conflict);
+            response.send(format!("\"The name `{}` is already taken!\"", user_name));
+            return;
+        },
+        Err(e) => {
+            error!("Could not check if a user name is taken: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    }
+
+    let new_user = NewUser {
+        name: user_name,
+        token: rng.gen_ascii_chars().take(TOKEN_LENGTH).collect(),
+    };
+    match diesel::insert(&new_user).into(users::table).execute(&conn) {
+        Ok(_) => {},
+        Err(e) => {
+            error!("Failed to insert new user into users: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    }
+    let user_id: i32 = match users::table.select(users::id).filter(users::token.eq(&new_user.token))
+                                         .first(&conn) {
+        Ok(i) => i,
+        Err(e) => {
+            error!("Could not load the id of a new user: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.send(format!("{{\"id\": {}, \"name\": {}, \"token\": \"{}\"}}",
+                          user_id, json::as_json(&new_user.name), new_user.token));
+}
+
+/// Reads the `server_id` of a JSON body, and loads that server if it is listed.
+/// Sets the response status and returns `None` on failure.
+fn body_server(context: &mut Context, conn: &PgConnection, response: &mut Response)
+               -> Option<GameServer> {
+    use ::schema::game_servers::dsl::*;
+
+    let body = match context.body.read_json_body() {
+        Ok(b) => b,
+        Err(e) => {
+            error!("Could not decode JSON body with a server_id: {}", e);
+            response.set_status(StatusCode::BadRequest);
+            return None;
+        }
+    };
+    let server_id = match body.find("server_id").and_then(|v| v.as_i64()) {
+        Some(v) => v as i32,
+        None => {
+            error!("The JSON body must have an integer `server_id`.");
+            response.set_status(StatusCode::BadRequest);
+            return None;
+        }
+    };
+    match game_servers.filter(id.eq(server_id))
+                      .filter(status.eq(moderation::APPROVED))
+                      .first(conn) {
+        Ok(s) => Some(s),
+        Err(e) => {
+            error!("Server ID does not exist or is not approved: {:?}", e);
+            response.set_status(StatusCode::BadRequest);
+            None
+        }
+    }
+}
+
+/// Loads the entries of a user's list, newest first. `list` is the table of the list, and
+/// `time_column` its column of when an entry was last touched.
+fn load_entries(conn: &PgConnection, list: &'static str, time_column: &'static str, user_id: i32)
+                -> QueryResult<Vec<ServerListEntry>> {
+    sql::<(Integer, Nullable<Integer>, Text, Text, BigInt)>(&format!(
+        "SELECT id, server_id, ip, name, extract(epoch FROM {1})::bigint
+         FROM {0}
+         WHERE user_id = {2}
+         ORDER BY {1} DESC", list, time_column, user_id)).load(conn)
+}
+
+/// An entry of a user's list, with the server it refers to.
+#[derive(Debug, Clone, RustcEncodable)]
+struct ListedServer {
+    id: i32,
+    ip: String,
+    name: String,
+    time: i64,
+    /// Whether the server is no longer listed, or is hidden until an admin reviews it.
+    /// `server` is null when it is.
+    missing: bool,
+    server: Option<GameServer>,
+}
+
+/// Sends the entries of a user's list along with the current state of their servers.
+/// Entries whose server was deleted are matched to a server listed at the same address, if any.
+fn send_entries(conn: &PgConnection, entries: Vec<ServerListEntry>, mut response: Response) {
+    use ::schema::game_servers::dsl::*;
+
+    let ids: Vec<i32> = entries.iter().filter_map(|e| e.server_id).collect();
+    let orphans: Vec<SocketAddr> = entries.iter().filter(|e| e.server_id.is_none())
+                                          .filter_map(|e| parse_endpoint(&e.ip, None).ok()).collect();
+    let by_id = game_servers.filter(id.eq(any(ids)))
+                            .filter(status.eq(moderation::APPROVED))
+                            .load::<GameServer>(conn);
+    let by_ip = game_servers.filter(sql::<Bool>(&endpoints_sql(&orphans)))
+                            .filter(status.eq(moderation::APPROVED))
+                            .load::<GameServer>(conn);
+    let (by_id, by_ip) = match (by_id, by_ip) {
+        (Ok(a), Ok(b)) => (a, b),
+        (Err(e), _) | (_, Err(e)) => {
+            error!("Could not load the servers of a user's list: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    let by_id: HashMap<i32, GameServer> = by_id.into_iter().map(|s| (s.id, s)).collect();
+    let by_ip: HashMap<SocketAddr, GameServer> = by_ip.into_iter()
+                                                      .filter_map(|s| s.socket_address().map(|a| (a, s)))
+                                                      .collect();
+
+    let listed: Vec<ListedServer> = entries.into_iter().map(|e| {
+        let server = match e.server_id {
+            Some(i) => by_id.get(&i).cloned(),
+            None => parse_endpoint(&e.ip, None).ok().and_then(|a| by_ip.get(&a)).cloned(),
+        };
+        ListedServer {
+            id: e.id,
+            ip: e.ip,
+            name: e.name,
+            time: e.time,
+            missing: server.is_none(),
+            server: server,
+        }
+    }).collect();
+
+    let encoded = match json::encode(&listed) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Could not encode a user's list as json: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.send(format!("{{\"results\": {}, \"size\": {}}}", encoded, listed.len()));
+}
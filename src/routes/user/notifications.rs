@@ -0,0 +1,108 @@
+use diesel;
+use diesel::prelude::*;
+use diesel::expression::dsl::sql;
+use diesel::types::{BigInt, Bool, Integer, Nullable, Text};
+use rustc_serialize::json;
+use rustful::{Context, Response, header, StatusCode};
+
+use ::establish_connection;
+use ::models::Notification;
+use ::notifications;
+use ::routes::require_user;
+
+/// How many of the latest notifications are listed.
+const NOTIFICATIONS_SHOWN: i64 = 100;
+
+/// Lists the user's notifications, newest first.
+pub fn get_notifications(context: Context, mut response: Response) {
+    let conn = match establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Could not establish connection to DB: {}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+    let user = match require_user(&context, &conn, &mut response) {
+        Some(u) => u,
+        None => return,
+    };
+
+    let listed: Vec<Notification> = match sql::<(Integer, Nullable<Integer>, Text, BigInt, Bool)>(&format!(
+        "SELECT id, server_id, message, extract(epoch FROM created_at)::bigint, delivered_at IS NOT NULL
+         FROM notifications
+         WHERE user_id = {}
+         ORDER BY created_at DESC, id DESC
+         LIMIT {}", user.id, NOTIFICATIONS_SHOWN)).load(&conn) {
+        Ok(n) => n,
+        Err(e) => {
+            error!("Could not execute query in get_notifications: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    let encoded = match json::encode(&listed) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Could not encode notifications as json: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.send(format!("{{\"results\": {}, \"size\": {}}}", encoded, listed.len()));
+}
+
+/// Sets where the user's notifications are posted, as `{"contact": "https://..."}`.
+/// A null contact stops posting them.
+pub fn set_contact(mut context: Context, mut response: Response) {
+    use ::schema::users::dsl::*;
+
+    let conn = match establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Could not establish connection to DB: {}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+    let user = match require_user(&context, &conn, &mut response) {
+        Some(u) => u,
+        None => return,
+    };
+    let body = match context.body.read_json_body() {
+        Ok(b) => b,
+        Err(e) => {
+            error!("Could not read set_contact json body {:?}", e);
+            response.set_status(StatusCode::BadRequest);
+            return;
+        }
+    };
+    let new_contact: Option<String> = match body.find("contact") {
+        Some(c) if c.is_null() => None,
+        Some(c) => match c.as_string().map(|c| c.trim()) {
+            Some(c) if notifications::is_valid_contact(c) => Some(c.into()),
+            _ => {
+                response.set_status(StatusCode::BadRequest);
+                response.send("\"contact must be an http(s) URL, or null!\"");
+                return;
+            }
+        },
+        None => {
+            response.set_status(StatusCode::BadRequest);
+            response.send("\"The JSON body must have a `contact`!\"");
+            return;
+        }
+    };
+
+    match diesel::update(users.filter(id.eq(user.id))).set(contact.eq(&new_contact)).execute(&conn) {
+        Ok(_) => {},
+        Err(e) => {
+            error!("Could not set the contact of user {}: {:?}", user.id, e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    }
+    response.send(format!("{{\"contact\": {}}}", json::as_json(&new_contact)));
+}
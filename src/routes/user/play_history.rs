@@ -0,0 +1,133 @@
+
+use diesel;
+use diesel::prelude::*;
+use diesel::expression::dsl::now;
+use rustful::{Context, Response, header, StatusCode};
+
+use ::establish_connection;
+use ::in_transaction;
+use ::models::NewPlayHistory;
+use ::routes::require_user;
+use super::{body_server, load_entries, send_entries};
+
+/// How many recently played servers are remembered per user.
+const HISTORY_LENGTH: usize = 50;
+
+pub fn get_play_history(context: Context, mut response: Response) {
+    let conn = match establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Could not establish connection to DB: {}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+    let user = match require_user(&context, &conn, &mut response) {
+        Some(u) => u,
+        None => return,
+    };
+
+    let entries = match load_entries(&conn, "play_history", "played_at", user.id) {
+        Ok(e) => e,
+        Err(e) => {
+            error!("Could not execute query in get_play_history: {:?}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    send_entries(&conn, entries, response);
+}
+
+/// Records that the user joined a server. Only the latest join of every server is kept.
+pub fn add_play_history(mut context: Context, mut response: Response) {
+    use ::schema::play_history::dsl::*;
+
+    let conn = match establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Could not establish connection to DB: {}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    response.headers_mut().set(header::ContentType::json());
+    let user = match require_user(&context, &conn, &mut response) {
+        Some(u) => u,
+        None => return,
+    };
+    let server = match body_server(&mut context, &conn, &mut response) {
+        Some(s) => s,
+        None => return,
+    };
+
+    let result = in_transaction(&conn, || {
+        let target = play_history.filter(user_id.eq(user.id)).filter(ip.eq(server.endpoint()));
+        let updated = try!(diesel::update(target).set((server_id.eq(Some(server.id)),
+                                                       name.eq(&server.name),
+                                                       played_at.eq(now))).execute(&conn));
+        if updated == 0 {
+            let entry = NewPlayHistory {
+                user_id: user.id,
+                server_id: Some(server.id),
+                ip: server.endpoint(),
+                name: server.name.clone(),
+            };
+            try!(diesel::insert(&entry).into(play_history).execute(&conn));
+        }
+        conn.execute(&format!(
+            "DELETE FROM play_history WHERE user_id = {0} AND id NOT IN (
+                 SELECT id FROM play_history WHERE user_id = {0} ORDER BY played_at DESC LIMIT {1})",
+            user.id, HISTORY_LENGTH))
+    });
+    if let Err(e) = result {
+        error!("Failed to record play history in add_play_history: {:?}", e);
+        response.set_status(StatusCode::InternalServerError);
+        return;
+    }
+    response.send(format!("\"server `{}` added to history!\"", server.name));
+}
+
+/// Removes an entry of the history by its own id, which is not necessarily the id of its server.
+pub fn delete_play_history(context: Context, mut response: Response) {
+    use ::schema::play_history::dsl::*;
+
+    let conn = match establish_connection() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Could not establish connection to DB: {}", e);
+            response.set_status(StatusCode::InternalServerError);
+            return;
+        }
+    };
+    let user = match require_user(&context, &conn, &mut response) {
+        Some(u) => u,
+        None => return,
+    };
+    let entry_id: i32 = match context.variables.parse("id") {
+        Ok(i) => i,
+        Err(Some(e)) => {
+            error!("The id must be an integer: {:?}", e);
+            response.set_status(StatusCode::BadRequest);
+            return;
+        },
+        Err(None) => {
+            error!("No id provided!");
+            response.set_status(StatusCode::BadRequest);
+            return;
+        }
+    };
+    let target = play_history.filter(id.eq(entry_id)).filter(user_id.eq(user.id));
+    match diesel::delete(target).execute(&conn) {
+        Ok(v) => {
+            if v != 1 {
+                error!("History entry does not exist, nothing deleted.");
+                response.set_status(StatusCode::BadRequest);
+            }
+        },
+        Err(e) => {
+            error!("Encountered an error deleting history entry id {}: {:?}", entry_id, e);
+            response.set_status(StatusCode::InternalServerError);
+        }
+    }
+}
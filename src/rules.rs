@@ -0,0 +1,101 @@
+//! Rules (cvars) of servers, such as `sv_cheats` or `tickrate`.
+//!
+//! Rules are read from game servers with A2S_RULES by the `jobs` thread, and can be set by
+//! the owner of a server. What an owner set is kept over what the server reports.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use diesel;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+
+use ::a2s;
+use ::in_transaction;
+use ::models::GameServer;
+use ::search::quote_literal;
+use ::verification;
+
+/// How long to wait on a game server to list its rules.
+const QUERY_TIMEOUT_SECS: u64 = 2;
+
+/// The rules of a server, by name.
+pub fn load(conn: &PgConnection, server: i32) -> QueryResult<Vec<(String, String)>> {
+    use ::schema::server_rules::dsl::*;
+
+    server_rules.select((name, value)).filter(server_id.eq(server)).order(name).load(conn)
+}
+
+/// Sets rules of a server on behalf of its owner. A rule without a value is removed.
+pub fn set_by_owner(conn: &PgConnection, server: i32, rules: &[(String, Option<String>)])
+                    -> QueryResult<()> {
+    use ::schema::server_rules::dsl::*;
+
+    in_transaction(conn, || {
+        for &(ref rule, ref rule_value) in rules {
+            match *rule_value {
+                Some(ref v) => {
+                    try!(conn.execute(&format!(
+                        "INSERT INTO server_rules (server_id, name, value, owner_supplied)
+                         VALUES ({}, {}, {}, true)
+                         ON CONFLICT (server_id, name) DO UPDATE SET
+                             value = EXCLUDED.value,
+                             owner_supplied = true", server, quote_literal(rule), quote_literal(v))));
+                },
+                None => {
+                    try!(diesel::delete(server_rules.filter(server_id.eq(server))
+                                                    .filter(name.eq(rule)))
+                                .execute(conn));
+                }
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Replaces the rules a server reported, leaving those its owner set alone.
+pub fn store_reported(conn: &PgConnection, server: i32, rules: &[(String, String)]) -> QueryResult<()> {
+    use ::schema::server_rules::dsl::*;
+
+    in_transaction(conn, || {
+        try!(diesel::delete(server_rules.filter(server_id.eq(server))
+                                        .filter(owner_supplied.eq(false)))
+                    .execute(conn));
+        if rules.is_empty() {
+            return Ok(());
+        }
+        let values: Vec<String> = rules.iter().map(|&(ref n, ref v)| {
+            format!("({}, {}, {})", server, quote_literal(n), quote_literal(v))
+        }).collect();
+        try!(conn.execute(&format!(
+            "INSERT INTO server_rules (server_id, name, value)
+             VALUES {}
+             ON CONFLICT (server_id, name) DO NOTHING", values.join(", "))));
+        Ok(())
+    })
+}
+
+/// Asks every server listed by address for its rules. Servers that do not answer keep
+/// the rules they last reported, and are asked less often until they answer again.
+pub fn refresh_all(conn: &PgConnection) -> QueryResult<()> {
+    use ::schema::game_servers::dsl::*;
+
+    let servers: Vec<(GameServer, SocketAddr)> = try!(game_servers.load::<GameServer>(conn))
+        .into_iter().filter_map(|s| s.socket_address().map(|a| (s, a))).collect();
+    let addresses: Vec<SocketAddr> = servers.iter().map(|&(_, a)| a).collect();
+    let timeout = Duration::from_secs(QUERY_TIMEOUT_SECS);
+    let answers = a2s::query_all("rules", &addresses, move |a| a2s::rules(a, timeout));
+    for (&(ref server, _), answer) in servers.iter().zip(answers) {
+        match answer {
+            Some(Ok(rules)) => {
+                try!(store_reported(conn, server.id, &rules));
+                if verification::rules_show_token(server, &rules) {
+                    try!(verification::mark_verified(conn, server, "rules"));
+                }
+            },
+            Some(Err(e)) => debug!("Could not fetch the rules of server {}: {}", server.id, e),
+            None => {},
+        }
+    }
+    Ok(())
+}
@@ -1 +1,58 @@
-infer_schema!(dotenv!("DATABASE_URL"));
+// `game_servers.address` is an `inet`, which `infer_schema!` can not map to a diesel type,
+// so that table is declared by hand and every other table is inferred on its own.
+table! {
+    game_servers {
+        id -> Integer,
+        name -> VarChar,
+        region -> VarChar,
+        game_type -> VarChar,
+        max_users -> Integer,
+        current_users -> Integer,
+        current_premium_users -> Nullable<Integer>,
+        max_premium_users -> Nullable<Integer>,
+        tags -> Array<VarChar>,
+        motd -> VarChar,
+        owner_id -> Nullable<Integer>,
+        current_map -> Nullable<VarChar>,
+        map_rotation -> Array<VarChar>,
+        password_protected -> Bool,
+        vac_secured -> Bool,
+        bots -> Integer,
+        dedicated -> Bool,
+        os -> Nullable<VarChar>,
+        version -> Nullable<VarChar>,
+        outdated -> Bool,
+        address -> ::inet::Inet,
+        port -> Integer,
+        hostname -> Nullable<VarChar>,
+        verified -> Bool,
+        verification_token -> VarChar,
+        status -> VarChar,
+        status_reason -> Nullable<VarChar>,
+        trust_score -> Float,
+        suspicious -> Bool,
+        ranking_score -> Float,
+        rating_average -> Nullable<Float>,
+        rating_count -> Integer,
+        heartbeat_secret -> VarChar,
+    }
+}
+
+infer_table_from_schema!(dotenv!("DATABASE_URL"), "regions");
+infer_table_from_schema!(dotenv!("DATABASE_URL"), "region_aliases");
+infer_table_from_schema!(dotenv!("DATABASE_URL"), "player_counts");
+infer_table_from_schema!(dotenv!("DATABASE_URL"), "player_count_rollups");
+infer_table_from_schema!(dotenv!("DATABASE_URL"), "users");
+infer_table_from_schema!(dotenv!("DATABASE_URL"), "favorites");
+infer_table_from_schema!(dotenv!("DATABASE_URL"), "play_history");
+infer_table_from_schema!(dotenv!("DATABASE_URL"), "audit_log");
+infer_table_from_schema!(dotenv!("DATABASE_URL"), "rcon_credentials");
+infer_table_from_schema!(dotenv!("DATABASE_URL"), "player_rosters");
+infer_table_from_schema!(dotenv!("DATABASE_URL"), "server_players");
+infer_table_from_schema!(dotenv!("DATABASE_URL"), "server_rules");
+infer_table_from_schema!(dotenv!("DATABASE_URL"), "notifications");
+infer_table_from_schema!(dotenv!("DATABASE_URL"), "reports");
+infer_table_from_schema!(dotenv!("DATABASE_URL"), "player_count_checks");
+infer_table_from_schema!(dotenv!("DATABASE_URL"), "reviews");
+// `blocked_networks` and `blocklist_hits` have `cidr` and `inet` columns too, and are only
+// queried with SQL in `blocklist`.
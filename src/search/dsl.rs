@@ -0,0 +1,356 @@
+//! A small query language for searching servers from a single text box, such as
+//! `region:naeast tag:128tick -tag:surf players>10 name:"dust"`.
+//!
+//! A query is a whitespace separated list of `key:value` filters. String filters (`region`,
+//! `game_type`, `tag`, `name` and `map`, where `*` is a wildcard as in `map:de_*`) can be
+//! negated with a leading `-`. Number filters (`players`, `slots` and `bots`) also take `=`,
+//! `<`, `<=`, `>` and `>=`. Flags (`password`, `vac`, `dedicated`, `outdated` and `verified`)
+//! are `true` or `false`, and `os` and `version` match exactly. `rating>=4` only finds servers
+//! rated 4 or better on average. `rule` filters on the rules (cvars) of servers, as in
+//! `rule:sv_cheats=0` or `-rule:sourcemod_version`, see `RuleFilter`. Values
+//! containing spaces are quoted, and `\"` or `\\` escape a quote or backslash inside quotes.
+//! Words that are not filters, like `dust2` or `"24/7 only"`, are searched for in server names,
+//! tags and MOTDs.
+
+use std::fmt;
+
+use super::{Comparison, RuleFilter, SearchFilters};
+use ::reviews::{MIN_RATING, MAX_RATING};
+
+const KEYS: &'static str = "region, game_type, tag, name, map, rule, players, slots, bots, \
+                             password, vac, dedicated, outdated, verified, os, version, rating";
+
+/// What went wrong in a query, and where. `position` counts characters, starting at 1.
+#[derive(Debug, Clone, PartialEq, RustcEncodable)]
+pub struct ParseError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "at character {}: {}", self.position, self.message)
+    }
+}
+
+/// Parses a query into the filters it describes.
+pub fn parse(query: &str) -> Result<SearchFilters, ParseError> {
+    let mut parser = Parser { chars: query.chars().collect(), pos: 0 };
+    let mut filters = SearchFilters::default();
+    loop {
+        parser.skip_whitespace();
+        if parser.at_end() {
+            return Ok(filters);
+        }
+        try!(parser.term(&mut filters));
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn at_end(&self) -> bool {
+        self.pos >= self.chars.len()
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn error<T, S: Into<String>>(&self, position: usize, message: S) -> Result<T, ParseError> {
+        Err(ParseError { position: position + 1, message: message.into() })
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().map_or(false, |c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    /// Parses a single `[-]key<op>value` filter, or a piece of free text, into `filters`.
+    fn term(&mut self, filters: &mut SearchFilters) -> Result<(), ParseError> {
+        let start = self.pos;
+        let negated = self.peek() == Some('-');
+        if negated {
+            self.pos += 1;
+        }
+
+        let key_start = self.pos;
+        while self.peek().map_or(false, |c| c.is_alphanumeric() || c == '_') {
+            self.pos += 1;
+        }
+        let key = self.chars[key_start..self.pos].iter().collect::<String>().to_lowercase();
+
+        // Anything that is not a `key<op>value` filter is free text.
+        let is_filter = !key.is_empty() && self.peek().map_or(false, |c| {
+            c == ':' || c == '=' || c == '<' || c == '>'
+        });
+        if !is_filter {
+            if negated {
+                return self.error(start, "free text can not be negated, \
+                                          use a filter such as `-name:value`");
+            }
+            self.pos = key_start;
+            let words = try!(self.value());
+            filters.add_text(&words);
+            return Ok(());
+        }
+
+        let op_start = self.pos;
+        let cmp = match (self.peek(), self.chars.get(self.pos + 1).cloned()) {
+            (Some(':'), _) | (Some('='), _) => { self.pos += 1; Comparison::Equal },
+            (Some('<'), Some('=')) => { self.pos += 2; Comparison::LessOrEqual },
+            (Some('>'), Some('=')) => { self.pos += 2; Comparison::GreaterOrEqual },
+            (Some('<'), _) => { self.pos += 1; Comparison::Less },
+            (Some('>'), _) => { self.pos += 1; Comparison::Greater },
+            _ => unreachable!("checked to be a filter above"),
+        };
+
+        let value_start = self.pos;
+        let value = try!(self.value());
+        if value.is_empty() {
+            return self.error(value_start, format!("expected a value for `{}`", key));
+        }
+
+        match &key[..] {
+            "region" | "game_type" | "tag" | "name" | "map" => {
+                if cmp != Comparison::Equal {
+                    return self.error(op_start, format!("`{}` can only be matched with `:`", key));
+                }
+                let list = match (&key[..], negated) {
+                    ("region", false) => &mut filters.regions,
+                    ("region", true) => &mut filters.exclude_regions,
+                    ("game_type", false) => &mut filters.game_types,
+                    ("game_type", true) => &mut filters.exclude_game_types,
+                    ("tag", false) => &mut filters.tags,
+                    ("tag", true) => &mut filters.exclude_tags,
+                    ("name", false) => &mut filters.names,
+                    ("name", true) => &mut filters.exclude_names,
+                    ("map", false) => &mut filters.maps,
+                    _ => &mut filters.exclude_maps,
+                };
+                list.push(value);
+            },
+            "rule" => {
+                if cmp != Comparison::Equal {
+                    return self.error(op_start,
+                                      "`rule` is matched with `:`, as in `rule:tickrate>=128`");
+                }
+                let rule = match RuleFilter::parse(&value) {
+                    Ok(r) => r,
+                    Err(msg) => return self.error(value_start, msg),
+                };
+                if negated {
+                    filters.exclude_rules.push(rule);
+                } else {
+                    filters.rules.push(rule);
+                }
+            },
+            "password" | "vac" | "dedicated" | "outdated" | "verified" => {
+                if cmp != Comparison::Equal || negated {
+                    return self.error(start, format!("`{}` can only be matched with `:true` or \
+                                                      `:false`", key));
+                }
+                let flag = match &value.to_lowercase()[..] {
+                    "true" | "yes" | "1" => true,
+                    "false" | "no" | "0" => false,
+                    _ => {
+                        return self.error(value_start,
+                                          format!("`{}` is not `true` or `false`", value));
+                    },
+                };
+                match &key[..] {
+                    "password" => filters.password_protected = Some(flag),
+                    "vac" => filters.vac_secured = Some(flag),
+                    "dedicated" => filters.dedicated = Some(flag),
+                    "outdated" => filters.outdated = Some(flag),
+                    _ => filters.verified = Some(flag),
+                }
+            },
+            "os" | "version" => {
+                if cmp != Comparison::Equal || negated {
+                    return self.error(start, format!("`{}` can only be matched with `:`", key));
+                }
+                if key == "os" {
+                    filters.operating_systems.push(value.to_lowercase());
+                } else {
+                    filters.versions.push(value);
+                }
+            },
+            "players" | "slots" | "bots" => {
+                if negated {
+                    return self.error(start, format!("`{}` can not be negated, compare the other \
+                                                      way instead", key));
+                }
+                let n: i32 = match value.parse() {
+                    Ok(n) => n,
+                    Err(_) => {
+                        return self.error(value_start,
+                                          format!("`{}` is not a whole number", value));
+                    },
+                };
+                match &key[..] {
+                    "players" => filters.players.push((cmp, n)),
+                    "slots" => filters.slots.push((cmp, n)),
+                    _ => filters.bots.push((cmp, n)),
+                }
+            },
+            "rating" => {
+                if cmp != Comparison::GreaterOrEqual || negated {
+                    return self.error(start,
+                                      "`rating` can only be matched with `>=`, as in `rating>=4`");
+                }
+                match value.parse::<f32>() {
+                    Ok(n) if n >= MIN_RATING as f32 && n <= MAX_RATING as f32 => {
+                        filters.min_rating = Some(n);
+                    },
+                    _ => {
+                        return self.error(value_start, format!("`{}` is not a rating from {} to {}",
+                                                               value, MIN_RATING, MAX_RATING));
+                    },
+                }
+            },
+            _ => {
+                return self.error(key_start,
+                                  format!("unknown filter `{}`, expected one of {}", key, KEYS));
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses a quoted value, or a bare one running up to the next whitespace.
+    fn value(&mut self) -> Result<String, ParseError> {
+        let mut value = String::new();
+        if self.peek() != Some('"') {
+            while let Some(c) = self.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                value.push(c);
+                self.pos += 1;
+            }
+            return Ok(value);
+        }
+
+        let quote_start = self.pos;
+        self.pos += 1;
+        loop {
+            match self.peek() {
+                None => return self.error(quote_start, "this quote is never closed"),
+                Some('"') => {
+                    self.pos += 1;
+                    break;
+                },
+                Some('\\') => {
+                    match self.chars.get(self.pos + 1).cloned() {
+                        Some(c) if c == '"' || c == '\\' => value.push(c),
+                        _ => return self.error(self.pos, "only `\\\"` and `\\\\` can be escaped"),
+                    }
+                    self.pos += 2;
+                },
+                Some(c) => {
+                    value.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+        if self.peek().map_or(false, |c| !c.is_whitespace()) {
+            return self.error(self.pos, "expected whitespace after a quoted value");
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+    use ::search::{Comparison, RuleFilter};
+
+    /// The position and message of the error a query fails with.
+    fn error(query: &str) -> (usize, String) {
+        let e = parse(query).unwrap_err();
+        (e.position, e.message)
+    }
+
+    #[test]
+    fn string_filters_and_negation() {
+        let f = parse("region:naeast -region:nawest tag:128tick -tag:surf map:de_* -name:x")
+                    .unwrap();
+        assert_eq!(f.regions, vec!["naeast"]);
+        assert_eq!(f.exclude_regions, vec!["nawest"]);
+        assert_eq!(f.tags, vec!["128tick"]);
+        assert_eq!(f.exclude_tags, vec!["surf"]);
+        assert_eq!(f.maps, vec!["de_*"]);
+        assert_eq!(f.exclude_names, vec!["x"]);
+        assert_eq!(f.text, None);
+    }
+
+    #[test]
+    fn keys_are_case_insensitive() {
+        assert_eq!(parse("Region:naeast").unwrap().regions, vec!["naeast"]);
+    }
+
+    #[test]
+    fn comparisons() {
+        let f = parse("players>10 players<=20 slots=32 bots<1 bots>=0 rating>=4.5").unwrap();
+        assert_eq!(f.players, vec![(Comparison::Greater, 10), (Comparison::LessOrEqual, 20)]);
+        assert_eq!(f.slots, vec![(Comparison::Equal, 32)]);
+        assert_eq!(f.bots, vec![(Comparison::Less, 1), (Comparison::GreaterOrEqual, 0)]);
+        assert_eq!(f.min_rating, Some(4.5));
+    }
+
+    #[test]
+    fn flags_and_exact_matches() {
+        let f = parse("password:false vac:yes dedicated:1 os:Linux version:1.2.3").unwrap();
+        assert_eq!(f.password_protected, Some(false));
+        assert_eq!(f.vac_secured, Some(true));
+        assert_eq!(f.dedicated, Some(true));
+        assert_eq!(f.operating_systems, vec!["linux"]);
+        assert_eq!(f.versions, vec!["1.2.3"]);
+    }
+
+    #[test]
+    fn rules() {
+        let f = parse("rule:tickrate>=128 -rule:sourcemod_version").unwrap();
+        assert_eq!(f.rules, vec![RuleFilter::parse("tickrate>=128").unwrap()]);
+        assert_eq!(f.exclude_rules, vec![RuleFilter::parse("sourcemod_version").unwrap()]);
+    }
+
+    #[test]
+    fn quoting_and_escaping() {
+        let f = parse(r#"name:"dust 2" tag:"say \"hi\"" map:"c:\\maps""#).unwrap();
+        assert_eq!(f.names, vec!["dust 2"]);
+        assert_eq!(f.tags, vec![r#"say "hi""#]);
+        assert_eq!(f.maps, vec![r"c:\maps"]);
+    }
+
+    #[test]
+    fn free_text() {
+        let f = parse(r#"dust2 region:naeast "24/7 only""#).unwrap();
+        assert_eq!(f.text, Some("dust2 24/7 only".to_string()));
+        assert_eq!(f.regions, vec!["naeast"]);
+        assert_eq!(parse("   ").unwrap().text, None);
+    }
+
+    #[test]
+    fn error_positions() {
+        assert_eq!(error("region:naeast colour:red").0, 15);
+        assert_eq!(error("tag:").0, 5);
+        assert_eq!(error("players>lots").0, 9);
+        assert_eq!(error("region>naeast").0, 7);
+        assert_eq!(error("-password:true").0, 1);
+        assert_eq!(error("rating>4").0, 1);
+        assert_eq!(error("rating>=9").0, 9);
+    }
+
+    #[test]
+    fn errors_in_quotes() {
+        assert_eq!(error(r#"name:"dust"#), (6, "this quote is never closed".to_string()));
+        assert_eq!(error(r#"name:"a\b""#).0, 8);
+        assert_eq!(error(r#"name:"dust"2"#).0, 12);
+        assert_eq!(error("-dust2").0, 1);
+    }
+}
@@ -0,0 +1,470 @@
+//! Server search filters, and the boxed diesel query they compile into.
+//!
+//! Filters can come from the JSON body of `POST /server/search`, or from the query string
+//! language parsed by `dsl`. Either way they end up in `SearchFilters`, and `load` runs them.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use diesel::expression::AsExpression;
+use diesel::expression::dsl::{all, any, sql};
+use diesel::types::{Float, Integer, VarChar};
+use rustc_serialize::json::Json;
+
+use ::models::GameServer;
+use ::moderation;
+use ::reviews::{MIN_RATING, MAX_RATING};
+use ::trust;
+
+pub mod dsl;
+
+// Lowercases text in SQL, for case-insensitive matches on indexed columns.
+sql_function!(lower, lower_t, (x: ::diesel::types::VarChar) -> ::diesel::types::VarChar);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Equal,
+    GreaterOrEqual,
+    Greater,
+}
+
+impl Comparison {
+    pub fn as_sql(&self) -> &'static str {
+        match *self {
+            Comparison::Less => "<",
+            Comparison::LessOrEqual => "<=",
+            Comparison::Equal => "=",
+            Comparison::GreaterOrEqual => ">=",
+            Comparison::Greater => ">",
+        }
+    }
+}
+
+/// A filter on a rule (cvar) of servers, such as `tickrate>=128`. Without a test, servers
+/// only need to have the rule, so `sourcemod_version` finds servers running SourceMod.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleFilter {
+    pub name: String,
+    pub test: Option<(Comparison, String)>,
+}
+
+impl RuleFilter {
+    /// Parses `name`, or `name` followed by `=`, `<`, `<=`, `>` or `>=` and a value.
+    /// Values are compared as numbers when they are one, and only `=` works on other values.
+    pub fn parse(s: &str) -> Result<RuleFilter, String> {
+        let op_start = s.find(|c: char| c == '=' || c == '<' || c == '>').unwrap_or(s.len());
+        let name = s[..op_start].trim();
+        if name.is_empty() {
+            return Err(format!("rule filter `{}` has no rule name", s));
+        }
+        if op_start == s.len() {
+            return Ok(RuleFilter { name: name.into(), test: None });
+        }
+        let rest = &s[op_start..];
+        let (cmp, op_len) = if rest.starts_with("<=") {
+            (Comparison::LessOrEqual, 2)
+        } else if rest.starts_with(">=") {
+            (Comparison::GreaterOrEqual, 2)
+        } else if rest.starts_with('<') {
+            (Comparison::Less, 1)
+        } else if rest.starts_with('>') {
+            (Comparison::Greater, 1)
+        } else {
+            (Comparison::Equal, 1)
+        };
+        let value = rest[op_len..].trim();
+        if cmp != Comparison::Equal && !value.parse::<f64>().map(|n| n.is_finite()).unwrap_or(false) {
+            return Err(format!("rule `{}` can only be compared with a number, not `{}`", name, value));
+        }
+        Ok(RuleFilter { name: name.into(), test: Some((cmp, value.into())) })
+    }
+
+    /// Whether the value of a rule with the filter's name matches.
+    fn matches(&self, value: &str) -> bool {
+        let (cmp, expected) = match self.test {
+            None => return true,
+            Some((cmp, ref v)) => (cmp, v),
+        };
+        match expected.parse::<f64>() {
+            Ok(e) if e.is_finite() => match rule_number(value) {
+                Some(v) => match cmp {
+                    Comparison::Less => v < e,
+                    Comparison::LessOrEqual => v <= e,
+                    Comparison::Equal => v == e,
+                    Comparison::GreaterOrEqual => v >= e,
+                    Comparison::Greater => v > e,
+                },
+                None => false,
+            },
+            _ => value == expected,
+        }
+    }
+}
+
+/// A rule value as a number, when it is written as one, such as `128`, ` 1.5` or `-1.`.
+fn rule_number(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let unsigned = if value.starts_with('-') { &value[1..] } else { value };
+    let mut parts = unsigned.splitn(2, '.');
+    let whole = parts.next().unwrap_or("");
+    let fraction = parts.next().unwrap_or("");
+    let digits = |s: &str| s.chars().all(|c| c.is_digit(10));
+    if whole.is_empty() || !digits(whole) || !digits(fraction) {
+        return None;
+    }
+    value.parse().ok()
+}
+
+/// The servers with a rule matching a filter.
+fn servers_with_rule(conn: &PgConnection, rule: &RuleFilter) -> QueryResult<Vec<i32>> {
+    use ::schema::server_rules::dsl::*;
+
+    let rules = try!(server_rules.select((server_id, value))
+                                 .filter(lower(name).eq(rule.name.to_lowercase()))
+                                 .load::<(i32, String)>(conn));
+    Ok(rules.into_iter().filter(|&(_, ref v)| rule.matches(v)).map(|(s, _)| s).collect())
+}
+
+/// Every filter of a search. Lists of values to include match servers with any of the values,
+/// except for `tags`, where a server needs all of them.
+#[derive(Debug, Clone, Default)]
+pub struct SearchFilters {
+    pub regions: Vec<String>,
+    pub exclude_regions: Vec<String>,
+    pub game_types: Vec<String>,
+    pub exclude_game_types: Vec<String>,
+    pub tags: Vec<String>,
+    pub exclude_tags: Vec<String>,
+    /// Case-insensitive substrings of the server name.
+    pub names: Vec<String>,
+    pub exclude_names: Vec<String>,
+    /// Maps being played, where `*` matches anything, as in `de_*`.
+    pub maps: Vec<String>,
+    pub exclude_maps: Vec<String>,
+    /// Comparisons against `current_users`.
+    pub players: Vec<(Comparison, i32)>,
+    /// Comparisons against `max_users`.
+    pub slots: Vec<(Comparison, i32)>,
+    /// Only servers with a free slot.
+    pub not_full: bool,
+    /// Comparisons against `bots`.
+    pub bots: Vec<(Comparison, i32)>,
+    pub password_protected: Option<bool>,
+    pub vac_secured: Option<bool>,
+    pub dedicated: Option<bool>,
+    pub outdated: Option<bool>,
+    /// Whether listings must be verified (or unverified), see `verification`.
+    pub verified: Option<bool>,
+    /// The lowest average rating servers can have. Servers without ratings are left out.
+    pub min_rating: Option<f32>,
+    pub operating_systems: Vec<String>,
+    pub versions: Vec<String>,
+    /// Servers need a rule matching every one of these, and none matching any `exclude_rules`.
+    pub rules: Vec<RuleFilter>,
+    pub exclude_rules: Vec<RuleFilter>,
+    /// Free text, matched against names, tags and MOTDs, see `relevance`.
+    pub text: Option<String>,
+}
+
+/// The servers found by a search. When searching for text, `scores` holds the relevance of
+/// every server by id, and the servers are ordered from most to least relevant.
+#[derive(Debug, Clone)]
+pub struct SearchResults {
+    pub servers: Vec<GameServer>,
+    pub scores: HashMap<i32, f32>,
+}
+
+impl SearchFilters {
+    /// Reads the filters of a `POST /server/search` JSON body.
+    pub fn from_json(body: &Json) -> Result<SearchFilters, String> {
+        let mut filters = SearchFilters::default();
+        if let Some(r) = body.find("region") {
+            match r.as_string() {
+                Some(s) => filters.regions.push(s.into()),
+                None => return Err("region must be a string.".into()),
+            }
+        }
+        if let Some(g) = body.find("game_type") {
+            match g.as_string() {
+                Some(s) => filters.game_types.push(s.into()),
+                None => return Err("game_type must be a string.".into()),
+            }
+        }
+        if let Some(m) = body.find("map") {
+            match m.as_string() {
+                Some(s) => filters.maps.push(s.into()),
+                None => return Err("map must be a string.".into()),
+            }
+        }
+        filters.password_protected = try!(read_flag(body, "password_protected"));
+        filters.vac_secured = try!(read_flag(body, "vac_secured"));
+        filters.dedicated = try!(read_flag(body, "dedicated"));
+        filters.outdated = try!(read_flag(body, "outdated"));
+        filters.verified = try!(read_flag(body, "verified"));
+        if let Some(r) = body.find("min_rating") {
+            match r.as_f64() {
+                Some(n) if n >= MIN_RATING as f64 && n <= MAX_RATING as f64 => {
+                    filters.min_rating = Some(n as f32);
+                },
+                _ => return Err(format!("min_rating must be a number from {} to {}.", MIN_RATING, MAX_RATING)),
+            }
+        }
+        if let Some(o) = body.find("os") {
+            match o.as_string() {
+                Some(s) => filters.operating_systems.push(s.to_lowercase()),
+                None => return Err("os must be a string.".into()),
+            }
+        }
+        if let Some(r) = body.find("rules") {
+            let list = match r.as_array() {
+                Some(l) => l,
+                None => return Err("rules must be an array of strings such as `tickrate>=128`.".into()),
+            };
+            for rule in list {
+                match rule.as_string() {
+                    Some(s) => filters.rules.push(try!(RuleFilter::parse(s))),
+                    None => return Err("rules must be an array of strings such as `tickrate>=128`.".into()),
+                }
+            }
+        }
+        if let Some(t) = body.find("text") {
+            match t.as_string() {
+                Some(s) => filters.add_text(s),
+                None => return Err("text must be a string.".into()),
+            }
+        }
+        Ok(filters)
+    }
+
+    /// Adds words to the free text searched for.
+    pub fn add_text(&mut self, words: &str) {
+        let words = words.trim();
+        if words.is_empty() {
+            return;
+        }
+        self.text = Some(match self.text.take() {
+            Some(t) => format!("{} {}", t, words),
+            None => words.into(),
+        });
+    }
+
+    /// Whether the search is limited to certain regions.
+    pub fn has_region(&self) -> bool {
+        !self.regions.is_empty()
+    }
+}
+
+/// Reads an optional boolean filter of a JSON body.
+fn read_flag(body: &Json, key: &str) -> Result<Option<bool>, String> {
+    match body.find(key) {
+        None => Ok(None),
+        Some(f) => match f.as_boolean() {
+            Some(b) => Ok(Some(b)),
+            None => Err(format!("{} must be a boolean.", key)),
+        },
+    }
+}
+
+/// Quotes a string as a Postgres literal, for the few filters diesel can not express itself.
+pub fn quote_literal(s: &str) -> String {
+    format!("'{}'", s.replace('\0', "").replace('\'', "''"))
+}
+
+/// Escapes the wildcards of a string used in a LIKE pattern.
+pub fn escape_like(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+/// A LIKE pattern of a map filter, matching maps case-insensitively with `*` as a wildcard.
+/// Maps are stored lowercased, see `server_info::normalize_map`.
+fn map_pattern(map: &str) -> String {
+    escape_like(&map.to_lowercase()).replace('*', "%")
+}
+
+/// A LIKE pattern matching names that contain `text`, compared with `lower(name)`.
+fn name_pattern(text: &str) -> String {
+    format!("%{}%", escape_like(&text.to_lowercase()))
+}
+
+/// Values for comparing a nullable column against any of them.
+fn nullable(values: &[String]) -> Vec<Option<String>> {
+    values.iter().map(|v| Some(v.clone())).collect()
+}
+
+/// A condition on `game_servers` which holds for the servers at any of the addresses.
+pub fn endpoints_sql(endpoints: &[SocketAddr]) -> String {
+    if endpoints.is_empty() {
+        return "false".into();
+    }
+    let rows: Vec<String> = endpoints.iter().map(|e| {
+        format!("({}::inet, {})", quote_literal(&e.ip().to_string()), e.port())
+    }).collect();
+    format!("(address, port) IN ({})", rows.join(", "))
+}
+
+macro_rules! compare {
+    ($query:expr, $column:expr, $cmp:expr, $value:expr) => {
+        match $cmp {
+            Comparison::Less => $query.filter($column.lt($value)),
+            Comparison::LessOrEqual => $query.filter($column.le($value)),
+            Comparison::Equal => $query.filter($column.eq($value)),
+            Comparison::GreaterOrEqual => $query.filter($column.ge($value)),
+            Comparison::Greater => $query.filter($column.gt($value)),
+        }
+    }
+}
+
+/// Scores how well every server matches some free text, combining full-text search over the
+/// name, tags and MOTD with trigram similarity of the name, so that typos still match.
+/// Servers matching neither way are left out.
+pub fn relevance(conn: &PgConnection, text: &str) -> QueryResult<HashMap<i32, f32>> {
+    let text = quote_literal(text);
+    let scores = try!(sql::<(Integer, Float)>(&format!(
+        "SELECT id, (ts_rank(fula_search_document(name, tags, motd), plainto_tsquery('simple', {0}))
+                     + similarity(name, {0}))::real
+         FROM game_servers
+         WHERE fula_search_document(name, tags, motd) @@ plainto_tsquery('simple', {0})
+            OR name % {0}", text)).load::<(i32, f32)>(conn));
+    Ok(scores.into_iter().collect())
+}
+
+/// Loads every server matching the filters, best ranked first, see `ranking`. Servers
+/// suspected of faking their player counts come last when `trust::demote_suspicious`.
+pub fn load(conn: &PgConnection, filters: &SearchFilters) -> QueryResult<SearchResults> {
+    use ::schema::game_servers::dsl::*;
+
+    let scores = match filters.text {
+        Some(ref t) => try!(relevance(conn, t)),
+        None => HashMap::new(),
+    };
+
+    // Searches for text are sorted by relevance below instead.
+    let mut query = game_servers.filter(status.eq(moderation::APPROVED))
+                                .order(ranking_score.desc())
+                                .into_boxed();
+    if filters.text.is_some() {
+        query = query.filter(id.eq(any(scores.keys().cloned().collect::<Vec<i32>>())));
+    }
+    if !filters.regions.is_empty() {
+        query = query.filter(region.eq(any(filters.regions.clone())));
+    }
+    for r in &filters.exclude_regions {
+        query = query.filter(region.ne(r.clone()));
+    }
+    if !filters.game_types.is_empty() {
+        query = query.filter(game_type.eq(any(filters.game_types.clone())));
+    }
+    for g in &filters.exclude_game_types {
+        query = query.filter(game_type.ne(g.clone()));
+    }
+    for t in &filters.tags {
+        query = query.filter(AsExpression::<VarChar>::as_expression(t.clone()).eq(any(tags)));
+    }
+    for t in &filters.exclude_tags {
+        query = query.filter(AsExpression::<VarChar>::as_expression(t.clone()).ne(all(tags)));
+    }
+    for n in &filters.names {
+        query = query.filter(lower(name).like(name_pattern(n)));
+    }
+    for n in &filters.exclude_names {
+        query = query.filter(lower(name).not_like(name_pattern(n)));
+    }
+    if !filters.maps.is_empty() {
+        let patterns: Vec<String> = filters.maps.iter().map(|m| map_pattern(m)).collect();
+        query = query.filter(current_map.like(any(nullable(&patterns))));
+    }
+    for m in &filters.exclude_maps {
+        query = query.filter(current_map.is_null().or(current_map.not_like(Some(map_pattern(m)))));
+    }
+    for &(cmp, n) in &filters.players {
+        query = compare!(query, current_users, cmp, n);
+    }
+    for &(cmp, n) in &filters.slots {
+        query = compare!(query, max_users, cmp, n);
+    }
+    if filters.not_full {
+        query = query.filter(current_users.lt(max_users));
+    }
+    for &(cmp, n) in &filters.bots {
+        query = compare!(query, bots, cmp, n);
+    }
+    if let Some(b) = filters.password_protected {
+        query = query.filter(password_protected.eq(b));
+    }
+    if let Some(b) = filters.vac_secured {
+        query = query.filter(vac_secured.eq(b));
+    }
+    if let Some(b) = filters.dedicated {
+        query = query.filter(dedicated.eq(b));
+    }
+    if let Some(b) = filters.outdated {
+        query = query.filter(outdated.eq(b));
+    }
+    if let Some(b) = filters.verified {
+        query = query.filter(verified.eq(b));
+    }
+    if let Some(r) = filters.min_rating {
+        query = query.filter(rating_average.ge(Some(r)));
+    }
+    if !filters.operating_systems.is_empty() {
+        query = query.filter(os.eq(any(nullable(&filters.operating_systems))));
+    }
+    if !filters.versions.is_empty() {
+        query = query.filter(version.eq(any(nullable(&filters.versions))));
+    }
+    for r in &filters.rules {
+        query = query.filter(id.eq(any(try!(servers_with_rule(conn, r)))));
+    }
+    for r in &filters.exclude_rules {
+        query = query.filter(id.ne(all(try!(servers_with_rule(conn, r)))));
+    }
+
+    let mut servers = try!(query.load::<GameServer>(conn));
+    if filters.text.is_some() {
+        servers.sort_by(|a, b| {
+            let (a, b) = (scores[&a.id], scores[&b.id]);
+            b.partial_cmp(&a).unwrap_or(Ordering::Equal)
+        });
+    }
+    // The sort is stable, so suspicious servers keep their order among themselves.
+    if trust::demote_suspicious() {
+        servers.sort_by_key(|s| s.suspicious);
+    }
+    Ok(SearchResults { servers: servers, scores: scores })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rule_number, RuleFilter};
+
+    fn matches(filter: &str, value: &str) -> bool {
+        RuleFilter::parse(filter).unwrap().matches(value)
+    }
+
+    #[test]
+    fn rule_values_as_numbers() {
+        assert_eq!(rule_number("128"), Some(128.0));
+        assert_eq!(rule_number(" -1.5 "), Some(-1.5));
+        assert_eq!(rule_number("1."), Some(1.0));
+        assert_eq!(rule_number(".5"), None);
+        assert_eq!(rule_number("1e3"), None);
+        assert_eq!(rule_number("-"), None);
+        assert_eq!(rule_number("fast"), None);
+    }
+
+    #[test]
+    fn rule_filters() {
+        assert!(matches("sourcemod_version", "1.8.0"));
+        assert!(matches("tickrate>=128", "128"));
+        assert!(!matches("tickrate>=128", "64"));
+        assert!(!matches("tickrate>=128", "fast"));
+        assert!(matches("sv_cheats=0", "0.0"));
+        assert!(matches("hostname=Dust", "Dust"));
+        assert!(!matches("hostname=Dust", "dust"));
+    }
+}
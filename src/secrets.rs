@@ -0,0 +1,85 @@
+//! Encryption of secrets stored in the database, such as RCON passwords.
+//!
+//! Secrets are sealed with AES-256-GCM under the key in `RCON_SECRET_KEY` (64 hex digits).
+//! A sealed secret is the 12 byte nonce, then the 16 byte tag, then the ciphertext. The
+//! caller's associated data ties a secret to what it belongs to, so that it can not be
+//! copied over to another row.
+
+use std::env;
+use std::fmt;
+
+use crypto::aead::{AeadDecryptor, AeadEncryptor};
+use crypto::aes::KeySize;
+use crypto::aes_gcm::AesGcm;
+use rand::{OsRng, Rng};
+use rustc_serialize::hex::FromHex;
+
+const KEY_LENGTH: usize = 32;
+const NONCE_LENGTH: usize = 12;
+const TAG_LENGTH: usize = 16;
+
+#[derive(Debug)]
+pub enum SecretError {
+    /// `RCON_SECRET_KEY` is unset, or not 64 hex digits.
+    NoKey,
+    /// The OS random number generator could not be used.
+    Random,
+    /// The sealed secret was tampered with, or sealed with another key.
+    Corrupted,
+}
+
+impl fmt::Display for SecretError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SecretError::NoKey => write!(f, "RCON_SECRET_KEY must be set to 64 hex digits"),
+            SecretError::Random => write!(f, "could not generate a nonce"),
+            SecretError::Corrupted => write!(f, "the secret could not be decrypted"),
+        }
+    }
+}
+
+fn key() -> Result<Vec<u8>, SecretError> {
+    match env::var("RCON_SECRET_KEY").ok().and_then(|k| k.from_hex().ok()) {
+        Some(ref k) if k.len() == KEY_LENGTH => Ok(k.clone()),
+        _ => Err(SecretError::NoKey),
+    }
+}
+
+/// Encrypts a secret.
+pub fn seal(secret: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, SecretError> {
+    let key = try!(key());
+    let mut nonce = [0u8; NONCE_LENGTH];
+    match OsRng::new() {
+        Ok(mut rng) => rng.fill_bytes(&mut nonce),
+        Err(_) => return Err(SecretError::Random),
+    }
+
+    let mut tag = [0u8; TAG_LENGTH];
+    let mut ciphertext = vec![0u8; secret.len()];
+    AesGcm::new(KeySize::KeySize256, &key, &nonce, associated_data)
+           .encrypt(secret, &mut ciphertext, &mut tag);
+
+    let mut sealed = Vec::with_capacity(NONCE_LENGTH + TAG_LENGTH + secret.len());
+    sealed.extend_from_slice(&nonce);
+    sealed.extend_from_slice(&tag);
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
+}
+
+/// Decrypts a secret sealed with the same associated data.
+pub fn open(sealed: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, SecretError> {
+    let key = try!(key());
+    if sealed.len() < NONCE_LENGTH + TAG_LENGTH {
+        return Err(SecretError::Corrupted);
+    }
+    let (nonce, rest) = sealed.split_at(NONCE_LENGTH);
+    let (tag, ciphertext) = rest.split_at(TAG_LENGTH);
+
+    let mut secret = vec![0u8; ciphertext.len()];
+    if AesGcm::new(KeySize::KeySize256, &key, nonce, associated_data)
+              .decrypt(ciphertext, &mut secret, tag) {
+        Ok(secret)
+    } else {
+        Err(SecretError::Corrupted)
+    }
+}
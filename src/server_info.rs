@@ -0,0 +1,110 @@
+//! Keeps what servers report about themselves with A2S_INFO, such as their current map and
+//! version, up to date. Run periodically by the `jobs` thread.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use diesel;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+
+use ::a2s;
+use ::models::GameServer;
+use ::search::quote_literal;
+use ::trust;
+use ::verification;
+
+/// How long to wait on a game server to describe itself.
+const QUERY_TIMEOUT_SECS: u64 = 2;
+
+/// Asks every server listed by address about itself. Servers that do not answer are
+/// left as they were, and are asked less often until they answer again.
+pub fn refresh_all(conn: &PgConnection) -> QueryResult<()> {
+    use ::schema::game_servers::dsl::*;
+
+    let servers: Vec<(GameServer, SocketAddr)> = try!(game_servers.load::<GameServer>(conn))
+        .into_iter().filter_map(|s| s.socket_address().map(|a| (s, a))).collect();
+    let addresses: Vec<SocketAddr> = servers.iter().map(|&(_, a)| a).collect();
+    let timeout = Duration::from_secs(QUERY_TIMEOUT_SECS);
+    let answers = a2s::query_all("info", &addresses, move |a| a2s::info(a, timeout));
+    for (&(ref server, _), answer) in servers.iter().zip(answers) {
+        let info = match answer {
+            Some(Ok(i)) => i,
+            Some(Err(e)) => {
+                debug!("Could not fetch the info of server {}: {}", server.id, e);
+                continue;
+            },
+            None => continue,
+        };
+        try!(diesel::update(game_servers.filter(id.eq(server.id)))
+                    .set((current_map.eq(normalize_map(&info.map)),
+                          password_protected.eq(info.password),
+                          vac_secured.eq(info.vac),
+                          bots.eq(info.bots as i32),
+                          dedicated.eq(info.server_type == 'd'),
+                          os.eq(operating_system(info.environment)),
+                          version.eq(Some(info.version.clone()))))
+                    .execute(conn));
+        try!(trust::record_check(conn, server, info.players as i32));
+        if verification::name_shows_token(server, &info.name) {
+            try!(verification::mark_verified(conn, server, "name"));
+        }
+    }
+    flag_outdated(conn, None)
+}
+
+/// Flags the servers running an older version than the newest one seen for their game type,
+/// of every game type or only of `only_game_type`. Versions are compared part by part as
+/// numbers, ignoring trailing zeros so that `1.2` is `1.2.0`. Versions that are not made of
+/// dot separated numbers are never outdated. Only servers whose flag changes are written.
+pub fn flag_outdated(conn: &PgConnection, only_game_type: Option<&str>) -> QueryResult<()> {
+    let condition = match only_game_type {
+        Some(g) => format!("AND game_type = {}", quote_literal(g)),
+        None => String::new(),
+    };
+    let servers = match only_game_type {
+        Some(g) => format!("WHERE g.game_type = {}", quote_literal(g)),
+        None => String::new(),
+    };
+    conn.execute(&format!("
+        WITH parsed AS (
+            SELECT id, game_type,
+                   string_to_array(regexp_replace(version, '(\\.0+)+$', ''), '.')::int[] AS parts
+            FROM game_servers
+            WHERE version ~ '^[0-9]{{1,9}}(\\.[0-9]{{1,9}})*$' {0}
+        ), newest AS (
+            SELECT game_type, max(parts) AS parts
+            FROM parsed
+            GROUP BY game_type
+        ), flags AS (
+            SELECT g.id, coalesce(p.parts < n.parts, false) AS outdated
+            FROM game_servers AS g
+            LEFT JOIN parsed AS p ON p.id = g.id
+            LEFT JOIN newest AS n ON n.game_type = p.game_type
+            {1}
+        )
+        UPDATE game_servers SET outdated = flags.outdated
+        FROM flags
+        WHERE flags.id = game_servers.id
+          AND game_servers.outdated IS DISTINCT FROM flags.outdated", condition, servers)).map(|_| ())
+}
+
+/// The `GameServer::os` of an A2S_INFO environment.
+fn operating_system(environment: char) -> Option<String> {
+    match environment {
+        'l' => Some("linux".into()),
+        'w' => Some("windows".into()),
+        'm' | 'o' => Some("mac".into()),
+        _ => None,
+    }
+}
+
+/// Maps are stored lowercased, so that `de_dust2` and `DE_DUST2` are the same map.
+pub fn normalize_map(map: &str) -> Option<String> {
+    let map = map.trim();
+    if map.is_empty() {
+        None
+    } else {
+        Some(map.to_lowercase())
+    }
+}
@@ -0,0 +1,201 @@
+//! Detection of servers faking their player counts to climb the listings.
+//!
+//! Every time the `jobs` thread queries a server with A2S_INFO, the player count it answered
+//! is kept next to the one it last reported in a heartbeat. From those checks, and from the
+//! player count history of the last day, every server is scored on how far its reported
+//! player counts can be trusted:
+//!
+//! * heartbeats claiming more players than the server answers to A2S_INFO,
+//! * player counts that never change, as bots holding slots do,
+//! * and servers that are always exactly full.
+//!
+//! Servers scoring below `SUSPICIOUS_BELOW` are flagged, and reported to the admins as
+//! `fake_players`. When `DEMOTE_SUSPICIOUS_SERVERS` is set, flagged servers are also moved
+//! to the end of everything `search::load` lists: search results, `GET /server` and master
+//! server replies.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::env;
+
+use diesel;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use diesel::expression::dsl::sql;
+use diesel::types::{BigInt, Bool, Float, Integer, Text};
+
+use ::models::{GameServer, NewPlayerCountCheck, NewReport};
+use ::moderation::APPROVED;
+use ::reports;
+
+/// How far back checks and history are looked at.
+const WINDOW: &'static str = "24 hours";
+/// How long checks are kept. `ranking` counts a week of them towards uptime.
+const CHECK_RETENTION: &'static str = "7 days";
+/// A heartbeat can be ahead of A2S_INFO by this many players, or a tenth of the players
+/// A2S_INFO counted, whichever is more, since players join between the two.
+const MISMATCH_SLACK: i32 = 2;
+/// Checks it takes before mismatches are counted against a server.
+const MIN_CHECKS: i64 = 10;
+/// Hours of history it takes to call a player count flat or always full.
+const MIN_HOURS: i64 = 12;
+/// The share of samples a server must be full in to be called always full.
+const ALWAYS_FULL_SHARE: f32 = 0.9;
+
+/// What each signal takes off a perfect score of 1. Mismatches count by their share of checks.
+const MISMATCH_WEIGHT: f32 = 0.7;
+const FLAT_LINE_PENALTY: f32 = 0.3;
+const ALWAYS_FULL_PENALTY: f32 = 0.3;
+/// Servers scoring below this are flagged as suspicious.
+pub const SUSPICIOUS_BELOW: f32 = 0.5;
+
+/// What a server's trust score was computed from.
+#[derive(Debug, Clone, RustcEncodable)]
+pub struct TrustSignals {
+    pub server_id: i32,
+    pub name: String,
+    /// Checks in the last day, and how many of them caught the heartbeat claiming more
+    /// players than A2S_INFO.
+    pub checks: i64,
+    pub mismatches: i64,
+    /// Whether the hourly player counts did not change at all over the last day.
+    pub flat_lined: bool,
+    /// The share of heartbeats in the last day that reported the server full.
+    pub full_share: f32,
+    pub trust_score: f32,
+}
+
+impl TrustSignals {
+    fn score(&self) -> f32 {
+        let mut score = 1.0;
+        if self.checks >= MIN_CHECKS {
+            score -= MISMATCH_WEIGHT * self.mismatches as f32 / self.checks as f32;
+        }
+        if self.flat_lined {
+            score -= FLAT_LINE_PENALTY;
+        }
+        if self.full_share >= ALWAYS_FULL_SHARE {
+            score -= ALWAYS_FULL_PENALTY;
+        }
+        if score < 0.0 { 0.0 } else { score }
+    }
+
+    /// Why the server is suspicious, for moderators.
+    fn describe(&self) -> String {
+        let mut reasons = vec![];
+        if self.checks >= MIN_CHECKS && self.mismatches > 0 {
+            reasons.push(format!("{} of {} A2S checks found fewer players than the heartbeats reported",
+                                 self.mismatches, self.checks));
+        }
+        if self.flat_lined {
+            reasons.push("the player count has not changed for a day".into());
+        }
+        if self.full_share >= ALWAYS_FULL_SHARE {
+            reasons.push(format!("the server was reported full {:.0}% of the time", self.full_share * 100.0));
+        }
+        format!("Trust score {:.2}: {}.", self.trust_score, reasons.join(", "))
+    }
+}
+
+/// Whether flagged servers go to the end of search results.
+pub fn demote_suspicious() -> bool {
+    env::var("DEMOTE_SUSPICIOUS_SERVERS").is_ok()
+}
+
+/// Keeps what a server answered to A2S_INFO next to what it last reported.
+pub fn record_check(conn: &PgConnection, server: &GameServer, queried_users: i32) -> QueryResult<()> {
+    use ::schema::player_count_checks;
+
+    let check = NewPlayerCountCheck {
+        server_id: server.id,
+        reported_users: server.current_users,
+        queried_users: queried_users,
+        max_users: server.max_users,
+    };
+    diesel::insert(&check).into(player_count_checks::table).execute(conn).map(|_| ())
+}
+
+/// The signals of every approved server, or only of the flagged ones, least trusted first.
+pub fn load_signals(conn: &PgConnection, only_suspicious: bool) -> QueryResult<Vec<TrustSignals>> {
+    let condition = if only_suspicious { "AND s.suspicious" } else { "" };
+    let rows = try!(sql::<(Integer, Text, BigInt, BigInt, Bool, Float)>(&format!(
+        "SELECT s.id, s.name, coalesce(c.checks, 0), coalesce(c.mismatches, 0),
+                coalesce(h.flat_lined, false), coalesce(f.full_share, 0)::real
+         FROM game_servers AS s
+         LEFT JOIN (
+             SELECT server_id, count(*) AS checks,
+                    count(*) FILTER (WHERE reported_users >
+                                           queried_users + greatest({slack}, queried_users / 10)) AS mismatches
+             FROM player_count_checks
+             WHERE checked_at > now() - interval '{window}'
+             GROUP BY server_id
+         ) AS c ON c.server_id = s.id
+         LEFT JOIN (
+             SELECT server_id,
+                    count(*) >= {hours} AND min(min_users) = max(max_users) AND max(max_users) > 0 AS flat_lined
+             FROM player_count_rollups
+             WHERE resolution = 'hour' AND bucket > now() - interval '{window}'
+             GROUP BY server_id
+         ) AS h ON h.server_id = s.id
+         LEFT JOIN (
+             SELECT p.server_id, avg((p.current_users >= g.max_users)::int) AS full_share
+             FROM player_counts AS p JOIN game_servers AS g ON g.id = p.server_id
+             WHERE p.recorded_at > now() - interval '{window}' AND g.max_users > 0
+             GROUP BY p.server_id
+             HAVING count(DISTINCT date_trunc('hour', p.recorded_at)) >= {hours}
+         ) AS f ON f.server_id = s.id
+         WHERE s.status = '{approved}' {condition}",
+        slack = MISMATCH_SLACK, window = WINDOW, hours = MIN_HOURS, approved = APPROVED,
+        condition = condition))
+        .load::<(i32, String, i64, i64, bool, f32)>(conn));
+    let mut signals: Vec<TrustSignals> = rows.into_iter().map(|(i, n, c, m, fl, fu)| {
+        let mut s = TrustSignals {
+            server_id: i,
+            name: n,
+            checks: c,
+            mismatches: m,
+            flat_lined: fl,
+            full_share: fu,
+            trust_score: 1.0,
+        };
+        s.trust_score = s.score();
+        s
+    }).collect();
+    signals.sort_by(|a, b| a.trust_score.partial_cmp(&b.trust_score).unwrap_or(Ordering::Equal));
+    Ok(signals)
+}
+
+/// Scores every approved server, and reports those newly found suspicious to the admins.
+pub fn score_all(conn: &PgConnection) -> QueryResult<()> {
+    use ::schema::game_servers::dsl::*;
+
+    try!(conn.execute(&format!(
+        "DELETE FROM player_count_checks WHERE checked_at < now() - interval '{}'", CHECK_RETENTION)));
+    let servers: HashMap<i32, GameServer> = try!(game_servers.filter(status.eq(APPROVED))
+                                                             .load::<GameServer>(conn))
+                                                .into_iter().map(|s| (s.id, s)).collect();
+    for signals in try!(load_signals(conn, false)) {
+        let server = match servers.get(&signals.server_id) {
+            Some(s) => s,
+            None => continue,
+        };
+        let flagged = signals.trust_score < SUSPICIOUS_BELOW;
+        if server.trust_score == signals.trust_score && server.suspicious == flagged {
+            continue;
+        }
+        try!(diesel::update(game_servers.filter(id.eq(server.id)))
+                    .set((trust_score.eq(signals.trust_score), suspicious.eq(flagged)))
+                    .execute(conn));
+        if flagged && !server.suspicious {
+            info!("Server {} looks like it fakes its player count: {}", server.id, signals.describe());
+            let report = NewReport {
+                server_id: server.id,
+                reporter_id: None,
+                category: "fake_players".into(),
+                detail: signals.describe(),
+            };
+            try!(reports::submit(conn, server, &report));
+        }
+    }
+    Ok(())
+}
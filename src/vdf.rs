@@ -0,0 +1,332 @@
+//! Reading and writing Valve's KeyValues (VDF) text format, as used by the Steam server
+//! browser for its favorites and history files.
+//!
+//! A document is a list of `"key" "value"` pairs, where a value is either a string or a
+//! `{ ... }` section of more pairs. Keys may repeat and their order is kept.
+
+use std::ascii::AsciiExt;
+use std::fmt;
+
+/// How deep sections can be nested. Steam's own files never go past a handful of levels.
+pub const MAX_DEPTH: usize = 32;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Str(String),
+    Section(Vec<(String, Value)>),
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Value::Str(ref s) => Some(s),
+            Value::Section(_) => None,
+        }
+    }
+
+    pub fn as_section(&self) -> Option<&[(String, Value)]> {
+        match *self {
+            Value::Str(_) => None,
+            Value::Section(ref pairs) => Some(pairs),
+        }
+    }
+
+    /// The first value of a section with the given key, ignoring case like Steam does.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.as_section().and_then(|pairs| {
+            pairs.iter().find(|&&(ref k, _)| k.eq_ignore_ascii_case(key)).map(|&(_, ref v)| v)
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Str(String),
+    Open,
+    Close,
+}
+
+/// Parses a document into its top level pairs.
+pub fn parse(text: &str) -> Result<Vec<(String, Value)>, ParseError> {
+    let mut lexer = Lexer { chars: text.chars().collect(), pos: 0, line: 1 };
+    let pairs = try!(parse_pairs(&mut lexer, 0));
+    match try!(lexer.next_token()) {
+        None => Ok(pairs),
+        Some(_) => Err(ParseError { line: lexer.line, message: "unexpected `}`".into() }),
+    }
+}
+
+/// Parses pairs up to a `}` or the end of the document, leaving the `}` unread. `depth` is
+/// how many sections the pairs are inside of.
+fn parse_pairs(lexer: &mut Lexer, depth: usize) -> Result<Vec<(String, Value)>, ParseError> {
+    let mut pairs = vec![];
+    loop {
+        match try!(lexer.peek_token()) {
+            None | Some(Token::Close) => return Ok(pairs),
+            _ => {},
+        }
+        let key = match try!(lexer.next_token()) {
+            Some(Token::Str(k)) => k,
+            _ => return Err(ParseError { line: lexer.line, message: "expected a key".into() }),
+        };
+        let value = match try!(lexer.next_token()) {
+            Some(Token::Str(v)) => Value::Str(v),
+            Some(Token::Open) => {
+                if depth >= MAX_DEPTH {
+                    return Err(ParseError {
+                        line: lexer.line,
+                        message: format!("sections are nested more than {} deep", MAX_DEPTH),
+                    });
+                }
+                let section = try!(parse_pairs(lexer, depth + 1));
+                match try!(lexer.next_token()) {
+                    Some(Token::Close) => Value::Section(section),
+                    _ => {
+                        return Err(ParseError {
+                            line: lexer.line,
+                            message: format!("section `{}` is never closed", key),
+                        });
+                    }
+                }
+            },
+            _ => {
+                return Err(ParseError {
+                    line: lexer.line,
+                    message: format!("expected a value or section after `{}`", key),
+                });
+            }
+        };
+        // Platform conditionals like `[$WIN32]` after a pair are ignored.
+        lexer.skip_conditional();
+        pairs.push((key, value));
+    }
+}
+
+struct Lexer {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Lexer {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek();
+        if c == Some('\n') {
+            self.line += 1;
+        }
+        self.pos += 1;
+        c
+    }
+
+    /// Skips whitespace and `//` comments.
+    fn skip_blank(&mut self) {
+        loop {
+            match self.peek() {
+                Some(c) if c.is_whitespace() => { self.bump(); },
+                Some('/') if self.chars.get(self.pos + 1) == Some(&'/') => {
+                    while self.peek().map_or(false, |c| c != '\n') {
+                        self.bump();
+                    }
+                },
+                _ => return,
+            }
+        }
+    }
+
+    fn skip_conditional(&mut self) {
+        let start = (self.pos, self.line);
+        self.skip_blank();
+        if self.peek() == Some('[') {
+            while self.peek().map_or(false, |c| c != ']' && c != '\n') {
+                self.bump();
+            }
+            if self.peek() == Some(']') {
+                self.bump();
+                return;
+            }
+        }
+        self.pos = start.0;
+        self.line = start.1;
+    }
+
+    fn peek_token(&mut self) -> Result<Option<Token>, ParseError> {
+        let start = (self.pos, self.line);
+        let token = self.next_token();
+        self.pos = start.0;
+        self.line = start.1;
+        token
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>, ParseError> {
+        self.skip_blank();
+        match self.peek() {
+            None => Ok(None),
+            Some('{') => { self.bump(); Ok(Some(Token::Open)) },
+            Some('}') => { self.bump(); Ok(Some(Token::Close)) },
+            Some('"') => {
+                let line = self.line;
+                self.bump();
+                let mut s = String::new();
+                loop {
+                    match self.bump() {
+                        None => {
+                            return Err(ParseError { line: line, message: "string is never closed".into() });
+                        },
+                        Some('"') => return Ok(Some(Token::Str(s))),
+                        Some('\\') => match self.bump() {
+                            Some('n') => s.push('\n'),
+                            Some('t') => s.push('\t'),
+                            Some(c) => s.push(c),
+                            None => {
+                                return Err(ParseError { line: line, message: "string is never closed".into() });
+                            }
+                        },
+                        Some(c) => s.push(c),
+                    }
+                }
+            },
+            Some(_) => {
+                let mut s = String::new();
+                while let Some(c) = self.peek() {
+                    if c.is_whitespace() || c == '{' || c == '}' || c == '"' {
+                        break;
+                    }
+                    s.push(c);
+                    self.bump();
+                }
+                Ok(Some(Token::Str(s)))
+            }
+        }
+    }
+}
+
+/// Writes pairs as a document, indented with tabs like Steam writes them.
+pub fn write(pairs: &[(String, Value)]) -> String {
+    let mut out = String::new();
+    write_pairs(&mut out, pairs, 0);
+    out
+}
+
+fn write_pairs(out: &mut String, pairs: &[(String, Value)], depth: usize) {
+    let indent: String = (0..depth).map(|_| '\t').collect();
+    for &(ref key, ref value) in pairs {
+        match *value {
+            Value::Str(ref s) => {
+                out.push_str(&format!("{}{}\t\t{}\n", indent, quote(key), quote(s)));
+            },
+            Value::Section(ref inner) => {
+                out.push_str(&format!("{}{}\n{}{{\n", indent, quote(key), indent));
+                write_pairs(out, inner, depth + 1);
+                out.push_str(&format!("{}}}\n", indent));
+            }
+        }
+    }
+}
+
+fn quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair(key: &str, value: Value) -> (String, Value) {
+        (key.into(), value)
+    }
+
+    fn string(s: &str) -> Value {
+        Value::Str(s.into())
+    }
+
+    #[test]
+    fn parses_steam_favorites() {
+        let text = "\"Filters\"\n{\n\t\"Favorites\"\n\t{\n\t\t\"1\"\n\t\t{\n\
+                    \t\t\t\"name\"\t\t\"Fula Dust\"\n\t\t\t\"address\"\t\t\"10.0.0.1:27015\"\n\
+                    \t\t}\n\t}\n}\n";
+        let document = parse(text).unwrap();
+        let favorites = document[0].1.get("favorites").unwrap();
+        let first = favorites.get("1").unwrap();
+        assert_eq!(first.get("name"), Some(&string("Fula Dust")));
+        assert_eq!(first.get("ADDRESS").and_then(|a| a.as_str()), Some("10.0.0.1:27015"));
+    }
+
+    #[test]
+    fn round_trips() {
+        let document = vec![
+            pair("Filters", Value::Section(vec![
+                pair("quote", string("say \"hi\"")),
+                pair("path", string("C:\\steam\\")),
+                pair("lines", string("one\ntwo\tthree")),
+                pair("empty", string("")),
+                pair("nested", Value::Section(vec![pair("", Value::Section(vec![]))])),
+                pair("quote", string("repeated keys stay")),
+            ])),
+            pair("unicode", string("sérveur ☃")),
+        ];
+        assert_eq!(parse(&write(&document)).unwrap(), document);
+    }
+
+    #[test]
+    fn skips_comments_and_conditionals() {
+        let text = "// written by hand\n\"a\" \"1\" [$WIN32]\n\"b\" { \"c\" d } // done";
+        assert_eq!(parse(text).unwrap(), vec![
+            pair("a", string("1")),
+            pair("b", Value::Section(vec![pair("c", string("d"))])),
+        ]);
+    }
+
+    #[test]
+    fn reports_where_it_failed() {
+        assert_eq!(parse("\"a\"\n\"b\n\n").unwrap_err(),
+                   ParseError { line: 2, message: "string is never closed".into() });
+        assert_eq!(parse("\"a\" \"b\"\n}").unwrap_err().message, "unexpected `}`");
+        assert_eq!(parse("\"a\" {\n\"b\" \"c\"\n").unwrap_err().message,
+                   "section `a` is never closed");
+        assert_eq!(parse("\"a\"").unwrap_err().message, "expected a value or section after `a`");
+        assert_eq!(parse("{ }").unwrap_err().message, "expected a key");
+        assert_eq!(parse("\"a\" \"b\\").unwrap_err().message, "string is never closed");
+    }
+
+    #[test]
+    fn limits_nesting() {
+        let nested = |depth: usize| {
+            let open: String = (0..depth).map(|_| "k { ").collect();
+            let close: String = (0..depth).map(|_| "} ").collect();
+            format!("{}{}", open, close)
+        };
+        assert!(parse(&nested(MAX_DEPTH)).is_ok());
+        assert_eq!(parse(&nested(MAX_DEPTH + 1)).unwrap_err().message,
+                   format!("sections are nested more than {} deep", MAX_DEPTH));
+        // Deep enough to overflow the stack without the limit.
+        assert!(parse(&nested(1000000)).is_err());
+    }
+}
@@ -0,0 +1,106 @@
+//! Proving that whoever listed a server runs it.
+//!
+//! Every listing gets a token such as `fula-1a2b3c4d5e6f7a8b`. The listing is verified once the
+//! token shows up in what the server reports about itself: the value of any rule (for example
+//! `sv_tags` or a `fula_verify` cvar), or its name (the `hostname` cvar). The `jobs` thread
+//! looks for it whenever it queries servers, and owners can ask for a check right away.
+
+use std::io;
+use std::time::Duration;
+
+use diesel;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use rand::{OsRng, Rng};
+
+use ::a2s::{self, A2sError};
+use ::audit;
+use ::models::GameServer;
+
+/// Start of every token, so owners can tell what it is for in their configs.
+const TOKEN_PREFIX: &'static str = "fula-";
+/// Random characters after the prefix.
+const TOKEN_LENGTH: usize = 16;
+/// Random characters of a heartbeat secret.
+const HEARTBEAT_SECRET_LENGTH: usize = 32;
+/// How long to wait on a game server when an owner asks for a check.
+const QUERY_TIMEOUT_SECS: u64 = 2;
+
+/// A new, random verification token.
+pub fn new_token() -> io::Result<String> {
+    let mut rng = try!(OsRng::new());
+    let random: String = rng.gen_ascii_chars().take(TOKEN_LENGTH).collect();
+    Ok(format!("{}{}", TOKEN_PREFIX, random.to_lowercase()))
+}
+
+/// A new, random heartbeat secret. Unlike the token, it is never shown by the server, so
+/// it is longer and keeps its case.
+pub fn new_heartbeat_secret() -> io::Result<String> {
+    let mut rng = try!(OsRng::new());
+    Ok(rng.gen_ascii_chars().take(HEARTBEAT_SECRET_LENGTH).collect())
+}
+
+/// Whether `text` shows the token. Games may change the case of tags, so case is ignored.
+fn shows_token(token: &str, text: &str) -> bool {
+    text.to_lowercase().contains(&token.to_lowercase())
+}
+
+/// Whether any of the rules a server reported shows its token.
+pub fn rules_show_token(server: &GameServer, rules: &[(String, String)]) -> bool {
+    rules.iter().any(|&(_, ref value)| shows_token(&server.verification_token, value))
+}
+
+/// Whether the name a server reported shows its token.
+pub fn name_shows_token(server: &GameServer, name: &str) -> bool {
+    shows_token(&server.verification_token, name)
+}
+
+/// Marks a listing verified. `source` says where the token was seen, for the audit log.
+pub fn mark_verified(conn: &PgConnection, server: &GameServer, source: &str) -> QueryResult<()> {
+    use ::schema::game_servers::dsl::*;
+
+    if server.verified {
+        return Ok(());
+    }
+    try!(diesel::update(game_servers.filter(id.eq(server.id)))
+                .set(verified.eq(true))
+                .execute(conn));
+    info!("Server {} is verified, its token was seen in its {}.", server.id, source);
+    audit::record(conn, server.owner_id, Some(server.id), "verify_server",
+                  &format!("token seen in {} at {}", source, server.endpoint()))
+}
+
+#[derive(Debug)]
+pub enum CheckError {
+    /// The server is not listed by an address that can be queried.
+    NoAddress,
+    Query(A2sError),
+    Db(diesel::result::Error),
+}
+
+/// Queries a server for its rules and name right away, and marks it verified if either
+/// shows its token. Returns whether the server is verified.
+pub fn check(conn: &PgConnection, server: &GameServer) -> Result<bool, CheckError> {
+    if server.verified {
+        return Ok(true);
+    }
+    let address = match server.socket_address() {
+        Some(a) => a,
+        None => return Err(CheckError::NoAddress),
+    };
+    let timeout = Duration::from_secs(QUERY_TIMEOUT_SECS);
+
+    // Servers may refuse A2S_RULES, so the name is still worth a look when they do.
+    let source = match a2s::rules(address, timeout) {
+        Ok(ref rules) if rules_show_token(server, rules) => Some("rules"),
+        _ => match a2s::info(address, timeout) {
+            Ok(ref info) if name_shows_token(server, &info.name) => Some("name"),
+            Ok(_) => None,
+            Err(e) => return Err(CheckError::Query(e)),
+        },
+    };
+    match source {
+        Some(s) => mark_verified(conn, server, s).map(|_| true).map_err(CheckError::Db),
+        None => Ok(false),
+    }
+}